@@ -42,6 +42,10 @@ pub enum EvmApiMethod {
     AccountCodeHash,
     AddPages,
     CaptureHostIO,
+    GetTransientBytes32,
+    SetTransientBytes32,
+    BlockHash,
+    BlobHash,
 }
 
 /// This offset is added to EvmApiMethod when sending a request
@@ -85,6 +89,16 @@ pub trait EvmApi<D: DataReader>: Send + 'static {
     /// Analogous to repeated invocations of `vm.SSTORE`.
     fn flush_storage_cache(&mut self, clear: bool, gas_left: u64) -> Result<u64>;
 
+    /// Reads the 32-byte value in the EVM's transient storage at offset `key`.
+    /// Returns the value and the access cost in gas.
+    /// Analogous to `vm.TLOAD`.
+    fn get_transient_bytes32(&mut self, key: Bytes32) -> (Bytes32, u64);
+
+    /// Writes the 32-byte value to the EVM's transient storage at offset `key`.
+    /// Returns the access cost in gas, or an error message on failure.
+    /// Analogous to `vm.TSTORE`.
+    fn set_transient_bytes32(&mut self, key: Bytes32, value: Bytes32) -> Result<u64>;
+
     /// Calls the contract at the given address.
     /// Returns the EVM return data's length, the gas cost, and whether the call succeeded.
     /// Analogous to `vm.CALL`.
@@ -162,6 +176,16 @@ pub trait EvmApi<D: DataReader>: Send + 'static {
     /// Analogous to `vm.EXTCODEHASH`.
     fn account_codehash(&mut self, address: Bytes20) -> (Bytes32, u64);
 
+    /// Gets the hash of one of the 256 most recent complete blocks.
+    /// Returns the hash, which is zero when out of range, and the access cost in gas.
+    /// Analogous to `vm.BLOCKHASH`.
+    fn block_hash(&mut self, number: u64) -> (Bytes32, u64);
+
+    /// Gets the versioned hash of the current transaction's blob at the given index.
+    /// Returns the hash, which is zero when out of range, and the access cost in gas.
+    /// Analogous to `vm.BLOBHASH`.
+    fn blob_hash(&mut self, index: u32) -> (Bytes32, u64);
+
     /// Determines the cost in gas of allocating additional wasm pages.
     /// Note: has the side effect of updating Geth's memory usage tracker.
     /// Not analogous to any EVM opcode.
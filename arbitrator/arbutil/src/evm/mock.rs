@@ -0,0 +1,26 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+//! Pretend chain data for the mock EVMs of `stylus` and `user-test`, which must agree so that
+//! native and machine runs of a program produce the same output.
+
+use crate::{crypto, Bytes32};
+
+/// The pretend hash of a block, which is zero unless among the 256 before the current one.
+pub fn block_hash(number: u64, current: u64) -> Bytes32 {
+    let lower = current.saturating_sub(256);
+    match (lower..current).contains(&number) {
+        true => crypto::keccak(number.to_be_bytes()).into(),
+        false => Bytes32::default(),
+    }
+}
+
+/// The versioned hash of a pretend blob, of which each tx carries two.
+pub fn blob_hash(index: u32) -> Bytes32 {
+    if index >= 2 {
+        return Bytes32::default();
+    }
+    let mut hash = crypto::keccak(index.to_be_bytes());
+    hash[0] = 0x01; // the KZG version
+    hash.into()
+}
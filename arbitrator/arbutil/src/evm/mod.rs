@@ -4,6 +4,7 @@
 use crate::{Bytes20, Bytes32};
 
 pub mod api;
+pub mod mock;
 pub mod req;
 pub mod storage;
 pub mod user;
@@ -20,6 +21,12 @@ pub const COLD_SLOAD_GAS: u64 = 2100;
 // params.WarmStorageReadCostEIP2929
 pub const WARM_SLOAD_GAS: u64 = 100;
 
+// params.WarmStorageReadCostEIP2929 (see eips.go)
+pub const TLOAD_GAS: u64 = WARM_SLOAD_GAS;
+
+// params.WarmStorageReadCostEIP2929 (see eips.go)
+pub const TSTORE_GAS: u64 = WARM_SLOAD_GAS;
+
 // params.LogGas and params.LogDataGas
 pub const LOG_TOPIC_GAS: u64 = 375;
 pub const LOG_DATA_GAS: u64 = 8;
@@ -34,12 +41,27 @@ pub const KECCAK_WORD_GAS: u64 = 6;
 // vm.GasQuickStep (see gas.go)
 pub const GAS_QUICK_STEP: u64 = 2;
 
+// vm.GasFastestStep (see gas.go)
+pub const GAS_FASTEST_STEP: u64 = 3;
+
+// vm.GasExtStep (see gas.go)
+pub const GAS_EXT_STEP: u64 = 20;
+
 // vm.GasQuickStep (see jump_table.go)
 pub const ADDRESS_GAS: u64 = GAS_QUICK_STEP;
 
 // vm.GasQuickStep (see eips.go)
 pub const BASEFEE_GAS: u64 = GAS_QUICK_STEP;
 
+// vm.GasQuickStep (see eips.go)
+pub const BLOBBASEFEE_GAS: u64 = GAS_QUICK_STEP;
+
+// vm.GasFastestStep (see eips.go)
+pub const BLOBHASH_GAS: u64 = GAS_FASTEST_STEP;
+
+// vm.GasExtStep (see jump_table.go)
+pub const BLOCKHASH_GAS: u64 = GAS_EXT_STEP;
+
 // vm.GasQuickStep (see eips.go)
 pub const CHAINID_GAS: u64 = GAS_QUICK_STEP;
 
@@ -52,6 +74,9 @@ pub const GASLIMIT_GAS: u64 = GAS_QUICK_STEP;
 // vm.GasQuickStep (see jump_table.go)
 pub const NUMBER_GAS: u64 = GAS_QUICK_STEP;
 
+// vm.GasQuickStep (see jump_table.go)
+pub const PREVRANDAO_GAS: u64 = GAS_QUICK_STEP;
+
 // vm.GasQuickStep (see jump_table.go)
 pub const TIMESTAMP_GAS: u64 = GAS_QUICK_STEP;
 
@@ -74,10 +99,12 @@ pub const ORIGIN_GAS: u64 = GAS_QUICK_STEP;
 #[repr(C)]
 pub struct EvmData {
     pub block_basefee: Bytes32,
+    pub block_blob_basefee: Bytes32,
     pub chainid: u64,
     pub block_coinbase: Bytes20,
     pub block_gas_limit: u64,
     pub block_number: u64,
+    pub block_prevrandao: Bytes32,
     pub block_timestamp: u64,
     pub contract_address: Bytes20,
     pub msg_sender: Bytes20,
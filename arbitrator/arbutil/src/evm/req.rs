@@ -144,6 +144,23 @@ impl<D: DataReader, H: RequestHandler<D>> EvmApi<D> for EvmApiRequestor<D, H> {
         Ok(cost)
     }
 
+    fn get_transient_bytes32(&mut self, key: Bytes32) -> (Bytes32, u64) {
+        let (res, _, cost) = self.request(EvmApiMethod::GetTransientBytes32, key);
+        (res.try_into().unwrap(), cost)
+    }
+
+    fn set_transient_bytes32(&mut self, key: Bytes32, value: Bytes32) -> Result<u64> {
+        let mut data = Vec::with_capacity(2 * 32);
+        data.extend(key);
+        data.extend(value);
+
+        let (res, _, cost) = self.request(EvmApiMethod::SetTransientBytes32, data);
+        if res[0] != EvmApiStatus::Success.into() {
+            bail!("{}", String::from_utf8_or_hex(res));
+        }
+        Ok(cost)
+    }
+
     fn contract_call(
         &mut self,
         contract: Bytes20,
@@ -245,6 +262,16 @@ impl<D: DataReader, H: RequestHandler<D>> EvmApi<D> for EvmApiRequestor<D, H> {
         (res.try_into().unwrap(), cost)
     }
 
+    fn block_hash(&mut self, number: u64) -> (Bytes32, u64) {
+        let (res, _, cost) = self.request(EvmApiMethod::BlockHash, number.to_be_bytes());
+        (res.try_into().unwrap(), cost)
+    }
+
+    fn blob_hash(&mut self, index: u32) -> (Bytes32, u64) {
+        let (res, _, cost) = self.request(EvmApiMethod::BlobHash, index.to_be_bytes());
+        (res.try_into().unwrap(), cost)
+    }
+
     fn add_pages(&mut self, pages: u16) -> u64 {
         self.request(EvmApiMethod::AddPages, pages.to_be_bytes()).2
     }
@@ -1,9 +1,14 @@
 // Copyright 2021-2023, Offchain Labs, Inc.
 // For license information, see https://github.com/nitro/blob/master/LICENSE
 
+use eyre::{bail, ErrReport};
+use fnv::FnvHashMap as HashMap;
+use lazy_static::lazy_static;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
 use std::fmt::{Debug, Display, Formatter};
 use std::hash::Hash;
+use std::str::FromStr;
 use wasmparser::Operator;
 
 #[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -12,6 +17,11 @@ pub struct OperatorCode(usize);
 impl OperatorCode {
     // TODO: use std::mem::variant_count when it's stabilized
     pub const OPERATOR_COUNT: usize = 529;
+
+    /// Whether the operator is part of the SIMD proposal.
+    pub fn is_simd(self) -> bool {
+        self.0 >> 8 == 0xfd || self.0 >> 12 == 0xfd
+    }
 }
 
 impl Display for OperatorCode {
@@ -556,6 +566,42 @@ impl Display for OperatorCode {
     }
 }
 
+lazy_static! {
+    /// Associates each operator's name with its code
+    static ref OP_NAMES: HashMap<String, OperatorCode> = {
+        let codes = (0x00..=0xff).chain(0xfc00..=0xfeff).chain(0xfd000..=0xfdfff);
+        codes
+            .map(OperatorCode)
+            .map(|code| (code.to_string(), code))
+            .filter(|(name, _)| name != "UNKNOWN")
+            .collect()
+    };
+}
+
+impl FromStr for OperatorCode {
+    type Err = ErrReport;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match OP_NAMES.get(name) {
+            Some(code) => Ok(*code),
+            None => bail!("unknown operator {name}"),
+        }
+    }
+}
+
+impl Serialize for OperatorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for OperatorCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(de::Error::custom)
+    }
+}
+
 impl<'a> From<Operator<'a>> for OperatorCode {
     fn from(op: Operator) -> Self {
         OperatorCode::from(&op)
@@ -219,10 +219,12 @@ pub fn create_stylus_config(
 pub fn create_evm_data(
     mut env: WasmEnvMut,
     block_basefee_ptr: GuestPtr,
+    block_blob_basefee_ptr: GuestPtr,
     chainid: u64,
     block_coinbase_ptr: GuestPtr,
     block_gas_limit: u64,
     block_number: u64,
+    block_prevrandao_ptr: GuestPtr,
     block_timestamp: u64,
     contract_address_ptr: GuestPtr,
     msg_sender_ptr: GuestPtr,
@@ -235,10 +237,12 @@ pub fn create_evm_data(
 
     let evm_data = EvmData {
         block_basefee: mem.read_bytes32(block_basefee_ptr),
+        block_blob_basefee: mem.read_bytes32(block_blob_basefee_ptr),
         chainid,
         block_coinbase: mem.read_bytes20(block_coinbase_ptr),
         block_gas_limit,
         block_number,
+        block_prevrandao: mem.read_bytes32(block_prevrandao_ptr),
         block_timestamp,
         contract_address: mem.read_bytes20(contract_address_ptr),
         msg_sender: mem.read_bytes20(msg_sender_ptr),
@@ -1,4 +1,5 @@
 #![no_main]
+use arbutil::Bytes32;
 use evm::{
     backend::MemoryAccount,
     executor::stack::{self as evm_stack, StackSubstateMetadata},
@@ -9,11 +10,11 @@ use primitive_types::{H160, U256};
 use prover::{
     binary,
     machine::{GlobalState, Machine},
-    utils::Bytes32,
+    verify::{self, ExecutionContext},
     wavm::Opcode,
 };
 use serde::Deserialize;
-use std::{collections::BTreeMap, fs::File, rc::Rc};
+use std::{collections::BTreeMap, fs::File, path::Path, rc::Rc};
 
 const MAX_STEPS: u64 = 200;
 const DEBUG: bool = false;
@@ -182,19 +183,23 @@ fn test_proof(
 }
 
 fn fuzz_impl(data: &[u8]) -> Result<()> {
-    let wavm_binary = binary::parse(data)?;
+    let wavm_binary = binary::parse(data, Path::new("user"))?;
     let mut mach = Machine::from_binaries(
         &[],
         wavm_binary,
         true,
         true,
         false,
+        false,
+        false,
         GlobalState::default(),
         Default::default(),
         prover::machine::get_empty_preimage_resolver(),
+        None,
     )?;
     let mut last_hash = mach.hash();
     while mach.get_steps() <= MAX_STEPS {
+        let context = ExecutionContext::new(&mach);
         let proof = mach.serialize_proof();
         let op = mach.get_next_instruction().map(|i| i.opcode);
         if DEBUG {
@@ -202,6 +207,12 @@ fn fuzz_impl(data: &[u8]) -> Result<()> {
         }
         mach.step_n(1).expect("Failed to execute machine step");
         let new_hash = mach.hash();
+        let verified = verify::verify(&context, last_hash, &proof).expect("Failed to verify proof");
+        assert_eq!(
+            verified, new_hash,
+            "executing {:?} verified to the wrong hash",
+            op
+        );
         test_proof(last_hash, mach.get_steps(), op, proof, new_hash)
             .expect("Failed to validate proof");
         if new_hash == last_hash {
@@ -19,12 +19,20 @@ use nom::{
     sequence::{preceded, tuple},
 };
 use serde::{Deserialize, Serialize};
-use std::{convert::TryInto, fmt::Debug, hash::Hash, mem, path::Path, str::FromStr};
+use std::{
+    borrow::Cow,
+    convert::TryInto,
+    fmt::{self, Debug, Display},
+    hash::Hash,
+    mem,
+    path::Path,
+    str::FromStr,
+};
 use wasmer_types::{entity::EntityRef, FunctionIndex, LocalFunctionIndex};
 use wasmparser::{
-    Data, Element, Export, ExternalKind, Global, Import, MemoryType, Name, NameSectionReader,
-    Naming, Operator, Parser, Payload, SectionReader, SectionWithLimitedItems, TableType, Type,
-    TypeRef, ValType, Validator, WasmFeatures,
+    BinaryReader, ConstExpr, Data, DataKind, Element, ElementKind, Export, ExternalKind, Global,
+    Import, MemoryType, Name, NameSectionReader, Naming, Operator, Parser, Payload, SectionReader,
+    SectionWithLimitedItems, TableType, Type, TypeRef, ValType, Validator, WasmFeatures,
 };
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -216,6 +224,47 @@ pub fn op_as_const(op: Operator) -> Result<Value> {
     }
 }
 
+/// Whether a constant expression is a single constant, as in the MVP.
+fn is_simple_const(expr: &ConstExpr) -> Result<bool> {
+    let mut ops = expr.get_operators_reader();
+    Ok(op_as_const(ops.read()?).is_ok() && matches!(ops.read()?, Operator::End))
+}
+
+/// Evaluates a constant expression, which the extended-const proposal allows to contain arithmetic.
+/// Since user programs cannot import globals, no `global.get` may appear.
+pub fn eval_const_expr(expr: &ConstExpr) -> Result<Value> {
+    use Operator::*;
+    let mut stack = vec![];
+    let mut ops = expr.get_operators_reader();
+
+    macro_rules! binary {
+        ($ty:ident, $op:ident) => {{
+            let (Some(Value::$ty(b)), Some(Value::$ty(a))) = (stack.pop(), stack.pop()) else {
+                bail!("mistyped constant expression")
+            };
+            Value::$ty(a.$op(b))
+        }};
+    }
+
+    loop {
+        let value = match ops.read()? {
+            End => break,
+            I32Add => binary!(I32, wrapping_add),
+            I32Sub => binary!(I32, wrapping_sub),
+            I32Mul => binary!(I32, wrapping_mul),
+            I64Add => binary!(I64, wrapping_add),
+            I64Sub => binary!(I64, wrapping_sub),
+            I64Mul => binary!(I64, wrapping_mul),
+            op => op_as_const(op)?,
+        };
+        stack.push(value);
+    }
+    match (stack.pop(), stack.is_empty(), ops.eof()) {
+        (Some(value), true, true) => Ok(value),
+        _ => bail!("malformed constant expression"),
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct FuncImport<'a> {
     pub offset: u32,
@@ -283,28 +332,187 @@ pub struct WasmBinary<'a> {
     pub codes: Vec<Code<'a>>,
     pub datas: Vec<Data<'a>>,
     pub names: NameCustomSection,
+    /// Whether any constant expression uses the extended-const proposal, and so needs folding.
+    pub extended_consts: bool,
+}
+
+/// How a limit enforced during activation is measured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitKind {
+    /// Pages of memory, bounded by what remains for the transaction.
+    Memory,
+    /// The number of some kind of item.
+    Count,
+    /// The length of a name.
+    Length,
+    /// A feature that may not be used at all.
+    Forbidden,
+}
+
+/// A limit enforced during activation and how much of it a program uses.
+#[derive(Clone, Debug)]
+pub struct LimitUsage {
+    pub kind: LimitKind,
+    pub name: &'static str,
+    pub limit: u64,
+    /// The program's usage, or that of its largest item for per-item limits.
+    pub actual: u64,
+    /// The usage of each item for limits that apply per function or export.
+    pub items: Vec<(Location, u64)>,
+}
+
+impl LimitUsage {
+    /// How far the program can grow before reaching the limit.
+    pub fn headroom(&self) -> u64 {
+        self.limit.saturating_sub(self.actual)
+    }
+
+    pub fn exceeded(&self) -> bool {
+        self.actual > self.limit
+    }
+
+    /// Lists each violation of this limit, one per offending item.
+    pub fn errors(&self) -> Vec<ActivationError> {
+        let error = |actual, location| ActivationError::Limit {
+            kind: self.kind,
+            name: self.name,
+            limit: self.limit,
+            actual,
+            location,
+        };
+        if !self.exceeded() {
+            return vec![];
+        }
+        if self.items.is_empty() {
+            return vec![error(self.actual, None)];
+        }
+        let items = self.items.iter().filter(|x| x.1 > self.limit);
+        let error = |(loc, actual): &(Location, u64)| error(*actual, Some(loc.clone()));
+        items.map(error).collect()
+    }
+}
+
+/// Where in a program an activation error occurs.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Location {
+    /// A function, by its index in the function index space.
+    Func(u32),
+    /// An export, by name.
+    Export(String),
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Func(func) => write!(f, "func {func}"),
+            Self::Export(name) => write!(f, "export {name}"),
+        }
+    }
+}
+
+/// A reason a user program cannot be activated.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivationError {
+    /// The program exceeds a limit.
+    Limit {
+        kind: LimitKind,
+        name: &'static str,
+        limit: u64,
+        actual: u64,
+        location: Option<Location>,
+    },
+    /// The program doesn't define a memory.
+    MissingMemory,
+    /// A required export doesn't exist.
+    MissingExport { name: String },
+    /// A required export isn't a function.
+    NotAFunc { name: String, kind: ExportKind },
+    /// A required function export has the wrong type.
+    WrongType { name: String, ty: FunctionType },
+    /// The program couldn't be instrumented.
+    Instrumentation { error: String },
+}
+
+impl Display for ActivationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ActivationError::*;
+        match self {
+            Limit {
+                kind,
+                name,
+                limit,
+                actual,
+                location,
+            } => {
+                let (red_name, red_actual) = (name.red(), actual.red());
+                let red_limit = limit.red();
+                match kind {
+                    LimitKind::Memory => {
+                        write!(f, "memory exceeds limit: {red_actual} > {red_limit}")?
+                    }
+                    LimitKind::Count => write!(f, "too many wasm {name}: {actual} > {limit}")?,
+                    LimitKind::Length => {
+                        write!(f, "wasm {red_name} too long: {red_actual} > {red_limit}")?
+                    }
+                    LimitKind::Forbidden => write!(f, "wasm {name} not allowed")?,
+                }
+                match location {
+                    Some(location) => write!(f, " in {location}"),
+                    None => Ok(()),
+                }
+            }
+            MissingMemory => write!(f, "missing memory with export name \"memory\""),
+            MissingExport { name } => write!(f, "missing export with name {}", name.red()),
+            NotAFunc { name, kind } => {
+                let (name, kind) = (name.red(), kind.debug_red());
+                write!(f, "export {name} must be a function but is a {kind}")
+            }
+            WrongType { name, ty } => write!(f, "wrong type for {}: {}", name.red(), ty.red()),
+            Instrumentation { error } => write!(f, "failed to instrument program: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ActivationError {}
+
+/// Every reason a user program cannot be activated.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ActivationErrors(pub Vec<ActivationError>);
+
+impl Display for ActivationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors: Vec<_> = self.0.iter().map(ToString::to_string).collect();
+        write!(f, "{}", errors.join("; "))
+    }
 }
 
+impl std::error::Error for ActivationErrors {}
+
+/// The wasm extensions Arbitrator supports.
+pub(crate) const FEATURES: WasmFeatures = WasmFeatures {
+    mutable_global: true,
+    saturating_float_to_int: true,
+    sign_extension: true,
+    reference_types: false,
+    multi_value: true,
+    bulk_memory: true, // except the table ops
+    simd: false,       // lowered beforehand
+    relaxed_simd: false,
+    threads: false,
+    tail_call: false,
+    deterministic_only: false,
+    multi_memory: false,
+    exceptions: false,
+    memory64: false,
+    extended_const: true,
+    component_model: false,
+};
+
 pub fn parse<'a>(input: &'a [u8], path: &'_ Path) -> Result<WasmBinary<'a>> {
-    let features = WasmFeatures {
-        mutable_global: true,
-        saturating_float_to_int: true,
-        sign_extension: true,
-        reference_types: false,
-        multi_value: true,
-        bulk_memory: true, // not all ops supported yet
-        simd: false,
-        relaxed_simd: false,
-        threads: false,
-        tail_call: false,
-        deterministic_only: false,
-        multi_memory: false,
-        exceptions: false,
-        memory64: false,
-        extended_const: false,
-        component_model: false,
-    };
-    Validator::new_with_features(features)
+    Validator::new_with_features(FEATURES)
         .validate_all(input)
         .wrap_err_with(|| eyre!("failed to validate {}", path.to_string_lossy().red()))?;
 
@@ -364,13 +572,10 @@ pub fn parse<'a>(input: &'a [u8], path: &'_ Path) -> Result<WasmBinary<'a>> {
             }
             GlobalSection(globals) => {
                 for global in flatten!(Global, globals) {
-                    let mut init = global.init_expr.get_operators_reader();
-
-                    let value = match (init.read()?, init.read()?, init.eof()) {
-                        (op, Operator::End, true) => op_as_const(op)?,
-                        _ => bail!("Non-constant global initializer"),
-                    };
+                    let value = eval_const_expr(&global.init_expr)
+                        .wrap_err("Non-constant global initializer")?;
                     binary.globals.push(value);
+                    binary.extended_consts |= !is_simple_const(&global.init_expr)?;
                 }
             }
             ImportSection(imports) => {
@@ -413,6 +618,7 @@ pub fn parse<'a>(input: &'a [u8], path: &'_ Path) -> Result<WasmBinary<'a>> {
             ElementSection(elements) => process!(binary.elements, elements),
             DataSection(datas) => process!(binary.datas, datas),
             CodeSectionStart { .. } => {}
+            DataCountSection { .. } => {} // validated against the data section
             CustomSection(reader) => {
                 if reader.name() != "name" {
                     continue;
@@ -440,6 +646,18 @@ pub fn parse<'a>(input: &'a [u8], path: &'_ Path) -> Result<WasmBinary<'a>> {
         }
     }
 
+    // note any extended offsets, which are evaluated upon instantiation
+    for data in &binary.datas {
+        if let DataKind::Active { offset_expr, .. } = &data.kind {
+            binary.extended_consts |= !is_simple_const(offset_expr)?;
+        }
+    }
+    for elem in &binary.elements {
+        if let ElementKind::Active { offset_expr, .. } = &elem.kind {
+            binary.extended_consts |= !is_simple_const(offset_expr)?;
+        }
+    }
+
     // reject the module if it imports the same func with inconsistent signatures
     let mut imports = HashMap::default();
     for import in &binary.imports {
@@ -488,6 +706,124 @@ pub fn parse<'a>(input: &'a [u8], path: &'_ Path) -> Result<WasmBinary<'a>> {
     Ok(binary)
 }
 
+/// Rewrites each extended constant expression as the single constant it evaluates to.
+/// This lets compilers that predate the extended-const proposal consume the binary.
+pub fn fold_const_exprs(input: &[u8]) -> Result<Cow<'_, [u8]>> {
+    let mut folds = vec![];
+
+    let mut fold = |expr: &ConstExpr| -> Result<()> {
+        if !is_simple_const(expr)? {
+            let value = eval_const_expr(expr)?;
+            let reader = expr.get_binary_reader();
+            let start = reader.original_position();
+            folds.push((start, start + reader.bytes_remaining(), value));
+        }
+        Ok(())
+    };
+
+    for payload in Parser::new(0).parse_all(input) {
+        use Payload::*;
+        match payload? {
+            GlobalSection(mut reader) => {
+                for _ in 0..reader.get_count() {
+                    fold(&reader.read()?.init_expr)?;
+                }
+            }
+            DataSection(mut reader) => {
+                for _ in 0..reader.get_count() {
+                    if let DataKind::Active { offset_expr, .. } = reader.read()?.kind {
+                        fold(&offset_expr)?;
+                    }
+                }
+            }
+            ElementSection(mut reader) => {
+                for _ in 0..reader.get_count() {
+                    if let ElementKind::Active { offset_expr, .. } = reader.read()?.kind {
+                        fold(&offset_expr)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if folds.is_empty() {
+        return Ok(Cow::Borrowed(input));
+    }
+
+    // splice the folds into their sections, whose sizes change
+    let mut reader = BinaryReader::new(input);
+    let mut output = reader.read_bytes(8)?.to_vec();
+    let mut folds = folds.into_iter().peekable();
+
+    while !reader.eof() {
+        let id = reader.read_u8()?;
+        let size = reader.read_var_u32()? as usize;
+        let mut pos = reader.original_position();
+        let end = pos + size;
+        reader.read_bytes(size)?;
+
+        let mut contents = vec![];
+        while let Some((start, stop, value)) = folds.next_if(|fold| fold.0 < end) {
+            contents.extend(&input[pos..start]);
+            write_const_expr(&mut contents, value)?;
+            pos = stop;
+        }
+        contents.extend(&input[pos..end]);
+
+        output.push(id);
+        write_uleb(&mut output, contents.len() as u64);
+        output.extend(contents);
+    }
+    Ok(Cow::Owned(output))
+}
+
+fn write_const_expr(out: &mut Vec<u8>, value: Value) -> Result<()> {
+    match value {
+        Value::I32(x) => {
+            out.push(0x41);
+            write_sleb(out, x as i32 as i64);
+        }
+        Value::I64(x) => {
+            out.push(0x42);
+            write_sleb(out, x as i64);
+        }
+        Value::F32(x) => {
+            out.push(0x43);
+            out.extend(x.to_bits().to_le_bytes());
+        }
+        Value::F64(x) => {
+            out.push(0x44);
+            out.extend(x.to_bits().to_le_bytes());
+        }
+        x => bail!("value {:?} has no constant expression", x),
+    }
+    out.push(0x0B);
+    Ok(())
+}
+
+pub(crate) fn write_uleb(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return out.push(byte);
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+pub(crate) fn write_sleb(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            return out.push(byte);
+        }
+        out.push(byte | 0x80);
+    }
+}
+
 impl<'a> Debug for WasmBinary<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("WasmBinary")
@@ -510,6 +846,10 @@ impl<'a> Debug for WasmBinary<'a> {
 impl<'a> WasmBinary<'a> {
     /// Instruments a user wasm, producing a version bounded via configurable instrumentation.
     pub fn instrument(&mut self, compile: &CompileConfig) -> Result<StylusData> {
+        if self.extended_consts && compile.version < 2 {
+            bail!("extended constant expressions require Stylus version 2");
+        }
+
         let meter = Meter::new(&compile.pricing);
         let dygas = DynamicMeter::new(&compile.pricing);
         let depth = DepthChecker::new(compile.bounds);
@@ -567,9 +907,7 @@ impl<'a> WasmBinary<'a> {
         // 4GB maximum implies `footprint` fits in a u16
         let footprint = self.memory_info()?.min.0 as u16;
 
-        // check the entrypoint
-        let ty = FunctionType::new([ArbValueType::I32], [ArbValueType::I32]);
-        let user_main = self.check_func(STYLUS_ENTRY_POINT, ty)?;
+        let user_main = self.check_entrypoint()?;
 
         // naively assume for now an upper bound of 5Mb
         let asm_estimate = 5 * 1024 * 1024;
@@ -597,79 +935,118 @@ impl<'a> WasmBinary<'a> {
         compile: &CompileConfig,
     ) -> Result<(WasmBinary<'a>, StylusData)> {
         let mut bin = parse(wasm, Path::new("user"))?;
-        let stylus_data = bin.instrument(compile)?;
-
-        let Some(memory) = bin.memories.first() else {
-            bail!("missing memory with export name \"memory\"")
-        };
-        let pages = memory.initial;
+        let mut errors = vec![];
 
-        // ensure the wasm fits within the remaining amount of memory
-        if pages > page_limit.into() {
-            let limit = page_limit.red();
-            bail!("memory exceeds limit: {} > {limit}", pages.red());
-        }
-
-        // not strictly necessary, but anti-DoS limits and extra checks in case of bugs
-        macro_rules! limit {
-            ($limit:expr, $count:expr, $name:expr) => {
-                if $count > $limit {
-                    bail!("too many wasm {}: {} > {}", $name, $count, $limit);
-                }
-            };
+        // instrumentation requires a single memory and the entrypoint
+        if bin.memories.is_empty() {
+            errors.push(ActivationError::MissingMemory);
         }
-        limit!(1, bin.memories.len(), "memories");
-        limit!(128, bin.datas.len(), "datas");
-        limit!(128, bin.elements.len(), "elements");
-        limit!(1024, bin.exports.len(), "exports");
-        limit!(4096, bin.codes.len(), "functions");
-        limit!(32768, bin.globals.len(), "globals");
-        for code in &bin.codes {
-            limit!(348, code.locals.len(), "locals");
-            limit!(65536, code.expr.len(), "opcodes in func body");
-        }
-
-        let table_entries = bin.tables.iter().map(|x| x.initial).saturating_sum();
-        limit!(4096, table_entries, "table entries");
-
-        let elem_entries = bin.elements.iter().map(|x| x.range.len()).saturating_sum();
-        limit!(4096, elem_entries, "element entries");
-
-        let max_len = 512;
-        macro_rules! too_long {
-            ($name:expr, $len:expr) => {
-                bail!(
-                    "wasm {} too long: {} > {}",
-                    $name.red(),
-                    $len.red(),
-                    max_len.red()
-                )
-            };
+        if let Err(error) = bin.check_entrypoint() {
+            errors.push(error);
         }
-        if let Some((name, _)) = bin.exports.iter().find(|(name, _)| name.len() > max_len) {
-            too_long!("name", name.len())
+
+        // instrumentation only grows the program, so limits are still checked when it can't happen
+        let mut stylus_data = None;
+        if errors.is_empty() && bin.memories.len() == 1 {
+            match bin.instrument(compile) {
+                Ok(data) => stylus_data = Some(data),
+                Err(error) => errors.push(ActivationError::Instrumentation {
+                    error: error.to_string(),
+                }),
+            }
         }
-        if bin.names.module.len() > max_len {
-            too_long!("module name", bin.names.module.len())
+
+        // memory must fit within the remaining amount, and the rest are anti-DoS limits
+        for usage in bin.limit_usage(page_limit) {
+            errors.extend(usage.errors());
         }
-        if bin.start.is_some() {
-            bail!("wasm start functions not allowed");
+        match stylus_data {
+            Some(stylus_data) if errors.is_empty() => Ok((bin, stylus_data)),
+            _ => Err(ActivationErrors(errors).into()),
         }
-        Ok((bin, stylus_data))
+    }
+
+    /// Measures the program against each limit enforced during activation.
+    /// Per-item limits report the largest item.
+    pub fn limit_usage(&self, page_limit: u16) -> Vec<LimitUsage> {
+        use LimitKind::*;
+
+        let len = |x: usize| x as u64;
+        let imports = self.imports.len() as u32;
+        let funcs = |f: fn(&Code) -> usize| -> Vec<_> {
+            let codes = self.codes.iter().enumerate();
+            codes
+                .map(|(i, code)| (Location::Func(imports + i as u32), len(f(code))))
+                .collect()
+        };
+
+        let pages = self.memories.first().map(|x| x.initial).unwrap_or_default();
+        let locals = funcs(|code| code.locals.len());
+        let opcodes = funcs(|code| code.expr.len());
+        let table_entries = self.tables.iter().map(|x| x.initial).saturating_sum();
+        let elem_entries = self.elements.iter().map(|x| x.range.len()).saturating_sum();
+        let module_name = len(self.names.module.len());
+
+        let mut export_names: Vec<_> = self.exports.keys().collect();
+        export_names.sort();
+        let export_names = export_names
+            .into_iter()
+            .map(|name| (Location::Export(name.clone()), len(name.len())))
+            .collect();
+
+        let usage = |kind, name, limit: u64, actual: u64| LimitUsage {
+            kind,
+            name,
+            limit,
+            actual,
+            items: vec![],
+        };
+        let per_item = |kind, name, limit: u64, items: Vec<(Location, u64)>| LimitUsage {
+            kind,
+            name,
+            limit,
+            actual: items.iter().map(|x| x.1).max().unwrap_or_default(),
+            items,
+        };
+
+        vec![
+            usage(Memory, "memory pages", page_limit.into(), pages),
+            usage(Count, "memories", 1, len(self.memories.len())),
+            usage(Count, "datas", 128, len(self.datas.len())),
+            usage(Count, "elements", 128, len(self.elements.len())),
+            usage(Count, "exports", 1024, len(self.exports.len())),
+            usage(Count, "functions", 4096, len(self.codes.len())),
+            usage(Count, "globals", 32768, len(self.globals.len())),
+            per_item(Count, "locals", 348, locals),
+            per_item(Count, "opcodes in func body", 65536, opcodes),
+            usage(Count, "table entries", 4096, table_entries.into()),
+            usage(Count, "element entries", 4096, len(elem_entries)),
+            per_item(Length, "name", 512, export_names),
+            usage(Length, "module name", 512, module_name),
+            usage(Forbidden, "start functions", 0, self.start.is_some().into()),
+        ]
+    }
+
+    /// Ensures the entrypoint exists and has the right type.
+    fn check_entrypoint(&self) -> Result<u32, ActivationError> {
+        let ty = FunctionType::new([ArbValueType::I32], [ArbValueType::I32]);
+        self.check_func(STYLUS_ENTRY_POINT, ty)
     }
 
     /// Ensures a func exists and has the right type.
-    fn check_func(&self, name: &str, ty: FunctionType) -> Result<u32> {
-        let Some(&(func, kind)) = self.exports.get(name) else {
-            bail!("missing export with name {}", name.red());
+    fn check_func(&self, name: &str, ty: FunctionType) -> Result<u32, ActivationError> {
+        let name = name.to_owned();
+        let Some(&(func, kind)) = self.exports.get(&name) else {
+            return Err(ActivationError::MissingExport { name });
         };
         if kind != ExportKind::Func {
-            let kind = kind.debug_red();
-            bail!("export {} must be a function but is a {kind}", name.red());
+            return Err(ActivationError::NotAFunc { name, kind });
         }
-        let func_ty = self.get_function(FunctionIndex::new(func.try_into()?))?;
+        // exports are validated during parsing, so the func always exists
+        let func_ty = self.get_function(FunctionIndex::new(func as usize));
+        let func_ty = func_ty.map_err(|_| ActivationError::MissingExport { name: name.clone() })?;
         if func_ty != ty {
-            bail!("wrong type for {}: {}", name.red(), func_ty.red());
+            return Err(ActivationError::WrongType { name, ty: func_ty });
         }
         Ok(func)
     }
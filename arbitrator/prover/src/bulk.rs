@@ -0,0 +1,198 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+//! The bulk memory operators that read data segments.
+//!
+//! WAVM has no segments, so when a module reads or drops them, each has a global tracking its
+//! length until dropped. Passive data segments are compiled into the code reading them, which
+//! calls implementations specialized from `bulk_memory.wat` that are appended to the module
+//! after its internals.
+
+use crate::{
+    binary::WasmBinary,
+    host::BULK_MEMORY_BINARY,
+    machine::Function,
+    wavm::{wasm_to_wavm, FloatingPointImpls},
+};
+use eyre::{bail, Result};
+use wasmparser::{BlockType, DataKind, Operator, ValType};
+
+/// The globals WAVM adds to a module, and the implementations it appends.
+#[derive(Clone, Debug, Default)]
+pub struct BulkOps {
+    /// The first global holding each data segment's length, when used.
+    datas: Option<u32>,
+    /// The function index of the first implementation.
+    offset: u32,
+    /// The operators implemented, in order.
+    impls: Vec<BulkOp>,
+}
+
+/// An operator implemented by specializing a function in `bulk_memory.wat`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BulkOp {
+    /// The data segment.
+    MemoryInit(u32),
+}
+
+impl BulkOps {
+    /// Lays out the globals a binary needs, with any implementations starting at the given
+    /// function index.
+    pub fn new(bin: &WasmBinary, offset: u32) -> Self {
+        use Operator::*;
+
+        let mut impls = vec![];
+        let mut uses_datas = false;
+
+        for op in bin.codes.iter().flat_map(|code| &code.expr) {
+            let op = match *op {
+                MemoryInit { data_index, .. } => BulkOp::MemoryInit(data_index),
+                DataDrop { .. } => {
+                    uses_datas = true;
+                    continue;
+                }
+                _ => continue,
+            };
+            uses_datas = true;
+            if !impls.contains(&op) {
+                impls.push(op);
+            }
+        }
+
+        // length globals follow the module's own
+        let datas = uses_datas.then_some(bin.globals.len() as u32);
+
+        Self {
+            datas,
+            offset,
+            impls,
+        }
+    }
+
+    /// The global holding each data segment's length, if needed.
+    pub fn datas(&self) -> Option<u32> {
+        self.datas
+    }
+
+    /// The global holding a data segment's length.
+    pub fn data_len(&self, data: u32) -> u32 {
+        self.datas.expect("data segments not laid out") + data
+    }
+
+    /// Gets the function implementing an operator.
+    pub fn implementation(&self, op: &Operator) -> Result<u32> {
+        let op = match *op {
+            Operator::MemoryInit { data_index, .. } => BulkOp::MemoryInit(data_index),
+            _ => bail!("no bulk memory implementation for {:?}", op),
+        };
+        let Some(index) = self.impls.iter().position(|x| *x == op) else {
+            bail!("missing bulk memory implementation for {:?}", op)
+        };
+        Ok(self.offset + index as u32)
+    }
+
+    /// Produces the implementations to append to the module.
+    pub fn implement(&self, module: &WasmBinary, internals_offset: u32) -> Result<Vec<Function>> {
+        use Operator::*;
+
+        if self.impls.is_empty() {
+            return Ok(vec![]);
+        }
+        let bin = &*BULK_MEMORY_BINARY;
+        let func = |name: &str| {
+            let funcs = &bin.names.functions;
+            let func = funcs.iter().find(|(_, x)| *x == name).map(|(i, _)| *i);
+            func.ok_or_else(|| eyre::eyre!("missing bulk memory func {name}"))
+        };
+        let data_word = func("data_word")?;
+
+        let mut impls = vec![];
+        for op in &self.impls {
+            let BulkOp::MemoryInit(data) = *op;
+            let name = "memory_init";
+            let index = func(name)? as usize;
+            let code = &bin.codes[index];
+            let ty = bin.types[bin.functions[index] as usize].clone();
+
+            // memory_init searches the data segment's words, found via the last local
+            let local_index = (ty.inputs.len() + code.locals.len() - 1) as u32;
+            let segment = &module.datas[data as usize];
+            let bytes = match segment.kind {
+                DataKind::Passive => segment.data,
+                _ => &[], // active segments are dropped upon instantiation
+            };
+            let words: Vec<_> = bytes
+                .chunks(8)
+                .map(|chunk| {
+                    let mut word = [0; 8];
+                    word[..chunk.len()].copy_from_slice(chunk);
+                    i64::from_le_bytes(word)
+                })
+                .collect();
+            let mut search = vec![LocalSet { local_index }];
+            search_words(&mut search, &words, 0, local_index);
+
+            // point the implementation at the segment's length
+            let mut expr = vec![];
+            for op in &code.expr {
+                let op = match *op {
+                    GlobalGet { .. } => GlobalGet {
+                        global_index: self.data_len(data),
+                    },
+                    Call { function_index } if function_index == data_word => {
+                        expr.extend(search.iter().cloned());
+                        continue;
+                    }
+                    ref op => op.clone(),
+                };
+                expr.push(op);
+            }
+
+            let func = Function::new(
+                &code.locals,
+                |wasm| {
+                    wasm_to_wavm(
+                        &expr,
+                        wasm,
+                        &FloatingPointImpls::default(), // impls don't use floating point
+                        &[],                            // impls don't make calls
+                        &[ty.clone()],                  // only type needed is the func itself
+                        0,                              // -----------------------------------
+                        internals_offset,
+                        self,
+                        name,
+                    )
+                },
+                ty.clone(),
+                &[], // impls don't make indirect calls
+            )?;
+            impls.push(func);
+        }
+        Ok(impls)
+    }
+}
+
+/// Pushes a binary search for the word whose index is in the given local, the first having the given index.
+/// The index is bounds checked before searching, so an empty segment needn't produce a word.
+fn search_words(out: &mut Vec<Operator>, words: &[i64], first: usize, local_index: u32) {
+    use Operator::*;
+
+    if words.len() <= 1 {
+        let value = words.first().copied().unwrap_or_default();
+        out.push(I64Const { value });
+        return;
+    }
+    let mid = words.len() / 2;
+    out.push(LocalGet { local_index });
+    out.push(I32Const {
+        value: (first + mid) as i32,
+    });
+    out.push(I32LtU);
+    out.push(If {
+        blockty: BlockType::Type(ValType::I64),
+    });
+    search_words(out, &words[..mid], first, local_index);
+    out.push(Else);
+    search_words(out, &words[mid..], first + mid, local_index);
+    out.push(End);
+}
@@ -3,9 +3,15 @@
 ;;
 ;; This file implements the bulk memory instructions as per the specification below
 ;; https://github.com/WebAssembly/bulk-memory-operations/blob/master/proposals/bulk-memory-operations/Overview.md
+;;
+;; The operators reading data segments are specialized for each use, with $length becoming the
+;; global holding the segment's length, and each call to $data_word becoming a search of the data
+;; segment's contents for the given 8-byte word, using the function's last local to hold the
+;; word's index.
 
 (module
     (memory (export "memory") 0 0)
+    (global $length (mut i32) (i32.const 0))
     (func $memory_fill (param $dest i32) (param $value i32) (param $size i32)
         (local $value64 i64)
         ;; the bounds check happens before any data is written according to the spec
@@ -230,4 +236,56 @@
             )
         )
     )
+    (func $memory_init (param $dest i32) (param $source i32) (param $size i32)
+        (local $offset i32)
+        (local $index i32)
+        (local $word i32)
+
+        ;; trap if either region is out of bounds, even when copying nothing
+        (i64.gt_u
+            (i64.add
+                (i64.extend_i32_u (local.get $source))
+                (i64.extend_i32_u (local.get $size)))
+            (i64.extend_i32_u (global.get $length)))
+        (if (then unreachable))
+        (i64.gt_u
+            (i64.add
+                (i64.extend_i32_u (local.get $dest))
+                (i64.extend_i32_u (local.get $size)))
+            (i64.mul
+                (i64.extend_i32_u (memory.size))
+                (i64.const 0x10000)))
+        (if (then unreachable))
+
+        ;; copy the segment a word at a time when aligned, and a byte at a time otherwise
+        (loop $forward
+            ;; see if there's more data to set
+            (if (i32.eq (local.get $offset) (local.get $size))
+                (then return))
+            (local.set $index (i32.add (local.get $source) (local.get $offset)))
+
+            (if (i32.and
+                    (i32.eqz (i32.and (local.get $index) (i32.const 7)))
+                    (i32.ge_u (i32.sub (local.get $size) (local.get $offset)) (i32.const 8)))
+                (then
+                    ;; write the whole word
+                    (i64.store
+                        (i32.add (local.get $dest) (local.get $offset))
+                        (call $data_word (i32.shr_u (local.get $index) (i32.const 3))))
+                    (local.set $offset (i32.add (local.get $offset) (i32.const 8))))
+                (else
+                    ;; write the byte from within its word
+                    (i64.store8
+                        (i32.add (local.get $dest) (local.get $offset))
+                        (i64.shr_u
+                            (call $data_word (i32.shr_u (local.get $index) (i32.const 3)))
+                            (i64.extend_i32_u
+                                (i32.shl (i32.and (local.get $index) (i32.const 7)) (i32.const 3)))))
+                    (local.set $offset (i32.add (local.get $offset) (i32.const 1)))))
+            br $forward
+        )
+    )
+    (func $data_word (param $index i32) (result i64)
+        unreachable
+    )
 )
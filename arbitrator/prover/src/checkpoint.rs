@@ -0,0 +1,130 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+//! Random access to a machine's execution, as bisection in challenges requires.
+//!
+//! Challenge protocols repeatedly ask for the machine hash at some step, narrowing in on a
+//! disagreement before asking for the one-step proof there. Replaying from genesis for each query
+//! is quadratic, so the machine's state is snapshotted every `interval` steps, and each query
+//! resumes from the nearest snapshot at or before the step in question.
+//!
+//! Each snapshot is a full copy of the machine's state, so only a bounded number are kept. Should
+//! there be more, the interval doubles and the snapshots off of it are dropped, which keeps those
+//! remaining evenly spread however long the machine runs.
+
+use crate::machine::Machine;
+use arbutil::Bytes32;
+use eyre::{bail, Result};
+use std::collections::BTreeMap;
+
+pub struct CheckpointedMachine {
+    machine: Machine,
+    interval: u64,
+    /// The most snapshots to keep at once.
+    limit: usize,
+    /// Serialized states, keyed by step.
+    checkpoints: BTreeMap<u64, Vec<u8>>,
+}
+
+impl CheckpointedMachine {
+    /// Wraps a machine, snapshotting its state every `interval` steps as it's advanced,
+    /// and keeping at most `limit` snapshots.
+    pub fn new(machine: Machine, interval: u64, limit: usize) -> Result<Self> {
+        if interval == 0 {
+            bail!("checkpoint interval must be positive");
+        }
+        if limit < 2 {
+            bail!("checkpoint limit must be at least 2");
+        }
+        let mut checkpointed = Self {
+            machine,
+            interval,
+            limit,
+            checkpoints: BTreeMap::new(),
+        };
+        checkpointed.checkpoint()?;
+        Ok(checkpointed)
+    }
+
+    /// The machine, at whichever step was last queried.
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    /// Unwraps the machine, at whichever step was last queried.
+    pub fn into_machine(self) -> Machine {
+        self.machine
+    }
+
+    /// The steps between snapshots, which doubles each time they're thinned.
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// The steps at which snapshots have been taken.
+    pub fn checkpoints(&self) -> impl Iterator<Item = u64> + '_ {
+        self.checkpoints.keys().copied()
+    }
+
+    /// Snapshots the machine at its current step, in addition to those taken every interval.
+    /// Like any other, the snapshot may later be dropped to stay within the limit.
+    pub fn checkpoint(&mut self) -> Result<()> {
+        let step = self.machine.get_steps();
+        if !self.checkpoints.contains_key(&step) {
+            let mut state = vec![];
+            self.machine.serialize_state_into(&mut state)?;
+            self.checkpoints.insert(step, state);
+            self.thin();
+        }
+        Ok(())
+    }
+
+    /// Doubles the interval until the snapshots on it fit within the limit, always keeping the first.
+    fn thin(&mut self) {
+        let Some(&first) = self.checkpoints.keys().next() else {
+            return;
+        };
+        while self.checkpoints.len() > self.limit {
+            self.interval = self.interval.saturating_mul(2);
+            let interval = self.interval;
+            self.checkpoints
+                .retain(|&step, _| step == first || step % interval == 0);
+        }
+    }
+
+    /// Moves the machine to the given step, or to where it halted if that comes first.
+    pub fn seek(&mut self, step: u64) -> Result<&Machine> {
+        let current = self.machine.get_steps();
+        let nearest = self.checkpoints.range(..=step).next_back();
+
+        // resume from the nearest snapshot unless the machine is already closer
+        if let Some((&start, state)) = nearest {
+            if current > step || start > current {
+                self.machine
+                    .deserialize_and_replace_state_from(&state[..])?;
+            }
+        }
+
+        // advance, snapshotting on each multiple of the interval
+        while self.machine.get_steps() < step && !self.machine.is_halted() {
+            let steps = self.machine.get_steps();
+            let next = (steps / self.interval + 1) * self.interval;
+            self.machine.step_n(next.min(step) - steps)?;
+            if self.machine.get_steps() % self.interval == 0 {
+                self.checkpoint()?;
+            }
+        }
+        Ok(&self.machine)
+    }
+
+    /// The machine's hash after executing the given number of steps.
+    pub fn hash_at(&mut self, step: u64) -> Result<Bytes32> {
+        Ok(self.seek(step)?.hash())
+    }
+
+    /// The one-step proof of executing the instruction following the given number of steps.
+    /// Past the point where the machine halts, this proves that its state no longer changes.
+    pub fn proof_at(&mut self, step: u64) -> Result<Vec<u8>> {
+        Ok(self.seek(step)?.serialize_proof())
+    }
+}
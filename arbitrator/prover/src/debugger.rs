@@ -0,0 +1,463 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+//! An interactive debugger for stepping through a machine's execution.
+//!
+//! Execution can be rewound to an earlier stop, or moved to any other step, by replaying from the
+//! nearest checkpoint. Stops far from any checkpoint are checkpointed themselves, which makes it
+//! cheap to go back and forth around the step at which a JIT run and the machine diverge without
+//! serializing the machine at every stop.
+
+use crate::{
+    checkpoint::CheckpointedMachine,
+    machine::Machine,
+    value::{ProgramCounter, Value},
+    wavm::Opcode,
+};
+use arbutil::{format, Color, DebugColor};
+use eyre::{bail, eyre, Result};
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display},
+    io::{BufRead, Write},
+    mem,
+};
+
+const HELP: &str = "\
+step [n]              execute n instructions, stepping into calls
+next                  execute an instruction, stepping over calls
+finish                run until the current function returns
+continue              run until a breakpoint or the machine halts
+back                  rewind to the previous stop
+goto <step>           move to the given step, ignoring breakpoints
+break [func|op] <x>   stop on entering function x or reaching opcode x
+delete <index>        remove a breakpoint
+breakpoints           list the breakpoints
+stack                 print the value and internal stacks
+locals                print the current frame's locals
+globals [module]      print a module's globals
+memory <ptr> <len> [module]
+                      print a range of a module's memory
+where                 print the backtrace and machine hash
+quit                  exit the debugger
+
+Modules default to the current one, and an empty line repeats the last command.
+Names shared by a function and an opcode must be given a breakpoint kind.";
+
+/// Stops are only checkpointed when further than this fraction of the interval from a checkpoint.
+const STOP_CHECKPOINT_FRACTION: u64 = 16;
+
+/// Where the debugger should stop as the machine executes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Entering a function with the given name, demangled or not.
+    Func(String),
+    /// Reaching an instruction with the opcode's variant, whatever its fields.
+    Opcode(Opcode),
+}
+
+impl Breakpoint {
+    /// Whether the machine is about to execute where the breakpoint is set.
+    fn hit(&self, machine: &Machine) -> bool {
+        let Some(pc) = machine.get_pc() else {
+            return false;
+        };
+        match self {
+            Self::Func(name) => {
+                if pc.inst != 0 {
+                    return false;
+                }
+                let names = machine.get_module_names(pc.module());
+                let Some(func) = names.and_then(|x| x.functions.get(&pc.func)) else {
+                    return false;
+                };
+                func == name || demangle(func) == *name
+            }
+            Self::Opcode(opcode) => match machine.get_next_instruction() {
+                Some(inst) => mem::discriminant(&inst.opcode) == mem::discriminant(opcode),
+                None => false,
+            },
+        }
+    }
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Func(name) => write!(f, "{} {}", "func".grey(), name.mint()),
+            Self::Opcode(opcode) => write!(f, "{} {}", "op".grey(), opcode_name(*opcode).mint()),
+        }
+    }
+}
+
+/// Why the debugger stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    /// The command ran to completion.
+    Done,
+    /// The breakpoint at the given index was hit.
+    Breakpoint(usize),
+    Halted,
+}
+
+pub struct Debugger {
+    machine: CheckpointedMachine,
+    breakpoints: Vec<Breakpoint>,
+    /// The steps at which the debugger has stopped.
+    stops: BTreeSet<u64>,
+}
+
+impl Debugger {
+    /// Debugs a machine, checkpointing it every `interval` steps in addition to stops far from
+    /// any checkpoint, and keeping at most `limit` checkpoints.
+    pub fn new(machine: Machine, interval: u64, limit: usize) -> Result<Self> {
+        let stops = BTreeSet::from([machine.get_steps()]);
+        Ok(Self {
+            machine: CheckpointedMachine::new(machine, interval, limit)?,
+            breakpoints: vec![],
+            stops,
+        })
+    }
+
+    pub fn machine(&self) -> &Machine {
+        self.machine.machine()
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Adds a breakpoint, returning its index.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+        self.breakpoints.push(breakpoint);
+        self.breakpoints.len() - 1
+    }
+
+    /// Removes the breakpoint at the given index, shifting those after it down.
+    pub fn remove_breakpoint(&mut self, index: usize) -> Option<Breakpoint> {
+        (index < self.breakpoints.len()).then(|| self.breakpoints.remove(index))
+    }
+
+    /// Executes up to `n` instructions, stepping into calls.
+    pub fn step(&mut self, n: u64) -> Result<Stop> {
+        if n == 0 {
+            return Ok(Stop::Done);
+        }
+        let end = self.machine().get_steps().saturating_add(n);
+        self.run_until(|machine| machine.get_steps() >= end)
+    }
+
+    /// Executes the next instruction, running any function it calls to completion.
+    pub fn step_over(&mut self) -> Result<Stop> {
+        let depth = call_depth(self.machine());
+        self.run_until(|machine| call_depth(machine) <= depth)
+    }
+
+    /// Runs until the current function returns.
+    pub fn finish(&mut self) -> Result<Stop> {
+        let depth = call_depth(self.machine());
+        self.run_until(|machine| call_depth(machine) < depth)
+    }
+
+    /// Runs until a breakpoint is hit or the machine halts.
+    pub fn resume(&mut self) -> Result<Stop> {
+        self.run_until(|_| false)
+    }
+
+    /// Rewinds to the last stop before the current step, returning the step rewound to.
+    pub fn back(&mut self) -> Result<u64> {
+        let steps = self.machine().get_steps();
+        let Some(&prior) = self.stops.range(..steps).next_back() else {
+            bail!("no stop before step {}", steps.red());
+        };
+        self.machine.seek(prior)?;
+        Ok(prior)
+    }
+
+    /// Moves to the given step, or to where the machine halts if that comes first.
+    pub fn goto(&mut self, step: u64) -> Result<()> {
+        self.machine.seek(step)?;
+        self.stop()
+    }
+
+    /// Records a stop so that it may be rewound to later, checkpointing the machine there if
+    /// replaying from the nearest checkpoint would take a significant part of the interval.
+    fn stop(&mut self) -> Result<()> {
+        let steps = self.machine().get_steps();
+        self.stops.insert(steps);
+
+        let nearest = self
+            .machine
+            .checkpoints()
+            .take_while(|x| *x <= steps)
+            .last();
+        let distance = nearest.map_or(u64::MAX, |x| steps - x);
+        if distance > self.machine.interval() / STOP_CHECKPOINT_FRACTION {
+            self.machine.checkpoint()?;
+        }
+        Ok(())
+    }
+
+    /// Steps until `done` holds, a breakpoint is hit, or the machine halts, recording where
+    /// the machine stops so that it may be rewound there later.
+    fn run_until(&mut self, mut done: impl FnMut(&Machine) -> bool) -> Result<Stop> {
+        let stop = loop {
+            let steps = self.machine().get_steps();
+            let machine = self.machine.seek(steps + 1)?;
+            if machine.is_halted() {
+                break Stop::Halted;
+            }
+            if let Some(index) = self.breakpoints.iter().position(|x| x.hit(machine)) {
+                break Stop::Breakpoint(index);
+            }
+            if done(machine) {
+                break Stop::Done;
+            }
+        };
+        self.stop()?;
+        Ok(stop)
+    }
+
+    /// Reads and executes commands until the input ends or the user quits.
+    pub fn repl<R: BufRead>(&mut self, input: R) -> Result<()> {
+        self.print_location();
+        let mut lines = input.lines();
+        let mut last = String::new();
+        loop {
+            print!("{} ", "(wavm)".grey());
+            std::io::stdout().flush()?;
+
+            let Some(line) = lines.next() else {
+                println!();
+                return Ok(());
+            };
+            let mut line = line?;
+            if line.trim().is_empty() {
+                line = last;
+            }
+            match self.execute(&line) {
+                Ok(true) => return Ok(()),
+                Ok(false) => {}
+                Err(err) => println!("{}", err.red()),
+            }
+            last = line;
+        }
+    }
+
+    /// Executes a line of input, returning whether the user asked to quit.
+    fn execute(&mut self, line: &str) -> Result<bool> {
+        let mut args = line.split_whitespace();
+        let Some(command) = args.next() else {
+            return Ok(false);
+        };
+        let args: Vec<_> = args.collect();
+        let arg = |i: usize| {
+            let arg = args.get(i).copied();
+            arg.ok_or_else(|| eyre!("{} expects more arguments", command.red()))
+        };
+        let module = |i: usize| match args.get(i) {
+            Some(arg) => Ok(u32::try_from(parse_number(arg)?)?),
+            None => Ok::<_, eyre::Error>(self.current_module()),
+        };
+
+        match command {
+            "s" | "step" => {
+                let n = match args.first() {
+                    Some(arg) => parse_number(arg)?,
+                    None => 1,
+                };
+                let stop = self.step(n)?;
+                self.print_stop(stop);
+            }
+            "n" | "next" => {
+                let stop = self.step_over()?;
+                self.print_stop(stop);
+            }
+            "f" | "finish" => {
+                let stop = self.finish()?;
+                self.print_stop(stop);
+            }
+            "c" | "continue" => {
+                let stop = self.resume()?;
+                self.print_stop(stop);
+            }
+            "back" => {
+                self.back()?;
+                self.print_location();
+            }
+            "goto" => {
+                self.goto(parse_number(arg(0)?)?)?;
+                self.print_location();
+            }
+            "b" | "break" => {
+                let breakpoint = match (args.len(), arg(0)?) {
+                    (1, name) => match (find_opcode(name), has_func(self.machine(), name)) {
+                        (Some(_), true) => bail!(
+                            "{} names both a function and an opcode, so try {} or {}",
+                            name.red(),
+                            format!("break func {name}").mint(),
+                            format!("break op {name}").mint(),
+                        ),
+                        (Some(opcode), false) => Breakpoint::Opcode(opcode),
+                        (None, _) => Breakpoint::Func(name.to_owned()),
+                    },
+                    (_, "func") => Breakpoint::Func(arg(1)?.to_owned()),
+                    (_, "op") => match find_opcode(arg(1)?) {
+                        Some(opcode) => Breakpoint::Opcode(opcode),
+                        None => bail!("no opcode named {}", arg(1)?.red()),
+                    },
+                    (_, kind) => bail!("unknown breakpoint kind {}", kind.red()),
+                };
+                println!("{} {breakpoint}", self.breakpoints.len().blue());
+                self.add_breakpoint(breakpoint);
+            }
+            "d" | "delete" => {
+                let index = parse_number(arg(0)?)?;
+                let Some(breakpoint) = self.remove_breakpoint(index as usize) else {
+                    bail!("no breakpoint {}", index.red());
+                };
+                println!("{} {breakpoint}", "removed".grey());
+            }
+            "breakpoints" => {
+                for (i, breakpoint) in self.breakpoints.iter().enumerate() {
+                    println!("{} {breakpoint}", i.blue());
+                }
+            }
+            "stack" => {
+                let machine = self.machine();
+                for (i, stack) in machine.get_data_stacks().into_iter().enumerate() {
+                    let name = match i {
+                        0 => "main".to_owned(),
+                        i => format!("cothread {i}"),
+                    };
+                    println!("{} [{}]", name.grey(), format::commas(stack));
+                }
+                let internals = machine.get_internals_stack();
+                println!("{} [{}]", "internals".grey(), format::commas(internals));
+            }
+            "locals" => print_values("local", self.machine().get_locals()),
+            "globals" => {
+                let module = module(0)?;
+                let Some(globals) = self.machine().get_globals(module) else {
+                    bail!("no module at offset {}", module.red());
+                };
+                print_values("global", globals);
+            }
+            "x" | "memory" => {
+                let ptr = u32::try_from(parse_number(arg(0)?)?)?;
+                let len = u32::try_from(parse_number(arg(1)?)?)?;
+                let data = self.machine().read_memory(module(2)?, ptr, len)?;
+                for (i, chunk) in data.chunks(16).enumerate() {
+                    let offset = ptr as usize + 16 * i;
+                    println!(
+                        "{} {}",
+                        format!("{offset:#010x}").grey(),
+                        hex::encode(chunk)
+                    );
+                }
+            }
+            "bt" | "where" => {
+                let machine = self.machine();
+                println!("{} {}", "hash".grey(), machine.hash());
+                machine.print_backtrace(false);
+            }
+            "h" | "help" => println!("{HELP}"),
+            "q" | "quit" => return Ok(true),
+            _ => bail!("unknown command {}, try {}", command.red(), "help".mint()),
+        }
+        Ok(false)
+    }
+
+    fn current_module(&self) -> u32 {
+        self.machine()
+            .get_pc()
+            .map(|pc| pc.module)
+            .unwrap_or_default()
+    }
+
+    fn print_stop(&self, stop: Stop) {
+        if let Stop::Breakpoint(index) = stop {
+            let breakpoint = &self.breakpoints[index];
+            println!("{} {} {breakpoint}", "breakpoint".grey(), index.blue());
+        }
+        self.print_location();
+    }
+
+    /// Prints the step and the instruction about to execute.
+    fn print_location(&self) {
+        let machine = self.machine();
+        let steps = format!("step {}", machine.get_steps());
+        let (Some(pc), Some(inst)) = (machine.get_pc(), machine.get_next_instruction()) else {
+            println!("{} {}", steps.grey(), machine.get_status().red());
+            return;
+        };
+        let func = func_name(machine, pc).unwrap_or_else(|| pc.func.to_string());
+        let data = match inst.argument_data {
+            0 => String::new(),
+            x => format!(" {x:#x}").mint(),
+        };
+        println!(
+            "{} {pc} {} {}{data}",
+            steps.grey(),
+            func.mint(),
+            inst.opcode.debug_pink()
+        );
+    }
+}
+
+/// The machine's call depth, counting a function that's yet to initialize its frame as entered.
+fn call_depth(machine: &Machine) -> usize {
+    let entering = machine.get_pc().map_or(false, |pc| pc.inst == 0);
+    machine.get_call_depth() + entering as usize
+}
+
+/// The demangled name of the function at the program counter, if its module names it.
+fn func_name(machine: &Machine, pc: ProgramCounter) -> Option<String> {
+    let names = machine.get_module_names(pc.module())?;
+    names.functions.get(&pc.func).map(|x| demangle(x))
+}
+
+/// Whether any module has a function with the given name, demangled or not.
+fn has_func(machine: &Machine, name: &str) -> bool {
+    let mut modules = (0..).map_while(|i| machine.get_module_names(i));
+    modules.any(|names| {
+        let mut funcs = names.functions.values();
+        funcs.any(|func| func == name || demangle(func) == name)
+    })
+}
+
+/// Demangles a function name, dropping any hash.
+fn demangle(name: &str) -> String {
+    format!("{:#}", rustc_demangle::demangle(name))
+}
+
+/// The name of an opcode's variant, without its fields.
+fn opcode_name(opcode: Opcode) -> String {
+    let name = format!("{opcode:?}");
+    name.split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap()
+        .to_owned()
+}
+
+/// Finds an opcode by the name of its variant, ignoring case.
+fn find_opcode(name: &str) -> Option<Opcode> {
+    (0..=u16::MAX)
+        .filter_map(Opcode::from_repr)
+        .find(|x| opcode_name(*x).eq_ignore_ascii_case(name))
+}
+
+/// Parses a decimal or 0x-prefixed hexadecimal number.
+fn parse_number(arg: &str) -> Result<u64> {
+    let parsed = match arg.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => arg.parse(),
+    };
+    parsed.map_err(|_| eyre!("invalid number {}", arg.red()))
+}
+
+fn print_values(kind: &str, values: &[Value]) {
+    for (i, value) in values.iter().enumerate() {
+        println!("{} {value}", format!("${kind}_{i}").pink());
+    }
+}
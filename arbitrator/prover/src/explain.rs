@@ -0,0 +1,248 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+//! Decodes one-step proofs into their named parts, for debugging disputes.
+//!
+//! Decoding follows the layout `Machine::serialize_proof` produces without checking any of it,
+//! so that even a bad proof can be inspected. Use [`crate::verify`] to check one.
+
+use crate::{
+    machine::{GlobalState, MachineStatus},
+    memory::Memory,
+    value::Value,
+    verify::{
+        self, ElementProof, LastModule, MemoryLeaf, ModuleProof, ProvenMachine, Reader, TableProof,
+    },
+    wavm::{Instruction, Opcode},
+};
+use arbutil::Bytes32;
+use eyre::{bail, ensure, eyre, Result};
+
+const LEAF_SIZE: usize = Memory::LEAF_SIZE;
+
+/// A one-step proof, broken into its parts.
+#[derive(Clone, Debug)]
+pub struct ProofExplanation {
+    /// The machine as of the start of the step.
+    pub machine: ProvenMachine,
+    /// The siblings of the current module in the machine's tree of modules.
+    pub module_proof: Vec<Bytes32>,
+    /// The instruction executed, which halted machines lack.
+    pub step: Option<StepProof>,
+}
+
+/// The instruction a proof executes, and the data needed to execute it.
+#[derive(Clone, Debug)]
+pub struct StepProof {
+    /// The instruction, whose argument is only known when it's its own proving data.
+    pub inst: Instruction,
+    /// The siblings of the instruction in its function's code.
+    pub inst_proof: Vec<Bytes32>,
+    /// The siblings of the function in its module.
+    pub func_proof: Vec<Bytes32>,
+    /// The opcode-specific parts, in the order the proof includes them.
+    pub extra: Vec<ProofPart>,
+}
+
+/// A piece of opcode-specific proof data.
+#[derive(Clone, Debug)]
+pub enum ProofPart {
+    /// A local of the current frame, and its siblings.
+    Local {
+        index: usize,
+        value: Value,
+        proof: Vec<Bytes32>,
+    },
+    /// A global of the current module, and its siblings.
+    Global {
+        index: usize,
+        value: Value,
+        proof: Vec<Bytes32>,
+    },
+    MemoryLeaf(MemoryLeaf),
+    /// The hash of the function type a `call_indirect` expects.
+    FuncType(Bytes32),
+    Table(TableProof),
+    Element(ElementProof),
+    GlobalState(GlobalState),
+    /// The module called into, and its siblings.
+    Module {
+        index: usize,
+        module: ModuleProof,
+        proof: Vec<Bytes32>,
+    },
+    LastModule(LastModule),
+    Preimage(Vec<u8>),
+    InboxMessage(Vec<u8>),
+    /// The hashes of the new last stack and those between it and the first, for the values
+    /// and then the frames, left after popping a cothread.
+    PoppedCothread {
+        values: (Bytes32, Bytes32),
+        frames: (Bytes32, Bytes32),
+    },
+}
+
+/// Decodes a proof as produced by `Machine::serialize_proof`.
+pub fn explain(proof: &[u8]) -> Result<ProofExplanation> {
+    let mut proof = Reader::new(proof);
+    let machine = ProvenMachine::read(&mut proof)?;
+    let module_proof = proof.merkle_proof()?;
+
+    let mut step = None;
+    if machine.status == MachineStatus::Running {
+        let inst = read_instruction(&mut proof)?;
+        let inst_proof = proof.merkle_proof()?;
+        let func_proof = proof.merkle_proof()?;
+        let extra = read_extra(&machine, inst, &mut proof)?;
+        step = Some(StepProof {
+            inst,
+            inst_proof,
+            func_proof,
+            extra,
+        });
+    }
+    ensure!(proof.is_empty(), "proof has trailing data");
+
+    Ok(ProofExplanation {
+        machine,
+        module_proof,
+        step,
+    })
+}
+
+/// Inverts `Instruction::serialize_for_proof`.
+fn read_instruction(proof: &mut Reader) -> Result<Instruction> {
+    let repr = proof.u16()?;
+    let Some(opcode) = Opcode::from_repr(repr) else {
+        bail!("unknown opcode {repr:#06x}")
+    };
+    let data = proof.bytes32()?;
+    let argument_data = u64::from_be_bytes(data[24..].try_into().unwrap());
+    let inst = match data[..24].iter().all(|x| *x == 0) {
+        true => Instruction::with_data(opcode, argument_data),
+        false => Instruction {
+            opcode,
+            argument_data: 0,
+            proving_argument_data: Some(data),
+        },
+    };
+    Ok(inst)
+}
+
+/// Reads the parts of a proof specific to the instruction, which depend on the stack's top.
+fn read_extra(
+    machine: &ProvenMachine,
+    inst: Instruction,
+    proof: &mut Reader,
+) -> Result<Vec<ProofPart>> {
+    let stack = &machine.values.active.items;
+    let peek = |depth: usize| {
+        let value = stack.iter().rev().nth(depth);
+        value
+            .copied()
+            .ok_or_else(|| eyre!("stack deeper than proven"))
+    };
+    let peek_u32 = |depth: usize| match peek(depth)? {
+        Value::I32(x) => Ok(x),
+        x => bail!("expected an i32 on the stack but found {x:?}"),
+    };
+    let read_leaf = |proof: &mut Reader, index: usize| {
+        let leaf = MemoryLeaf::read(proof, index, machine.module.memory_size)?;
+        Ok::<_, eyre::Error>(ProofPart::MemoryLeaf(leaf))
+    };
+    let read_element = |proof: &mut Reader, table: &TableProof, index: u32| {
+        if u64::from(index) >= table.len {
+            return Ok(None);
+        }
+        let elem = ElementProof::read(proof, index as usize)?;
+        Ok::<_, eyre::Error>(Some(ProofPart::Element(elem)))
+    };
+
+    let mut parts = vec![];
+    let arg = inst.argument_data;
+
+    use Opcode::*;
+    match inst.opcode {
+        GetGlobalStateU64 | SetGlobalStateU64 => {
+            parts.push(ProofPart::GlobalState(verify::read_global_state(proof)?));
+        }
+        LocalGet | LocalSet | GlobalGet | GlobalSet => {
+            let (index, value, proof) = (arg as usize, proof.value()?, proof.merkle_proof()?);
+            parts.push(match inst.opcode {
+                LocalGet | LocalSet => ProofPart::Local {
+                    index,
+                    value,
+                    proof,
+                },
+                _ => ProofPart::Global {
+                    index,
+                    value,
+                    proof,
+                },
+            });
+        }
+        MemoryLoad { .. } | MemoryStore { .. } => {
+            let depth = matches!(inst.opcode, MemoryStore { .. }) as usize;
+            if let Some(index) = arg.checked_add(peek_u32(depth)?.into()) {
+                let leaf = index as usize / LEAF_SIZE;
+                parts.push(read_leaf(proof, leaf)?);
+                parts.push(read_leaf(proof, leaf + 1)?);
+            }
+        }
+        CallIndirect => {
+            let index = proof.u64()?;
+            parts.push(ProofPart::FuncType(proof.bytes32()?));
+            let table = TableProof::read(proof, index as usize)?;
+            let elem = read_element(proof, &table, peek_u32(0)?)?;
+            parts.push(ProofPart::Table(table));
+            parts.extend(elem);
+        }
+        CrossModuleInternalCall => {
+            let index = peek_u32(0)? as usize;
+            let module = ModuleProof::read(proof)?;
+            let proof = proof.merkle_proof()?;
+            parts.push(ProofPart::Module {
+                index,
+                module,
+                proof,
+            });
+        }
+        GetGlobalStateBytes32 | SetGlobalStateBytes32 => {
+            parts.push(ProofPart::GlobalState(verify::read_global_state(proof)?));
+            let ptr = peek_u32(0)? as usize;
+            if ptr % LEAF_SIZE == 0 {
+                parts.push(read_leaf(proof, ptr / LEAF_SIZE)?);
+            }
+        }
+        ReadPreImage | ReadInboxMessage => {
+            let ptr = peek_u32(1)? as usize;
+            if ptr % LEAF_SIZE == 0 {
+                parts.push(read_leaf(proof, ptr / LEAF_SIZE)?);
+            }
+
+            // inbox messages beyond the inbox are absent
+            if !proof.is_empty() {
+                ensure!(proof.u8()? == 0, "unknown preimage or inbox proof type");
+                let data = proof.rest().to_vec();
+                parts.push(match inst.opcode {
+                    ReadPreImage => ProofPart::Preimage(data),
+                    _ => ProofPart::InboxMessage(data),
+                });
+            }
+        }
+        LinkModule | UnlinkModule => {
+            if inst.opcode == LinkModule {
+                let ptr = peek_u32(0)? as usize;
+                parts.push(read_leaf(proof, ptr / LEAF_SIZE)?);
+            }
+            parts.push(ProofPart::LastModule(LastModule::read(proof)?));
+        }
+        PopCoThread => {
+            let values = (proof.bytes32()?, proof.bytes32()?);
+            let frames = (proof.bytes32()?, proof.bytes32()?);
+            parts.push(ProofPart::PoppedCothread { values, frames });
+        }
+        _ => {}
+    }
+    Ok(parts)
+}
@@ -4,7 +4,9 @@
 #![allow(clippy::vec_init_then_push, clippy::redundant_closure)]
 
 use crate::{
-    binary, host,
+    binary::{self, WasmBinary},
+    bulk::BulkOps,
+    host,
     machine::{Function, InboxIdentifier},
     programs::StylusData,
     utils,
@@ -470,12 +472,23 @@ pub fn new_internal_funcs(stylus_data: Option<StylusData>) -> Vec<Function> {
 }
 
 lazy_static! {
+    /// Implementations of the bulk memory operators, some of which are specialized for each use.
+    pub static ref BULK_MEMORY_WASM: Vec<u8> = {
+        let data = include_bytes!("bulk_memory.wat");
+        let wasm = wat::parse_bytes(data).expect("failed to parse bulk_memory.wat");
+        wasm.into_owned()
+    };
+
+    /// The parsed implementations, which are specialized each time a module uses them.
+    pub static ref BULK_MEMORY_BINARY: WasmBinary<'static> = {
+        let wasm = &*BULK_MEMORY_WASM;
+        binary::parse(wasm, Path::new("internal")).expect("failed to parse bulk_memory.wasm")
+    };
+
     static ref BULK_MEMORY_FUNCS: [Function; 2] = {
         use host::InternalFunc::*;
 
-        let data = include_bytes!("bulk_memory.wat");
-        let wasm = wat::parse_bytes(data).expect("failed to parse bulk_memory.wat");
-        let bin = binary::parse(&wasm, Path::new("internal")).expect("failed to parse bulk_memory.wasm");
+        let bin = &*BULK_MEMORY_BINARY;
         let types = [MemoryFill.ty(), MemoryCopy.ty()];
         let names = ["memory_fill", "memory_copy"];
 
@@ -496,6 +509,7 @@ lazy_static! {
                     &[ty.clone()],      // only type needed is the func itself
                     0,                  // -----------------------------------
                     0,                  // impls don't use other internals
+                    &BulkOps::default(), // impls don't use tables
                     &bin.names.module,
                 ),
                 ty.clone(),
@@ -4,6 +4,10 @@
 #![allow(clippy::missing_safety_doc, clippy::too_many_arguments)]
 
 pub mod binary;
+mod bulk;
+pub mod checkpoint;
+pub mod debugger;
+pub mod explain;
 mod host;
 pub mod machine;
 /// cbindgen:ignore
@@ -12,8 +16,11 @@ mod merkle;
 mod print;
 pub mod programs;
 mod reinterpret;
+pub mod simd;
+pub mod snapshot;
 pub mod utils;
 pub mod value;
+pub mod verify;
 pub mod wavm;
 
 #[cfg(test)]
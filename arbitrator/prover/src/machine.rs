@@ -5,11 +5,13 @@ use crate::{
     binary::{
         self, parse, ExportKind, ExportMap, FloatInstruction, Local, NameCustomSection, WasmBinary,
     },
+    bulk::BulkOps,
     host,
     memory::Memory,
     merkle::{Merkle, MerkleType},
     programs::{config::CompileConfig, meter::MeteredMachine, ModuleMod, StylusData},
     reinterpret::{ReinterpretAsSigned, ReinterpretAsUnsigned},
+    simd,
     utils::{file_bytes, CBytes, RemoteTableType},
     value::{ArbValueType, FunctionType, IntegerValType, ProgramCounter, Value},
     wavm::{
@@ -34,14 +36,14 @@ use std::{
     fmt::{self, Display},
     fs::File,
     hash::Hash,
-    io::{BufReader, BufWriter, Write},
+    io::{BufReader, BufWriter, Read, Write},
     num::Wrapping,
     ops::Add,
     path::{Path, PathBuf},
     sync::Arc,
 };
 use wasmer_types::FunctionIndex;
-use wasmparser::{DataKind, ElementItem, ElementKind, Operator, TableType};
+use wasmparser::{DataKind, ElementItem, ElementKind, TableType};
 
 #[cfg(feature = "rayon")]
 use rayon::prelude::*;
@@ -397,6 +399,13 @@ impl Module {
         func_types.extend(internals_types.clone());
         types.extend(internals_types);
 
+        // operators reading data segments call implementations specialized for each use
+        let bulk_offset = internals_offset + internals.len() as u32;
+        let bulk = BulkOps::new(bin, bulk_offset);
+        let bulk_impls = bulk.implement(bin, internals_offset)?;
+        func_types.extend(bulk_impls.iter().map(|f| f.ty.clone()));
+        types.extend(bulk_impls.iter().map(|f| f.ty.clone()));
+
         for c in &bin.codes {
             let idx = code.len();
             let func_ty = func_types[idx].clone();
@@ -411,6 +420,7 @@ impl Module {
                         &types,
                         func_type_idxs[idx],
                         internals_offset,
+                        &bulk,
                         bin_name,
                     )
                 },
@@ -419,6 +429,8 @@ impl Module {
             )?);
         }
         code.extend(internals);
+        code.extend(bulk_impls);
+
         ensure!(
             code.len() < (1usize << 31),
             "Module function count must be under 2^31",
@@ -450,11 +462,11 @@ impl Module {
         }
 
         for data in &bin.datas {
-            let (memory_index, mut init) = match data.kind {
+            let (memory_index, offset_expr) = match data.kind {
                 DataKind::Active {
                     memory_index,
                     offset_expr,
-                } => (memory_index, offset_expr.get_operators_reader()),
+                } => (memory_index, offset_expr),
                 _ => continue,
             };
             ensure!(
@@ -462,9 +474,9 @@ impl Module {
                 "Attempted to write to nonexistant memory"
             );
 
-            let offset = match (init.read()?, init.read()?, init.eof()) {
-                (Operator::I32Const { value }, Operator::End, true) => value as usize,
-                x => bail!("Non-constant element segment offset expression {:?}", x),
+            let offset = match binary::eval_const_expr(&offset_expr) {
+                Ok(Value::I32(value)) => value as usize,
+                x => bail!("Non-constant data segment offset expression {:?}", x),
             };
             if !matches!(
                 offset.checked_add(data.data.len()),
@@ -487,16 +499,29 @@ impl Module {
             });
         }
 
+        // data segments read by bulk memory operators have globals for their lengths,
+        // with their contents compiled into the code reading them
+        let mut globals = bin.globals.clone();
+        if bulk.datas().is_some() {
+            for data in &bin.datas {
+                let len = match data.kind {
+                    DataKind::Passive => data.data.len(),
+                    _ => 0, // active segments are dropped upon instantiation
+                };
+                globals.push(Value::I32(len as u32));
+            }
+        }
+
         for elem in &bin.elements {
-            let (t, mut init) = match elem.kind {
+            let (t, offset_expr) = match elem.kind {
                 ElementKind::Active {
                     table_index,
                     offset_expr,
-                } => (table_index, offset_expr.get_operators_reader()),
+                } => (table_index, offset_expr),
                 _ => continue,
             };
-            let offset = match (init.read()?, init.read()?, init.eof()) {
-                (Operator::I32Const { value }, Operator::End, true) => value as usize,
+            let offset = match binary::eval_const_expr(&offset_expr) {
+                Ok(Value::I32(value)) => value as usize,
                 x => bail!("Non-constant element segment offset expression {:?}", x),
             };
             let Some(table) = tables.get_mut(t as usize) else {
@@ -544,7 +569,7 @@ impl Module {
 
         Ok(Module {
             memory,
-            globals: bin.globals.clone(),
+            globals,
             tables_merkle: Merkle::new(MerkleType::Table, tables_hashes?),
             tables,
             funcs_merkle: Arc::new(Merkle::new(
@@ -667,7 +692,7 @@ pub struct GlobalState {
 }
 
 impl GlobalState {
-    fn hash(&self) -> Bytes32 {
+    pub(crate) fn hash(&self) -> Bytes32 {
         let mut h = Keccak256::new();
         h.update("Global state:");
         for item in self.bytes32_vals {
@@ -733,8 +758,20 @@ impl Display for MachineStatus {
 pub struct ModuleState<'a> {
     globals: Cow<'a, Vec<Value>>,
     memory: Cow<'a, Memory>,
+    tables: Cow<'a, Vec<Table>>,
+}
+
+/// A module's state as serialized before tables were, when they couldn't change.
+#[derive(Serialize, Deserialize)]
+struct LegacyModuleState<'a> {
+    globals: Cow<'a, Vec<Value>>,
+    memory: Cow<'a, Memory>,
 }
 
+/// Prefixes serialized states, distinguishing them from those written before tables were included.
+/// Were a legacy state to start with these bytes, it'd have executed over 10^18 steps.
+const STATE_MAGIC: [u8; 8] = *b"wavmst01";
+
 /// Represents if the machine can recover and where to jump back if so.
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum ThreadState {
@@ -745,14 +782,14 @@ pub enum ThreadState {
 }
 
 impl ThreadState {
-    fn is_cothread(&self) -> bool {
+    pub(crate) fn is_cothread(&self) -> bool {
         match self {
             ThreadState::Main => false,
             ThreadState::CoThread(_) => true,
         }
     }
 
-    fn serialize(&self) -> Bytes32 {
+    pub(crate) fn serialize(&self) -> Bytes32 {
         match self {
             ThreadState::Main => Bytes32([0xff; 32]),
             ThreadState::CoThread(pc) => (*pc).serialize(),
@@ -761,20 +798,38 @@ impl ThreadState {
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct MachineState<'a> {
+pub struct MachineState<'a, M = ModuleState<'a>> {
     steps: u64, // Not part of machine hash
     thread_state: ThreadState,
     status: MachineStatus,
     value_stacks: Cow<'a, Vec<Vec<Value>>>,
     internal_stack: Cow<'a, Vec<Value>>,
     frame_stacks: Cow<'a, Vec<Vec<StackFrame>>>,
-    modules: Vec<ModuleState<'a>>,
+    modules: Vec<M>,
     global_state: GlobalState,
     pc: ProgramCounter,
     stdio_output: Cow<'a, Vec<u8>>,
     initial_hash: Bytes32,
 }
 
+impl<'a, M> MachineState<'a, M> {
+    fn map_modules<N>(self, f: impl FnMut(M) -> N) -> MachineState<'a, N> {
+        MachineState {
+            steps: self.steps,
+            thread_state: self.thread_state,
+            status: self.status,
+            value_stacks: self.value_stacks,
+            internal_stack: self.internal_stack,
+            frame_stacks: self.frame_stacks,
+            modules: self.modules.into_iter().map(f).collect(),
+            global_state: self.global_state,
+            pc: self.pc,
+            stdio_output: self.stdio_output,
+            initial_hash: self.initial_hash,
+        }
+    }
+}
+
 pub type PreimageResolver = Arc<dyn Fn(u64, Bytes32) -> Option<CBytes>>;
 
 /// Wraps a preimage resolver to provide an easier API
@@ -996,7 +1051,7 @@ where
 }
 
 #[must_use]
-fn exec_ibin_op<T>(a: T, b: T, op: IBinOpType) -> Option<T>
+pub(crate) fn exec_ibin_op<T>(a: T, b: T, op: IBinOpType) -> Option<T>
 where
     Wrapping<T>: ReinterpretAsSigned,
     T: Zero,
@@ -1031,7 +1086,7 @@ where
 }
 
 #[must_use]
-fn exec_iun_op<T>(a: T, op: IUnOpType) -> u32
+pub(crate) fn exec_iun_op<T>(a: T, op: IUnOpType) -> u32
 where
     T: PrimInt,
 {
@@ -1042,7 +1097,7 @@ where
     }
 }
 
-fn exec_irel_op<T>(a: T, b: T, op: IRelOpType) -> Value
+pub(crate) fn exec_irel_op<T>(a: T, b: T, op: IRelOpType) -> Value
 where
     T: Ord,
 {
@@ -1110,6 +1165,7 @@ impl Machine {
     pub fn from_user_path(path: &Path, compile: &CompileConfig) -> Result<Self> {
         let data = std::fs::read(path)?;
         let wasm = wasmer::wat2wasm(&data)?;
+        let wasm = simd::lower_user(&wasm, compile.version)?;
         let mut bin = binary::parse(&wasm, Path::new("user"))?;
         let stylus_data = bin.instrument(compile)?;
 
@@ -1142,7 +1198,8 @@ impl Machine {
     /// Adds a user program to the machine's known set of wasms, compiling it into a link-able module.
     /// Note that the module produced will need to be configured before execution via hostio calls.
     pub fn add_program(&mut self, wasm: &[u8], version: u16, debug_funcs: bool) -> Result<Bytes32> {
-        let mut bin = binary::parse(wasm, Path::new("user"))?;
+        let wasm = simd::lower_user(wasm, version)?;
+        let mut bin = binary::parse(&wasm, Path::new("user"))?;
         let config = CompileConfig::version(version, debug_funcs);
         let stylus_data = bin.instrument(&config)?;
 
@@ -1491,15 +1548,35 @@ impl Machine {
     pub fn serialize_state<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let mut f = File::create(path)?;
         let mut writer = BufWriter::new(&mut f);
+        self.serialize_state_into(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        f.sync_data()?;
+        Ok(())
+    }
+
+    /// Writes the parts of the machine that change as it executes.
+    pub fn serialize_state_into<W: Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(&STATE_MAGIC)?;
+        bincode::serialize_into(writer, &self.state(true))?;
+        Ok(())
+    }
+
+    /// The parts of the machine that change as it executes, with or without its memories.
+    pub(crate) fn state(&self, memories: bool) -> MachineState<'_> {
         let modules = self
             .modules
             .iter()
             .map(|m| ModuleState {
                 globals: Cow::Borrowed(&m.globals),
-                memory: Cow::Borrowed(&m.memory),
+                memory: match memories {
+                    true => Cow::Borrowed(&m.memory),
+                    false => Cow::Owned(Memory::default()),
+                },
+                tables: Cow::Borrowed(&m.tables),
             })
             .collect();
-        let state = MachineState {
+        MachineState {
             steps: self.steps,
             thread_state: self.thread_state,
             status: self.status,
@@ -1511,33 +1588,91 @@ impl Machine {
             pc: self.pc,
             stdio_output: Cow::Borrowed(&self.stdio_output),
             initial_hash: self.initial_hash,
-        };
-        bincode::serialize_into(&mut writer, &state)?;
-        writer.flush()?;
-        drop(writer);
-        f.sync_data()?;
-        Ok(())
+        }
     }
 
     // Requires that this is the same base machine. If this returns an error, it has not mutated `self`.
     pub fn deserialize_and_replace_state<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
         let reader = BufReader::new(File::open(path)?);
-        let new_state: MachineState = bincode::deserialize_from(reader)?;
+        self.deserialize_and_replace_state_from(reader)
+    }
+
+    /// Restores state written by [`Machine::serialize_state_into`], or by versions prior to
+    /// tables being included, in which case the machine's tables are left as they are.
+    /// Requires that this is the same base machine. If this returns an error, it has not mutated `self`.
+    pub fn deserialize_and_replace_state_from<R: Read>(&mut self, mut reader: R) -> Result<()> {
+        let mut magic = [0; STATE_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+
+        let new_state: MachineState = match magic == STATE_MAGIC {
+            true => bincode::deserialize_from(reader)?,
+            false => {
+                let legacy: MachineState<LegacyModuleState> =
+                    bincode::deserialize_from(magic.as_slice().chain(reader))?;
+                let mut tables = self.modules.iter().map(|m| m.tables.clone());
+                legacy.map_modules(|m| ModuleState {
+                    globals: m.globals,
+                    memory: m.memory,
+                    tables: Cow::Owned(tables.next().unwrap_or_default()),
+                })
+            }
+        };
+        self.replace_state(new_state, true)?;
+        if self.modules_merkle.is_some() {
+            self.start_merkle_caching();
+        }
+        Ok(())
+    }
+
+    /// Replaces the parts of the machine that change as it executes, optionally keeping its memories.
+    /// If this returns an error, it has not mutated `self`. Callers must refresh any cached merkles.
+    pub(crate) fn replace_state(&mut self, new_state: MachineState, memories: bool) -> Result<()> {
         if self.initial_hash != new_state.initial_hash {
             bail!(
                 "attempted to load deserialize machine with initial hash {} into machine with initial hash {}",
                 new_state.initial_hash, self.initial_hash,
             );
         }
-        assert_eq!(self.modules.len(), new_state.modules.len());
+        ensure!(
+            self.modules.len() == new_state.modules.len(),
+            "attempted to load state for {} modules into a machine with {}",
+            new_state.modules.len(),
+            self.modules.len(),
+        );
+
+        // table merkles aren't serialized, so rebuild them before mutating anything
+        let mut modules = vec![];
+        for new_module_state in new_state.modules {
+            let mut tables = new_module_state.tables.into_owned();
+            for table in &mut tables {
+                table.elems_merkle = Merkle::new(
+                    MerkleType::TableElement,
+                    table.elems.iter().map(TableElement::hash).collect(),
+                );
+            }
+            let hashes: Result<_> = tables.iter().map(Table::hash).collect();
+            let tables_merkle = Merkle::new(MerkleType::Table, hashes?);
+            modules.push((
+                new_module_state.globals,
+                new_module_state.memory,
+                tables,
+                tables_merkle,
+            ));
+        }
 
         // Start mutating the machine. We must not return an error past this point.
-        for (module, new_module_state) in self.modules.iter_mut().zip(new_state.modules.into_iter())
+        for (module, (globals, memory, tables, tables_merkle)) in
+            self.modules.iter_mut().zip(modules)
         {
-            module.globals = new_module_state.globals.into_owned();
-            module.memory = new_module_state.memory.into_owned();
+            module.globals = globals.into_owned();
+            if memories {
+                module.memory = memory.into_owned();
+            }
+            module.tables = tables;
+            module.tables_merkle = tables_merkle;
         }
         self.steps = new_state.steps;
+        self.thread_state = new_state.thread_state;
         self.status = new_state.status;
         self.value_stacks = new_state.value_stacks.into_owned();
         self.internal_stack = new_state.internal_stack.into_owned();
@@ -1548,6 +1683,21 @@ impl Machine {
         Ok(())
     }
 
+    /// Each module's memory, in order.
+    pub(crate) fn memories(&self) -> impl Iterator<Item = &Memory> {
+        self.modules.iter().map(|m| &m.memory)
+    }
+
+    /// Each module's memory, in order.
+    pub(crate) fn memories_mut(&mut self) -> impl Iterator<Item = &mut Memory> {
+        self.modules.iter_mut().map(|m| &mut m.memory)
+    }
+
+    /// Whether the machine's merkle trees are cached.
+    pub(crate) fn is_caching_merkles(&self) -> bool {
+        self.modules_merkle.is_some()
+    }
+
     pub fn start_merkle_caching(&mut self) {
         for module in &mut self.modules {
             module.memory.cache_merkle_tree();
@@ -1689,6 +1839,11 @@ impl Machine {
         bail!("global {} not found", name.red())
     }
 
+    /// The globals of the module at the given offset, if one exists.
+    pub fn get_globals(&self, module: u32) -> Option<&[Value]> {
+        self.modules.get(module as usize).map(|m| &m.globals[..])
+    }
+
     pub fn read_memory(&self, module: u32, ptr: u32, len: u32) -> Result<&[u8]> {
         let Some(module) = &self.modules.get(module as usize) else {
             bail!("no module at offset {}", module.red())
@@ -1745,6 +1900,16 @@ impl Machine {
         self.steps
     }
 
+    /// The first sequencer message not in the inbox, reading which leaves the machine too far.
+    pub(crate) fn get_first_too_far(&self) -> u64 {
+        self.first_too_far
+    }
+
+    /// The hash of the machine before it executed any steps.
+    pub fn get_initial_hash(&self) -> Bytes32 {
+        self.initial_hash
+    }
+
     pub fn step_n(&mut self, n: u64) -> Result<()> {
         if self.is_halted() {
             return Ok(());
@@ -2953,6 +3118,19 @@ impl Machine {
         &self.internal_stack
     }
 
+    /// The locals of the innermost frame, which are empty before any frame is initialized.
+    pub fn get_locals(&self) -> &[Value] {
+        match self.get_frame_stack().last() {
+            Some(frame) => &frame.locals,
+            None => &[],
+        }
+    }
+
+    /// The number of frames on the active frame stack.
+    pub fn get_call_depth(&self) -> usize {
+        self.get_frame_stack().len()
+    }
+
     pub fn get_global_state(&self) -> GlobalState {
         self.global_state.clone()
     }
@@ -3020,3 +3198,67 @@ impl Machine {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_legacy_state() -> Result<()> {
+        let wat = r#"
+            (module
+                (memory 1 1)
+                (func (export "test") (param $n i32) (result i32)
+                    (loop $next
+                        (i32.store (i32.shl (local.get $n) (i32.const 2)) (local.get $n))
+                        (local.tee $n (i32.sub (local.get $n) (i32.const 1)))
+                        (br_if $next)
+                    )
+                    (i32.load (i32.const 4))
+                )
+            )"#;
+        let machine = || -> Result<Machine> {
+            let wasm = wasmer::wat2wasm(wat.as_bytes())?;
+            let bin = binary::parse(&wasm, Path::new("user"))?;
+            let mut mach = Machine::from_binaries(
+                &[],
+                bin,
+                false,
+                false,
+                true,
+                false,
+                false,
+                GlobalState::default(),
+                HashMap::default(),
+                Arc::new(|_, _| panic!("tried to read preimage")),
+                None,
+            )?;
+            let (module, func) = mach.find_module_func("user", "test")?;
+            mach.jump_into_func(module, func, vec![Value::I32(8)])?;
+            Ok(mach)
+        };
+
+        let mut mach = machine()?;
+        mach.step_n(20)?;
+
+        // states written before tables were included still load
+        let legacy = mach.state(true).map_modules(|m| LegacyModuleState {
+            globals: m.globals,
+            memory: m.memory,
+        });
+        let legacy = bincode::serialize(&legacy)?;
+        let mut restored = machine()?;
+        restored.deserialize_and_replace_state_from(&legacy[..])?;
+        assert_eq!(restored.get_steps(), 20);
+        assert_eq!(restored.hash(), mach.hash());
+
+        // as do current ones
+        let mut state = vec![];
+        mach.serialize_state_into(&mut state)?;
+        assert!(state.starts_with(&STATE_MAGIC));
+        let mut restored = machine()?;
+        restored.deserialize_and_replace_state_from(&state[..])?;
+        assert_eq!(restored.hash(), mach.hash());
+        Ok(())
+    }
+}
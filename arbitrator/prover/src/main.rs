@@ -4,11 +4,14 @@
 #![cfg(feature = "native")]
 
 use arbutil::{format, Bytes32, Color, DebugColor};
-use eyre::{eyre, Context, Result};
+use eyre::{ensure, eyre, Context, Result};
 use fnv::{FnvHashMap as HashMap, FnvHashSet as HashSet};
 use prover::{
+    debugger::Debugger,
+    explain,
     machine::{GlobalState, InboxIdentifier, Machine, MachineStatus, PreimageResolver, ProofInfo},
     utils::{file_bytes, CBytes},
+    verify::{self, ExecutionContext},
     wavm::Opcode,
 };
 use sha3::{Digest, Keccak256};
@@ -24,7 +27,8 @@ use structopt::StructOpt;
 #[derive(StructOpt)]
 #[structopt(name = "arbitrator-prover")]
 struct Opts {
-    binary: PathBuf,
+    #[structopt(required_unless = "explain-proof")]
+    binary: Option<PathBuf>,
     #[structopt(short, long)]
     libraries: Vec<PathBuf>,
     #[structopt(short, long)]
@@ -86,6 +90,21 @@ struct Opts {
     skip_until_host_io: bool,
     #[structopt(long)]
     max_steps: Option<u64>,
+    /// Check each proof with the Rust one-step verifier
+    #[structopt(long)]
+    verify_proofs: bool,
+    /// Decode a hex-encoded proof into its parts and exit
+    #[structopt(long)]
+    explain_proof: Option<String>,
+    /// Step through the machine interactively instead of generating proofs
+    #[structopt(long)]
+    debug: bool,
+    /// Steps between the debugger's snapshots, which bound how far it replays to move back
+    #[structopt(long, default_value = "1000000")]
+    debug_checkpoint_interval: u64,
+    /// The most snapshots the debugger keeps, each a full copy of the machine's state
+    #[structopt(long, default_value = "64")]
+    debug_checkpoint_limit: usize,
 }
 
 fn parse_size_delim(path: &Path) -> Result<Vec<Vec<u8>>> {
@@ -140,6 +159,13 @@ const DELAYED_HEADER_LEN: usize = 112; // also in test-case's host-io.rs & contr
 fn main() -> Result<()> {
     let opts = Opts::from_args();
 
+    if let Some(proof) = &opts.explain_proof {
+        let proof = hex::decode(proof.trim_start_matches("0x")).wrap_err("invalid proof hex")?;
+        println!("{}", explain::explain(&proof)?);
+        return Ok(());
+    }
+    let binary = opts.binary.clone().unwrap();
+
     let mut inbox_contents = HashMap::default();
     let mut inbox_position = opts.inbox_position;
     let mut delayed_position = opts.delayed_inbox_position;
@@ -193,7 +219,7 @@ fn main() -> Result<()> {
 
     let mut mach = Machine::from_paths(
         &opts.libraries,
-        &opts.binary,
+        &binary,
         true,
         opts.always_merkleize,
         opts.allow_hostapi,
@@ -228,6 +254,15 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
+    if opts.debug {
+        let mut debugger = Debugger::new(
+            mach,
+            opts.debug_checkpoint_interval,
+            opts.debug_checkpoint_limit,
+        )?;
+        return debugger.repl(std::io::stdin().lock());
+    }
+
     println!("Starting machine hash: {}", mach.hash());
 
     let mut proofs: Vec<ProofInfo> = Vec::new();
@@ -361,9 +396,19 @@ fn main() -> Result<()> {
             if !seen_states.insert(before) {
                 break;
             }
+            let context = ExecutionContext::new(&mach);
             let proof = mach.serialize_proof();
             mach.step_n(1)?;
             let after = mach.hash();
+            if opts.verify_proofs {
+                let verified = verify::verify(&context, before, &proof)
+                    .wrap_err_with(|| format!("failed to verify proof {}", proofs.len()))?;
+                ensure!(
+                    verified == after,
+                    "proof {} verified to {verified} instead of {after}",
+                    proofs.len(),
+                );
+            }
             println!(" - done");
             proofs.push(ProofInfo {
                 before: before.to_string(),
@@ -387,9 +432,15 @@ fn main() -> Result<()> {
 
     if !proofs.is_empty() && mach.is_halted() {
         let hash = mach.hash();
+        let proof = mach.serialize_proof();
+        if opts.verify_proofs {
+            let context = ExecutionContext::new(&mach);
+            let verified = verify::verify(&context, hash, &proof)?;
+            ensure!(verified == hash, "halted proof changed the machine");
+        }
         proofs.push(ProofInfo {
             before: hash.to_string(),
-            proof: hex::encode(mach.serialize_proof()),
+            proof: hex::encode(proof),
             after: hash.to_string(),
         });
     }
@@ -443,7 +494,6 @@ fn main() -> Result<()> {
             }
         }
 
-        let opts_binary = opts.binary;
         let opts_libraries = opts.libraries;
         let format_pc = |module_num: usize, func_num: usize| -> (String, String) {
             let Some(names) = mach.get_module_names(module_num) else {
@@ -455,7 +505,7 @@ fn main() -> Result<()> {
             let module_name = if module_num == 0 {
                 names.module.clone()
             } else if module_num == &opts_libraries.len() + 1 {
-                opts_binary.file_name().unwrap().to_str().unwrap().into()
+                binary.file_name().unwrap().to_str().unwrap().into()
             } else {
                 opts_libraries[module_num - 1]
                     .file_name()
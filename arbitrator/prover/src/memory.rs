@@ -141,6 +141,25 @@ impl Memory {
         buf
     }
 
+    /// Overwrites a leaf, whose bytes past the end of the memory must be zero.
+    #[must_use]
+    pub fn set_leaf_data(&mut self, leaf_idx: usize, data: [u8; Self::LEAF_SIZE]) -> bool {
+        let idx = match leaf_idx.checked_mul(Self::LEAF_SIZE) {
+            Some(x) if x < self.buffer.len() => x,
+            _ => return false,
+        };
+        let size = std::cmp::min(Self::LEAF_SIZE, self.buffer.len() - idx);
+        if data[size..].iter().any(|&x| x != 0) {
+            return false;
+        }
+        self.buffer[idx..(idx + size)].copy_from_slice(&data[..size]);
+
+        if let Some(merkle) = &mut self.merkle {
+            merkle.set(leaf_idx, hash_leaf(data));
+        }
+        true
+    }
+
     pub fn hash(&self) -> Bytes32 {
         let mut h = Keccak256::new();
         h.update("Memory:");
@@ -50,7 +50,7 @@ pub struct Merkle {
     min_depth: usize,
 }
 
-fn hash_node(ty: MerkleType, a: Bytes32, b: Bytes32) -> Bytes32 {
+pub(crate) fn hash_node(ty: MerkleType, a: Bytes32, b: Bytes32) -> Bytes32 {
     let mut h = Keccak256::new();
     h.update(ty.get_prefix());
     h.update(a);
@@ -142,6 +142,49 @@ impl Merkle {
         proof
     }
 
+    /// Finds the leaves that differ from those of a prior tree of the same depth.
+    /// Only subtrees whose roots differ are descended into.
+    pub fn diff(&self, prior: &Merkle) -> Vec<usize> {
+        assert_eq!(
+            self.layers.len(),
+            prior.layers.len(),
+            "merkle depths differ"
+        );
+        let node = |merkle: &Merkle, layer: usize, idx: usize| {
+            let empty = merkle.empty_layers[layer];
+            merkle.layers[layer].get(idx).cloned().unwrap_or(empty)
+        };
+        let Some(top) = self.layers.len().checked_sub(1) else {
+            return vec![];
+        };
+        let mut dirty = vec![0];
+        for layer in (0..=top).rev() {
+            if layer != top {
+                dirty = dirty.iter().flat_map(|i| [2 * i, 2 * i + 1]).collect();
+            }
+            dirty.retain(|&i| node(self, layer, i) != node(prior, layer, i));
+        }
+        dirty
+    }
+
+    /// Makes this tree equal another of the same depth, given the leaves at which they differ.
+    /// Only the paths from those leaves to the root are copied.
+    pub fn copy_paths(&mut self, other: &Merkle, leaves: &[usize]) {
+        assert_eq!(
+            self.layers.len(),
+            other.layers.len(),
+            "merkle depths differ"
+        );
+        for (layer, (dest, src)) in self.layers.iter_mut().zip(&other.layers).enumerate() {
+            dest.resize(src.len(), self.empty_layers[layer]);
+            for idx in leaves.iter().map(|x| x >> layer) {
+                if let Some(hash) = src.get(idx) {
+                    dest[idx] = *hash;
+                }
+            }
+        }
+    }
+
     /// Adds a new leaf to the merkle
     /// Currently O(n) in the number of leaves (could be log(n))
     pub fn push_leaf(&mut self, leaf: Bytes32) {
@@ -2,12 +2,15 @@
 // For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
 
 use crate::{
+    explain::{ProofExplanation, ProofPart},
     host::InternalFunc,
-    machine::Module,
-    value::{FunctionType, Value},
+    machine::{Module, ThreadState},
+    memory::Memory,
+    value::{ArbValueType, FunctionType, Value},
+    verify::{MemoryLeaf, ModuleProof},
     wavm::{self, Opcode},
 };
-use arbutil::Color;
+use arbutil::{format, Bytes32, Color};
 use fnv::FnvHashSet as HashSet;
 use num_traits::FromPrimitive;
 use std::fmt::{self, Display};
@@ -301,3 +304,239 @@ impl Display for Module {
         Ok(())
     }
 }
+
+impl Display for ProofExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut pad = 0;
+
+        macro_rules! w {
+            ($($args:expr),* $(,)?) => {{
+                let text = format!($($args),*);
+                write!(f, "{:pad$}{text}", "")?;
+            }};
+        }
+        macro_rules! wln {
+            ($($args:expr),* $(,)?) => {{
+                w!($($args),*);
+                writeln!(f)?;
+            }};
+        }
+
+        let hash = |hash: &Bytes32| format!("{hash}").orange();
+        let proof = |proof: &[Bytes32]| {
+            let siblings = format::commas(proof.iter().map(hash));
+            format!("({} {siblings})", "proof".grey())
+        };
+        let stack = |items: String, below: &Bytes32| {
+            format!("[{items}] {} {}", "below".grey(), hash(below))
+        };
+        let module = |module: &ModuleProof| {
+            let pages = module.memory_size / WASM_PAGE_SIZE as u64;
+            let memory = format!("{pages} {}", module.memory_max_size);
+            format!(
+                "({} {} {} {} {} {} {} {} {} {} {} {}",
+                "module".grey(),
+                "globals".grey(),
+                hash(&module.globals_root),
+                "memory".grey(),
+                memory.mint(),
+                hash(&module.memory_root),
+                "tables".grey(),
+                hash(&module.tables_root),
+                "funcs".grey(),
+                hash(&module.funcs_root),
+                "internals".grey(),
+                module.internals_offset.mint(),
+            )
+        };
+        let leaf = |leaf: &MemoryLeaf| {
+            let start = leaf.index * Memory::LEAF_SIZE;
+            let range = format!("[{start:#06x}-{:#06x}]", start + Memory::LEAF_SIZE - 1);
+            format!(
+                "({} {}: {} {})",
+                "leaf".grey(),
+                range.grey(),
+                hex::encode(leaf.data).yellow(),
+                proof(&leaf.proof),
+            )
+        };
+
+        let mach = &self.machine;
+        let values = &mach.values;
+        let frames = &mach.frames;
+        let multistack = |inactive: &Bytes32, rest: &Bytes32| {
+            let active = match mach.thread_state {
+                ThreadState::Main => "main",
+                ThreadState::CoThread(_) => "cothread",
+            };
+            format!(
+                "{} {} {} {} {} {}",
+                "in".grey(),
+                active.pink(),
+                "inactive".grey(),
+                hash(inactive),
+                "rest".grey(),
+                hash(rest),
+            )
+        };
+
+        let status = format!("{:?}", mach.status);
+        wln!("({} {}", "proof".grey(), status.pink());
+        pad += 4;
+
+        let items = format::commas(&values.active.items);
+        let (below, inactive, rest) = (&values.active.below, &values.inactive, &values.rest);
+        wln!(
+            "({} {} {})",
+            "values".grey(),
+            stack(items, below),
+            multistack(inactive, rest)
+        );
+        let items = format::commas(&mach.internals.items);
+        wln!(
+            "({} {})",
+            "internals".grey(),
+            stack(items, &mach.internals.below)
+        );
+        let items = frames.active.items.iter().map(|frame| {
+            format!(
+                "{} {} {} {} {}",
+                frame.return_ref,
+                "locals".grey(),
+                hash(&frame.locals_root),
+                "caller".grey(),
+                format!("{}:{}", frame.caller_module, frame.caller_module_internals).mint(),
+            )
+        });
+        let (below, inactive, rest) = (&frames.active.below, &frames.inactive, &frames.rest);
+        wln!(
+            "({} {} {})",
+            "frames".grey(),
+            stack(format::commas(items), below),
+            multistack(inactive, rest)
+        );
+        wln!("({} {})", "global_state".grey(), hash(&mach.global_state));
+        wln!("({} {})", "pc".grey(), mach.pc);
+        if let ThreadState::CoThread(recovery) = mach.thread_state {
+            wln!("({} {})", "recovery".grey(), recovery);
+        }
+        wln!("({} {})", "modules".grey(), hash(&mach.modules_root));
+        wln!("{}", module(&mach.module));
+        pad += 4;
+        wln!("{})", proof(&self.module_proof));
+        pad -= 4;
+
+        if let Some(step) = &self.step {
+            let data = match step.inst.proving_argument_data {
+                Some(data) => hash(&data),
+                None => format!("{:#x}", step.inst.argument_data).mint(),
+            };
+            let opcode = format!("{:?}", step.inst.opcode).pink();
+            wln!("({} {opcode} {data}", "inst".grey());
+            pad += 4;
+            wln!("{}", proof(&step.inst_proof));
+            wln!("({} {}))", "func".grey(), proof(&step.func_proof));
+            pad -= 4;
+
+            for part in &step.extra {
+                match part {
+                    ProofPart::Local {
+                        index,
+                        value,
+                        proof: siblings,
+                    }
+                    | ProofPart::Global {
+                        index,
+                        value,
+                        proof: siblings,
+                    } => {
+                        let kind = match part {
+                            ProofPart::Local { .. } => "local",
+                            _ => "global",
+                        };
+                        let name = format!("${kind}_{index}").pink();
+                        wln!("({} {name} {value} {})", kind.grey(), proof(siblings));
+                    }
+                    ProofPart::MemoryLeaf(data) => wln!("{}", leaf(data)),
+                    ProofPart::FuncType(ty) => wln!("({} {})", "type".grey(), hash(ty)),
+                    ProofPart::Table(table) => {
+                        let label = format!("$table_{}", table.index).pink();
+                        let ty = ArbValueType::try_from(table.ty)
+                            .map(|ty| format!("{ty:?}"))
+                            .unwrap_or_else(|_| format!("type {}", table.ty));
+                        wln!(
+                            "({} {label} {} {} {})",
+                            "table".grey(),
+                            format!("{} {ty}", table.len).mint(),
+                            hash(&table.root),
+                            proof(&table.proof),
+                        );
+                    }
+                    ProofPart::Element(elem) => {
+                        let index = format!("[{:#x}]", elem.index).grey();
+                        wln!(
+                            "({} {index} {} {} {} {})",
+                            "elem".grey(),
+                            elem.val,
+                            "type".grey(),
+                            hash(&elem.func_ty),
+                            proof(&elem.proof),
+                        );
+                    }
+                    ProofPart::GlobalState(state) => {
+                        let bytes32 = format::commas(state.bytes32_vals.iter().map(hash));
+                        let u64s = format::commas(state.u64_vals.iter().map(|x| x.mint()));
+                        wln!("({} [{bytes32}] [{u64s}])", "global_state".grey());
+                    }
+                    ProofPart::Module {
+                        index,
+                        module: data,
+                        proof: siblings,
+                    } => {
+                        wln!("({} {} {}", "callee".grey(), index.mint(), module(data));
+                        pad += 4;
+                        wln!("{}))", proof(siblings));
+                        pad -= 4;
+                    }
+                    ProofPart::LastModule(last) => {
+                        let index = last.index.mint();
+                        wln!("({} {index} {}", "last".grey(), module(&last.module));
+                        pad += 4;
+                        match &last.next_proof {
+                            Some(next) => {
+                                wln!("{}", proof(&last.siblings));
+                                wln!("({} {})))", "next".grey(), proof(next));
+                            }
+                            None => wln!("{}))", proof(&last.siblings)),
+                        }
+                        pad -= 4;
+                    }
+                    ProofPart::Preimage(data) | ProofPart::InboxMessage(data) => {
+                        let kind = match part {
+                            ProofPart::Preimage(_) => "preimage",
+                            _ => "message",
+                        };
+                        wln!("({} {})", kind.grey(), hex::encode(data).yellow());
+                    }
+                    ProofPart::PoppedCothread { values, frames } => {
+                        let remaining = |(last, rest): &(Bytes32, Bytes32)| {
+                            let (last, rest) = (hash(last), hash(rest));
+                            format!("{} {last} {} {rest}", "last".grey(), "rest".grey())
+                        };
+                        wln!(
+                            "({} ({} {}) ({} {}))",
+                            "popped".grey(),
+                            "values".grey(),
+                            remaining(values),
+                            "frames".grey(),
+                            remaining(frames),
+                        );
+                    }
+                }
+            }
+        }
+        pad -= 4;
+        wln!(")");
+        Ok(())
+    }
+}
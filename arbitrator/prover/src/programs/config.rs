@@ -3,10 +3,17 @@
 
 #![allow(clippy::field_reassign_with_default)]
 
-use crate::{programs::meter, value::FunctionType};
-use derivative::Derivative;
+use crate::value::FunctionType;
+use arbutil::{
+    crypto,
+    operator::{OperatorCode, OperatorInfo},
+    Bytes32,
+};
+use eyre::{bail, Context, Result};
 use fnv::FnvHashMap as HashMap;
-use std::fmt::Debug;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt::Debug, fs, path::Path, str::FromStr};
 use wasmer_types::{Pages, SignatureIndex, WASM_PAGE_SIZE};
 use wasmparser::Operator;
 
@@ -14,7 +21,7 @@ use wasmparser::Operator;
 use {
     super::{
         counter::Counter, depth::DepthChecker, dynamic::DynamicMeter, heap::HeapBound,
-        meter::Meter, start::StartMover, MiddlewareWrapper,
+        meter::Meter, profiler::Profiler, start::StartMover, MiddlewareWrapper,
     },
     std::sync::Arc,
     wasmer::{Cranelift, CraneliftOptLevel, Store},
@@ -82,7 +89,52 @@ impl PricingParams {
 }
 
 pub type SigMap = HashMap<SignatureIndex, FunctionType>;
-pub type OpCosts = fn(&Operator, &SigMap) -> u64;
+
+/// Associates operators to their ink costs.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct OpCosts {
+    /// The ink of each operator, keyed by name
+    #[serde(default)]
+    pub ops: BTreeMap<OperatorCode, u64>,
+    /// The ink of operators not in the table, which are otherwise unsupported
+    #[serde(default)]
+    pub fallback: Option<u64>,
+    /// The additional ink per `BrTable` target
+    #[serde(default)]
+    pub br_table_target: u64,
+    /// The additional ink per `CallIndirect` input
+    #[serde(default)]
+    pub call_indirect_input: u64,
+}
+
+impl OpCosts {
+    /// Prices every operator the same.
+    pub fn uniform(ink: u64) -> Self {
+        Self {
+            fallback: Some(ink),
+            ..Self::default()
+        }
+    }
+
+    /// The ink cost of an operator, which is `u64::MAX` when unsupported.
+    pub fn price(&self, op: &Operator, sigs: &SigMap) -> u64 {
+        let Some(ink) = self.ops.get(&op.code()).copied().or(self.fallback) else {
+            return u64::MAX;
+        };
+        match op {
+            Operator::BrTable { targets } => {
+                let targets = self.br_table_target.saturating_mul(targets.len().into());
+                ink.saturating_add(targets)
+            }
+            Operator::CallIndirect { type_index, .. } => {
+                let ty = sigs.get(&SignatureIndex::from_u32(*type_index));
+                let inputs = ty.expect("no type").inputs.len() as u64;
+                ink.saturating_add(self.call_indirect_input.saturating_mul(inputs))
+            }
+            _ => ink,
+        }
+    }
+}
 
 #[derive(Clone, Debug, Default)]
 pub struct CompileConfig {
@@ -92,10 +144,33 @@ pub struct CompileConfig {
     pub pricing: CompilePricingParams,
     /// Memory bounds
     pub bounds: CompileMemoryParams,
+    /// The compiler producing native code
+    pub compiler: NativeCompiler,
     /// Debug parameters for test chains
     pub debug: CompileDebugParams,
 }
 
+/// The compiler producing a program's native code.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum NativeCompiler {
+    /// Compiles in linear time, as activation requires
+    #[default]
+    Singlepass,
+    /// Compiles slowly but produces optimized code, as suits hot programs
+    Cranelift(OptLevel),
+}
+
+/// How hard Cranelift works to optimize.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum OptLevel {
+    /// No optimizations
+    None,
+    /// Optimizes for speed
+    Speed,
+    /// Optimizes for speed and code size
+    SpeedAndSize,
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct CompileMemoryParams {
     /// The maximum number of pages a program may start with
@@ -106,11 +181,9 @@ pub struct CompileMemoryParams {
     pub max_frame_contention: u16,
 }
 
-#[derive(Clone, Derivative)]
-#[derivative(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct CompilePricingParams {
     /// Associates opcodes to their ink costs
-    #[derivative(Debug = "ignore")]
     pub costs: OpCosts,
     /// Cost of checking the amount of ink left.
     pub ink_header_cost: u64,
@@ -118,6 +191,9 @@ pub struct CompilePricingParams {
     pub memory_fill_ink: u64,
     /// Per-byte `MemoryCopy` cost
     pub memory_copy_ink: u64,
+    /// Per-byte `MemoryInit` cost, or `None` if programs may not read data segments
+    #[serde(default)]
+    pub memory_init_ink: Option<u64>,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -128,15 +204,106 @@ pub struct CompileDebugParams {
     pub count_ops: bool,
     /// Whether to use the Cranelift compiler
     pub cranelift: bool,
+    /// Add instrumentation to attribute ink consumption to each function
+    pub profile_ink: bool,
+    /// The number of basic blocks per function to profile individually when profiling ink
+    pub profile_blocks: u32,
 }
 
 impl Default for CompilePricingParams {
     fn default() -> Self {
         Self {
-            costs: |_, _| 0,
+            costs: OpCosts::uniform(0),
             ink_header_cost: 0,
             memory_fill_ink: 0,
             memory_copy_ink: 0,
+            memory_init_ink: None,
+        }
+    }
+}
+
+lazy_static! {
+    /// The pricing of Stylus version 1
+    static ref PRICING_V1: CompilePricingParams = {
+        let table = include_str!("pricing_v1.toml");
+        CompilePricingParams::from_toml(table).expect("invalid v1 pricing")
+    };
+
+    /// The pricing of Stylus version 2
+    static ref PRICING_V2: CompilePricingParams = {
+        let table = include_str!("pricing_v2.toml");
+        CompilePricingParams::from_toml(table).expect("invalid v2 pricing")
+    };
+}
+
+impl CompilePricingParams {
+    /// The pricing of Stylus version 1.
+    pub fn v1() -> Self {
+        PRICING_V1.clone()
+    }
+
+    /// The pricing of Stylus version 2.
+    pub fn v2() -> Self {
+        PRICING_V2.clone()
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self> {
+        toml::from_str(text).wrap_err("failed to parse pricing")
+    }
+
+    pub fn from_json(text: &str) -> Result<Self> {
+        serde_json::from_str(text).wrap_err("failed to parse pricing")
+    }
+
+    /// Reads a pricing table, as json if the file's extension is `.json` and toml otherwise.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).wrap_err("failed to read pricing")?;
+        match path.extension().map_or(false, |x| x == "json") {
+            true => Self::from_json(&text),
+            false => Self::from_toml(&text),
+        }
+    }
+
+    /// Identifies a pricing table by its contents.
+    pub fn digest(&self) -> Bytes32 {
+        let json = serde_json::to_vec(self).expect("failed to serialize pricing");
+        crypto::keccak(json).into()
+    }
+}
+
+impl NativeCompiler {
+    /// A stable name for the compiler, as used in cache keys and on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Singlepass => "singlepass",
+            Self::Cranelift(OptLevel::None) => "cranelift-none",
+            Self::Cranelift(OptLevel::Speed) => "cranelift-speed",
+            Self::Cranelift(OptLevel::SpeedAndSize) => "cranelift-speed-and-size",
+        }
+    }
+}
+
+impl FromStr for NativeCompiler {
+    type Err = eyre::ErrReport;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "singlepass" => Self::Singlepass,
+            "cranelift" | "cranelift-speed" => Self::Cranelift(OptLevel::Speed),
+            "cranelift-none" => Self::Cranelift(OptLevel::None),
+            "cranelift-speed-and-size" => Self::Cranelift(OptLevel::SpeedAndSize),
+            x => bail!("unknown native compiler {x}"),
+        })
+    }
+}
+
+#[cfg(feature = "native")]
+impl From<OptLevel> for CraneliftOptLevel {
+    fn from(level: OptLevel) -> Self {
+        match level {
+            OptLevel::None => Self::None,
+            OptLevel::Speed => Self::Speed,
+            OptLevel::SpeedAndSize => Self::SpeedAndSize,
         }
     }
 }
@@ -164,12 +331,13 @@ impl CompileConfig {
                 config.bounds.heap_bound = Pages(128); // 8 mb
                 config.bounds.max_frame_size = 10 * 1024;
                 config.bounds.max_frame_contention = 4096;
-                config.pricing = CompilePricingParams {
-                    costs: meter::pricing_v1,
-                    ink_header_cost: 2450,
-                    memory_fill_ink: 800 / 8,
-                    memory_copy_ink: 800 / 8,
-                };
+                config.pricing = CompilePricingParams::v1();
+            }
+            2 => {
+                config.bounds.heap_bound = Pages(128); // 8 mb
+                config.bounds.max_frame_size = 10 * 1024;
+                config.bounds.max_frame_contention = 4096;
+                config.pricing = CompilePricingParams::v2();
             }
             _ => panic!("no config exists for Stylus version {version}"),
         }
@@ -177,15 +345,23 @@ impl CompileConfig {
         config
     }
 
+    /// The compiler to use, which debug chains may override.
+    pub fn native_compiler(&self) -> NativeCompiler {
+        match self.debug.cranelift {
+            true => NativeCompiler::Cranelift(OptLevel::Speed),
+            false => self.compiler,
+        }
+    }
+
     #[cfg(feature = "native")]
     pub fn store(&self) -> Store {
-        let mut compiler: Box<dyn wasmer::CompilerConfig> = match self.debug.cranelift {
-            true => {
+        let mut compiler: Box<dyn wasmer::CompilerConfig> = match self.native_compiler() {
+            NativeCompiler::Cranelift(level) => {
                 let mut compiler = Cranelift::new();
-                compiler.opt_level(CraneliftOptLevel::Speed);
+                compiler.opt_level(level.into());
                 Box::new(compiler)
             }
-            false => Box::new(Singlepass::new()),
+            NativeCompiler::Singlepass => Box::new(Singlepass::new()),
         };
         compiler.canonicalize_nans(true);
         compiler.enable_verifier();
@@ -209,6 +385,12 @@ impl CompileConfig {
             compiler.push_middleware(Arc::new(MiddlewareWrapper::new(counter)));
         }
 
+        // the profiler observes the ink charged above, so it must come last
+        if self.debug.profile_ink {
+            let profiler = Profiler::new(self.debug.profile_blocks);
+            compiler.push_middleware(Arc::new(MiddlewareWrapper::new(profiler)));
+        }
+
         Store::new(compiler)
     }
 }
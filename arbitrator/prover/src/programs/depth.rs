@@ -335,6 +335,11 @@ impl<'a> FuncDepthChecker<'a> {
 
                 MemoryFill { .. } => ins_and_outs!(InternalFunc::MemoryFill.ty()),
                 MemoryCopy { .. } => ins_and_outs!(InternalFunc::MemoryCopy.ty()),
+                dot!(MemoryInit) => pop!(3),
+                dot!(DataDrop) => {
+                    push!(); // dropping zeroes the segment's length
+                    pop!()
+                }
 
                 op!(
                     Nop, Unreachable,
@@ -401,10 +406,7 @@ impl<'a> FuncDepthChecker<'a> {
                 },
 
                 unsupported @ (
-                    dot!(
-                        MemoryInit, DataDrop, TableInit, ElemDrop,
-                        TableCopy, TableFill, TableGet, TableSet, TableGrow, TableSize
-                    )
+                    dot!(TableInit, ElemDrop, TableCopy, TableFill, TableGet, TableSet, TableGrow, TableSize)
                 ) => bail!("bulk-memory-operations extension not fully supported {:?}", unsupported),
 
                 unsupported @ (
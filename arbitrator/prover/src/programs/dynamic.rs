@@ -17,6 +17,7 @@ pub const SCRATCH_GLOBAL: &str = "stylus_scratch_global";
 pub struct DynamicMeter {
     memory_fill: u64,
     memory_copy: u64,
+    memory_init: Option<u64>,
     globals: RwLock<Option<[GlobalIndex; 3]>>,
 }
 
@@ -25,6 +26,7 @@ impl DynamicMeter {
         Self {
             memory_fill: pricing.memory_fill_ink,
             memory_copy: pricing.memory_copy_ink,
+            memory_init: pricing.memory_init_ink,
             globals: RwLock::default(),
         }
     }
@@ -46,6 +48,7 @@ impl<M: ModuleMod> Middleware<M> for DynamicMeter {
         Ok(FuncDynamicMeter::new(
             self.memory_fill,
             self.memory_copy,
+            self.memory_init,
             globals,
         ))
     }
@@ -59,14 +62,21 @@ impl<M: ModuleMod> Middleware<M> for DynamicMeter {
 pub struct FuncDynamicMeter {
     memory_fill: u64,
     memory_copy: u64,
+    memory_init: Option<u64>,
     globals: [GlobalIndex; 3],
 }
 
 impl FuncDynamicMeter {
-    fn new(memory_fill: u64, memory_copy: u64, globals: [GlobalIndex; 3]) -> Self {
+    fn new(
+        memory_fill: u64,
+        memory_copy: u64,
+        memory_init: Option<u64>,
+        globals: [GlobalIndex; 3],
+    ) -> Self {
         Self {
             memory_fill,
             memory_copy,
+            memory_init,
             globals,
         }
     }
@@ -136,9 +146,14 @@ impl<'a> FuncMiddleware<'a> for FuncDynamicMeter {
         match op {
             dot!(MemoryFill) => out.extend(linear(self.memory_fill as i64)),
             dot!(MemoryCopy) => out.extend(linear(self.memory_copy as i64)),
+            dot!(MemoryInit) => match self.memory_init {
+                Some(ink) => out.extend(linear(ink as i64)),
+                None => bail!("opcode not supported"),
+            },
+            dot!(DataDrop) if self.memory_init.is_some() => {}
             dot!(
-                MemoryInit, DataDrop, ElemDrop, TableInit, TableCopy, TableFill, TableGet,
-                TableSet, TableGrow, TableSize
+                DataDrop, ElemDrop, TableInit, TableCopy, TableFill, TableGet, TableSet, TableGrow,
+                TableSize
             ) => {
                 bail!("opcode not supported")
             }
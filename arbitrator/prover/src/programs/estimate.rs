@@ -0,0 +1,425 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+//! Static bounds on the ink a program's functions consume.
+//!
+//! The estimator splits each function into the same basic blocks the ink meter charges for,
+//! prices them with the same [`CompilePricingParams`], and walks the resulting control-flow graph.
+//! Dynamic costs, like those of hostios and bulk memory operations, aren't included.
+
+use super::{
+    config::{CompilePricingParams, SigMap},
+    ModuleMod,
+};
+use crate::binary::{ExportKind, WasmBinary};
+use arbutil::operator::OperatorInfo;
+use eyre::{bail, Result};
+use fnv::FnvHashMap as HashMap;
+use std::collections::BTreeMap;
+use wasmparser::Operator;
+
+/// Static ink bounds for a single function, including the functions it calls.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FuncEstimate {
+    /// The least ink any path through the function consumes, including paths that trap.
+    pub min_ink: u64,
+    /// The most ink any path consumes, or `None` when loops, recursion, or indirect calls
+    /// make the function unbounded.
+    pub max_ink: Option<u64>,
+    /// The most ink a single iteration of each loop consumes, keyed by the loop's opcode offset.
+    /// Iterations that contain inner loops or indirect calls are unbounded.
+    pub loops: Vec<(usize, Option<u64>)>,
+}
+
+/// Static ink bounds for every function in a program.
+#[derive(Clone, Debug, Default)]
+pub struct InkEstimate {
+    /// Estimates for each function defined in the program, keyed by function index.
+    pub funcs: BTreeMap<u32, FuncEstimate>,
+    /// The function index of each exported function.
+    pub exports: BTreeMap<String, u32>,
+}
+
+impl InkEstimate {
+    /// Estimates the ink consumed by each of the program's functions.
+    /// The binary should not yet be instrumented.
+    pub fn new(bin: &WasmBinary, pricing: &CompilePricingParams) -> Result<Self> {
+        let sigs = bin.all_signatures()?;
+        let imports = bin.imports.len() as u32;
+
+        let mut cfgs = vec![];
+        for code in &bin.codes {
+            cfgs.push(Cfg::new(&code.expr, pricing, &sigs)?);
+        }
+
+        let mut estimator = Estimator {
+            imports,
+            cfgs: &cfgs,
+            states: vec![State::Unvisited; cfgs.len()],
+        };
+        let mut funcs = BTreeMap::new();
+        for (index, _) in cfgs.iter().enumerate() {
+            let func = imports + index as u32;
+            funcs.insert(func, estimator.estimate(func));
+        }
+
+        let exports = bin.exports.iter();
+        let exports = exports
+            .filter(|(_, (_, kind))| *kind == ExportKind::Func)
+            .map(|(name, (func, _))| (name.clone(), *func))
+            .collect();
+        Ok(Self { funcs, exports })
+    }
+
+    /// The estimate for an exported function.
+    pub fn export(&self, name: &str) -> Option<&FuncEstimate> {
+        self.funcs.get(self.exports.get(name)?)
+    }
+}
+
+/// Where control goes when a basic block ends.
+#[derive(Clone, Copy, Debug)]
+enum Target {
+    /// The start of the block at the given opcode offset.
+    Op(usize),
+    /// The function returns.
+    Exit,
+}
+
+/// A function called at the end of a basic block.
+#[derive(Clone, Copy, Debug)]
+enum Callee {
+    Func(u32),
+    Indirect,
+}
+
+/// A basic block, as charged by the ink meter.
+#[derive(Debug)]
+struct BasicBlock {
+    /// The ink charged upon entering the block, including the meter's header.
+    cost: u64,
+    /// The function called at the end of the block, if any.
+    callee: Option<Callee>,
+    /// The blocks control may continue to, which always follow this one.
+    forward: Vec<usize>,
+    /// The loop headers control may return to, which never follow this one.
+    back: Vec<usize>,
+}
+
+/// A loop whose header is the target of at least one back edge.
+#[derive(Debug)]
+struct LoopInfo {
+    /// The offset of the `loop` opcode.
+    op: usize,
+    /// The block beginning just after the `loop` opcode.
+    header: usize,
+    /// The last block inside the loop.
+    last: usize,
+}
+
+/// The control-flow graph of a function's basic blocks, in program order.
+#[derive(Debug)]
+struct Cfg {
+    blocks: Vec<BasicBlock>,
+    loops: Vec<LoopInfo>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ScopeKind {
+    Block,
+    Loop,
+    If,
+}
+
+#[derive(Debug)]
+struct Scope {
+    kind: ScopeKind,
+    op: usize,
+    else_op: Option<usize>,
+    end_op: usize,
+}
+
+impl Cfg {
+    fn new(code: &[Operator], pricing: &CompilePricingParams, sigs: &SigMap) -> Result<Self> {
+        use Operator::*;
+
+        // match each structured opcode to its scope, and each branch to the scopes it may target
+        let mut scopes: Vec<Scope> = vec![];
+        let mut open: Vec<usize> = vec![];
+        let mut scope_of = HashMap::default();
+        let mut branch_scopes = HashMap::default();
+
+        for (i, op) in code.iter().enumerate() {
+            let kind = match op {
+                Block { .. } => ScopeKind::Block,
+                Loop { .. } => ScopeKind::Loop,
+                If { .. } => ScopeKind::If,
+                Else => {
+                    let Some(&scope) = open.last() else {
+                        bail!("malformed if-else scope")
+                    };
+                    scopes[scope].else_op = Some(i);
+                    scope_of.insert(i, scope);
+                    continue;
+                }
+                End => {
+                    // the function's final end has no scope
+                    if let Some(scope) = open.pop() {
+                        scopes[scope].end_op = i;
+                        scope_of.insert(i, scope);
+                    }
+                    continue;
+                }
+                Br { .. } | BrIf { .. } | BrTable { .. } => {
+                    branch_scopes.insert(i, open.clone());
+                    continue;
+                }
+                _ => continue,
+            };
+            scope_of.insert(i, scopes.len());
+            open.push(scopes.len());
+            scopes.push(Scope {
+                kind,
+                op: i,
+                else_op: None,
+                end_op: code.len(),
+            });
+        }
+        if !open.is_empty() {
+            bail!("malformed scoping detected at end of function")
+        }
+
+        let target = |op: usize, depth: u32| -> Target {
+            let open: &Vec<usize> = &branch_scopes[&op];
+            let Some(index) = open.len().checked_sub(1 + depth as usize) else {
+                return Target::Exit; // branching out of the function returns
+            };
+            let scope = &scopes[open[index]];
+            match scope.kind {
+                ScopeKind::Loop => Target::Op(scope.op + 1),
+                _ => Target::Op(scope.end_op + 1),
+            }
+        };
+
+        // split the code into the meter's basic blocks
+        let mut starts = vec![];
+        let mut costs = vec![];
+        let mut traps = vec![];
+        let mut cost = 0_u64;
+        let mut trap = false;
+        let mut start = 0;
+        for (i, op) in code.iter().enumerate() {
+            cost = cost.saturating_add(pricing.costs.price(op, sigs));
+            trap |= matches!(op, Unreachable);
+            if op.ends_basic_block() || i + 1 == code.len() {
+                starts.push(start);
+                costs.push(cost.saturating_add(pricing.ink_header_cost));
+                traps.push(trap);
+                (cost, trap, start) = (0, false, i + 1);
+            }
+        }
+        let block_at: HashMap<_, _> = starts.iter().enumerate().map(|(b, s)| (*s, b)).collect();
+
+        let mut blocks = vec![];
+        for (index, &start) in starts.iter().enumerate() {
+            let end = starts.get(index + 1).copied().unwrap_or(code.len());
+            let last = end - 1;
+            let next = Target::Op(last + 1);
+
+            let mut targets = vec![];
+            let mut callee = None;
+            match &code[last] {
+                _ if traps[index] => {}
+                End => match scope_of.get(&last) {
+                    Some(_) => targets.push(next),
+                    None => targets.push(Target::Exit),
+                },
+                Else => {
+                    let scope = &scopes[scope_of[&last]];
+                    targets.push(Target::Op(scope.end_op + 1));
+                }
+                If { .. } => {
+                    let scope = &scopes[scope_of[&last]];
+                    let skip = scope.else_op.unwrap_or(scope.end_op);
+                    targets.extend([next, Target::Op(skip + 1)]);
+                }
+                Br { relative_depth } => targets.push(target(last, *relative_depth)),
+                BrIf { relative_depth } => targets.extend([target(last, *relative_depth), next]),
+                BrTable { targets: table } => {
+                    for depth in table.targets() {
+                        targets.push(target(last, depth?));
+                    }
+                    targets.push(target(last, table.default()));
+                }
+                Return => targets.push(Target::Exit),
+                Call { function_index } => {
+                    callee = Some(Callee::Func(*function_index));
+                    targets.push(next);
+                }
+                CallIndirect { .. } => {
+                    callee = Some(Callee::Indirect);
+                    targets.push(next);
+                }
+                _ => targets.push(next),
+            }
+
+            let mut forward = vec![];
+            let mut back = vec![];
+            for target in targets {
+                let Target::Op(op) = target else {
+                    continue;
+                };
+                let Some(&block) = block_at.get(&op) else {
+                    bail!("branch to {op} doesn't start a basic block in block {start}")
+                };
+                match block > index {
+                    true => forward.push(block),
+                    false => back.push(block),
+                }
+            }
+            blocks.push(BasicBlock {
+                cost: costs[index],
+                callee,
+                forward,
+                back,
+            });
+        }
+
+        let mut loops = vec![];
+        for scope in scopes.iter().filter(|x| x.kind == ScopeKind::Loop) {
+            let Some(&header) = block_at.get(&(scope.op + 1)) else {
+                continue;
+            };
+            let last = block_at.get(&(scope.end_op + 1)).map(|x| x - 1);
+            let last = last.unwrap_or(blocks.len() - 1);
+            let body = &blocks[header..=last];
+            if body.iter().any(|x| x.back.contains(&header)) {
+                loops.push(LoopInfo {
+                    op: scope.op,
+                    header,
+                    last,
+                });
+            }
+        }
+        Ok(Self { blocks, loops })
+    }
+}
+
+#[derive(Clone, Debug)]
+enum State {
+    Unvisited,
+    Visiting,
+    Done(FuncEstimate),
+}
+
+struct Estimator<'a> {
+    imports: u32,
+    cfgs: &'a [Cfg],
+    states: Vec<State>,
+}
+
+/// Adds two bounds, either of which may be unbounded.
+fn add(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    Some(a?.saturating_add(b?))
+}
+
+/// Takes the larger of two bounds, either of which may be unbounded.
+fn max(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    Some(a?.max(b?))
+}
+
+impl Estimator<'_> {
+    /// Estimates a function, memoizing the result.
+    fn estimate(&mut self, func: u32) -> FuncEstimate {
+        let Some(local) = func.checked_sub(self.imports) else {
+            return FuncEstimate::default(); // hostios are priced dynamically
+        };
+        let local = local as usize;
+        match &self.states[local] {
+            State::Done(estimate) => return estimate.clone(),
+            State::Visiting => {
+                // recursion may repeat any number of times
+                return FuncEstimate {
+                    max_ink: None,
+                    ..FuncEstimate::default()
+                };
+            }
+            State::Unvisited => self.states[local] = State::Visiting,
+        }
+
+        let cfgs = self.cfgs;
+        let cfg = &cfgs[local];
+        let count = cfg.blocks.len();
+
+        // the ink charged by each block, including its callee
+        let mut mins = Vec::with_capacity(count);
+        let mut maxs = Vec::with_capacity(count);
+        for block in &cfg.blocks {
+            let (min, max) = match block.callee {
+                Some(Callee::Func(func)) => {
+                    let callee = self.estimate(func);
+                    (callee.min_ink, callee.max_ink)
+                }
+                Some(Callee::Indirect) => (0, None),
+                None => (0, Some(0)),
+            };
+            mins.push(block.cost.saturating_add(min));
+            maxs.push(add(Some(block.cost), max));
+        }
+
+        // forward edges form a DAG in program order, so walk it backwards
+        let mut min_ink = vec![0; count];
+        let mut max_ink = vec![Some(0); count];
+        for index in (0..count).rev() {
+            let block = &cfg.blocks[index];
+            let rest_min = block.forward.iter().map(|x| min_ink[*x]).min();
+            let rest_max = block.forward.iter().map(|x| max_ink[*x]);
+            let rest_max = rest_max.fold(Some(0), max);
+            min_ink[index] = mins[index].saturating_add(rest_min.unwrap_or_default());
+            max_ink[index] = add(maxs[index], rest_max);
+        }
+
+        let loops = cfg.loops.iter().map(|x| (x.op, iteration(cfg, x, &maxs)));
+        let loops: Vec<_> = loops.collect();
+        let bounded = loops.is_empty();
+
+        let estimate = FuncEstimate {
+            min_ink: min_ink.first().copied().unwrap_or_default(),
+            max_ink: max_ink.first().copied().flatten().filter(|_| bounded),
+            loops,
+        };
+        self.states[local] = State::Done(estimate.clone());
+        estimate
+    }
+}
+
+/// The most ink a path from a loop's header back to itself consumes.
+fn iteration(cfg: &Cfg, lp: &LoopInfo, maxs: &[Option<u64>]) -> Option<u64> {
+    // None means no path repeats the loop, Some(None) means some path is unbounded
+    let mut paths: Vec<Option<Option<u64>>> = vec![None; lp.last + 1];
+
+    for index in (lp.header..=lp.last).rev() {
+        let block = &cfg.blocks[index];
+        let mut best: Option<Option<u64>> = None;
+        let mut take = |path: Option<u64>| {
+            best = Some(match best {
+                Some(prior) => max(prior, path),
+                None => path,
+            });
+        };
+        for &back in &block.back {
+            if back == lp.header {
+                take(Some(0));
+            } else if back > lp.header {
+                take(None); // an inner loop may repeat any number of times
+            }
+        }
+        for &next in block.forward.iter().filter(|x| **x <= lp.last) {
+            if let Some(path) = paths[next] {
+                take(path);
+            }
+        }
+        paths[index] = best.map(|rest| add(maxs[index], rest));
+    }
+    paths[lp.header].flatten()
+}
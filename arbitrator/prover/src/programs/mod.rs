@@ -6,6 +6,7 @@ use crate::{
     machine::Module,
     memory::MemoryType,
     programs::config::CompileConfig,
+    simd,
     value::{FunctionType as ArbFunctionType, Value},
 };
 use arbutil::{math::SaturatingSum, Color};
@@ -32,10 +33,12 @@ pub mod config;
 pub mod counter;
 pub mod depth;
 pub mod dynamic;
+pub mod estimate;
 pub mod heap;
 pub mod memory;
 pub mod meter;
 pub mod prelude;
+pub mod profiler;
 pub mod start;
 
 pub const STYLUS_ENTRY_POINT: &str = "user_entrypoint";
@@ -46,6 +49,7 @@ pub trait ModuleMod {
     fn get_signature(&self, sig: SignatureIndex) -> Result<ArbFunctionType>;
     fn get_function(&self, func: FunctionIndex) -> Result<ArbFunctionType>;
     fn all_functions(&self) -> Result<HashMap<FunctionIndex, ArbFunctionType>>;
+    fn num_imported_funcs(&self) -> u32;
     fn all_signatures(&self) -> Result<HashMap<SignatureIndex, ArbFunctionType>>;
     fn get_import(&self, module: &str, name: &str) -> Result<ImportIndex>;
     fn move_start_function(&mut self, name: &str) -> Result<()>;
@@ -207,6 +211,10 @@ impl ModuleMod for ModuleInfo {
         Ok(funcs)
     }
 
+    fn num_imported_funcs(&self) -> u32 {
+        self.num_imported_functions as u32
+    }
+
     fn all_signatures(&self) -> Result<HashMap<SignatureIndex, ArbFunctionType>> {
         let mut signatures = HashMap::default();
         for (index, _) in &self.signatures {
@@ -319,6 +327,10 @@ impl<'a> ModuleMod for WasmBinary<'a> {
         Ok(funcs)
     }
 
+    fn num_imported_funcs(&self) -> u32 {
+        self.imports.len() as u32
+    }
+
     fn all_signatures(&self) -> Result<HashMap<SignatureIndex, ArbFunctionType>> {
         let mut signatures = HashMap::default();
         for (index, ty) in self.types.iter().enumerate() {
@@ -401,6 +413,21 @@ impl Module {
         debug: bool,
         gas: &mut u64,
     ) -> Result<(Self, StylusData)> {
+        let wasm = simd::lower_user(wasm, version)?;
+        let (_, module, stylus_data) =
+            Self::activate_binary(&wasm, version, page_limit, debug, gas)?;
+        Ok((module, stylus_data))
+    }
+
+    /// Activates a program, also returning the instrumented binary so it needn't be parsed again.
+    /// Any SIMD must already have been lowered via [`simd::lower_user`].
+    pub fn activate_binary<'a>(
+        wasm: &'a [u8],
+        version: u16,
+        page_limit: u16,
+        debug: bool,
+        gas: &mut u64,
+    ) -> Result<(WasmBinary<'a>, Self, StylusData)> {
         // converts a number of microseconds to gas
         // TODO: collapse to a single value after finalizing factors
         let us_to_gas = |us: u64| {
@@ -452,6 +479,6 @@ impl Module {
         let module = Self::from_user_binary(&bin, compile.debug.debug_funcs, Some(stylus_data))
             .wrap_err("failed to build user module")?;
 
-        Ok((module, stylus_data))
+        Ok((bin, module, stylus_data))
     }
 }
@@ -0,0 +1,320 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+use super::{
+    dynamic::SCRATCH_GLOBAL, meter::STYLUS_INK_LEFT, FuncMiddleware, Middleware, ModuleMod,
+};
+use arbutil::operator::OperatorInfo;
+use eyre::{bail, Result};
+use parking_lot::RwLock;
+use std::{collections::BTreeMap, marker::PhantomData, sync::Arc};
+use wasmer_types::{GlobalIndex, GlobalInit, LocalFunctionIndex, Type};
+use wasmparser::Operator;
+
+pub const STYLUS_PROFILE_SCRATCH: &str = "stylus_profile_scratch";
+
+/// Attributes the ink a program consumes to each of its functions, and optionally to the first
+/// few basic blocks of each. Since this observes each write to the ink global, it must be applied
+/// after both the ink meter and dynamic meter.
+#[derive(Debug)]
+pub struct Profiler {
+    /// The number of basic blocks to profile individually in each function
+    block_slots: u32,
+    /// The globals of the module being instrumented
+    globals: RwLock<Option<Arc<ProfilerGlobals>>>,
+}
+
+#[derive(Debug)]
+struct ProfilerGlobals {
+    /// The amount of ink left
+    ink: GlobalIndex,
+    /// Holds an amount of ink while its difference is attributed
+    scratch: GlobalIndex,
+    /// Where the dynamic meter stages the sizes it charges for
+    staging: GlobalIndex,
+    /// The number of imported functions
+    imports: u32,
+    /// The ink and call counts of every function
+    funcs: Vec<[GlobalIndex; 2]>,
+    /// The block slots of each local function
+    blocks: Vec<Vec<GlobalIndex>>,
+}
+
+impl Profiler {
+    pub fn new(block_slots: u32) -> Self {
+        let globals = RwLock::new(None);
+        Self {
+            block_slots,
+            globals,
+        }
+    }
+
+    pub fn ink_name(func: u32) -> String {
+        format!("stylus_func{func}_ink")
+    }
+
+    pub fn calls_name(func: u32) -> String {
+        format!("stylus_func{func}_calls")
+    }
+
+    pub fn block_name(func: u32, block: u32) -> String {
+        format!("stylus_func{func}_block{block}_ink")
+    }
+}
+
+impl<M: ModuleMod> Middleware<M> for Profiler {
+    type FM<'a> = FuncProfiler<'a>;
+
+    fn update_module(&self, module: &mut M) -> Result<()> {
+        let zero = GlobalInit::I64Const(0);
+        let ink = module.get_global(STYLUS_INK_LEFT)?;
+        let scratch = module.add_global(STYLUS_PROFILE_SCRATCH, Type::I64, zero)?;
+        let staging = module.get_global(SCRATCH_GLOBAL)?;
+        let imports = module.num_imported_funcs();
+
+        let mut funcs = vec![];
+        let mut blocks = vec![];
+        for func in 0..module.all_functions()?.len() as u32 {
+            let ink = module.add_global(&Self::ink_name(func), Type::I64, zero)?;
+            let calls = module.add_global(&Self::calls_name(func), Type::I64, zero)?;
+            funcs.push([ink, calls]);
+
+            if func >= imports {
+                let mut slots = vec![];
+                for block in 0..self.block_slots {
+                    let name = Self::block_name(func, block);
+                    slots.push(module.add_global(&name, Type::I64, zero)?);
+                }
+                blocks.push(slots);
+            }
+        }
+
+        *self.globals.write() = Some(Arc::new(ProfilerGlobals {
+            ink,
+            scratch,
+            staging,
+            imports,
+            funcs,
+            blocks,
+        }));
+        Ok(())
+    }
+
+    fn instrument<'a>(&self, func: LocalFunctionIndex) -> Result<Self::FM<'a>> {
+        let Some(globals) = self.globals.read().clone() else {
+            bail!("missing globals");
+        };
+        Ok(FuncProfiler::new(globals, func.as_u32()))
+    }
+
+    fn name(&self) -> &'static str {
+        "ink profiler"
+    }
+}
+
+#[derive(Debug)]
+pub struct FuncProfiler<'a> {
+    /// The globals of the module being instrumented
+    globals: Arc<ProfilerGlobals>,
+    /// The local index of the function being instrumented
+    local: u32,
+    /// Whether the function's call count has been instrumented
+    entered: bool,
+    /// Whether the dynamic meter has staged a size in the current basic block
+    staged: bool,
+    /// The index of the next basic block the ink meter charges
+    block: u32,
+    /// Associates this middleware with the operators it processes
+    phantom: PhantomData<Operator<'a>>,
+}
+
+impl<'a> FuncProfiler<'a> {
+    fn new(globals: Arc<ProfilerGlobals>, local: u32) -> Self {
+        Self {
+            globals,
+            local,
+            entered: false,
+            staged: false,
+            block: 0,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a> FuncMiddleware<'a> for FuncProfiler<'a> {
+    fn feed<O>(&mut self, op: Operator<'a>, out: &mut O) -> Result<()>
+    where
+        O: Extend<Operator<'a>>,
+    {
+        use Operator::*;
+
+        let globals = self.globals.clone();
+        let func = globals.imports + self.local;
+        let [func_ink, func_calls] = globals.funcs[func as usize];
+        let ink = globals.ink.as_u32();
+        let scratch = globals.scratch.as_u32();
+
+        // pushes the difference of two globals
+        let diff = |lhs: u32, rhs: u32| {
+            [
+                GlobalGet { global_index: lhs },
+                GlobalGet { global_index: rhs },
+                I64Sub,
+            ]
+        };
+
+        // adds the value atop the stack to a global
+        let add = |global: GlobalIndex| {
+            let global_index = global.as_u32();
+            [
+                GlobalGet { global_index },
+                I64Add,
+                GlobalSet { global_index },
+            ]
+        };
+
+        // increments a global
+        let count = |global: GlobalIndex| {
+            let global_index = global.as_u32();
+            [
+                GlobalGet { global_index },
+                I64Const { value: 1 },
+                I64Add,
+                GlobalSet { global_index },
+            ]
+        };
+
+        if !self.entered {
+            out.extend(count(func_calls));
+            self.entered = true;
+        }
+
+        // the dynamic meter stages each size in its scratch global before charging for it,
+        // so only the ink meter's charges come before any staging within a basic block
+        let static_charge = !self.staged;
+        if let GlobalSet { global_index } = op {
+            self.staged |= global_index == globals.staging.as_u32();
+        }
+        if op.ends_basic_block() {
+            self.staged = false;
+        }
+
+        match op {
+            GlobalSet { global_index } if global_index == ink => {
+                // [new] → [] with ink - new attributed to the function
+                out.extend([GlobalSet {
+                    global_index: scratch,
+                }]);
+                out.extend(diff(ink, scratch));
+                out.extend(add(func_ink));
+
+                let slots = &globals.blocks[self.local as usize];
+                if static_charge && !slots.is_empty() {
+                    let slot = self.block.min(slots.len() as u32 - 1);
+                    out.extend(diff(ink, scratch));
+                    out.extend(add(slots[slot as usize]));
+                    self.block += 1;
+                }
+                out.extend([
+                    GlobalGet {
+                        global_index: scratch,
+                    },
+                    op,
+                ]);
+            }
+            Call { function_index } if function_index < globals.imports => {
+                // hostios charge ink themselves, which is attributed to the hostio
+                let [hostio_ink, hostio_calls] = globals.funcs[function_index as usize];
+                out.extend([
+                    GlobalGet { global_index: ink },
+                    GlobalSet {
+                        global_index: scratch,
+                    },
+                    op,
+                ]);
+                out.extend(diff(scratch, ink));
+                out.extend(add(hostio_ink));
+                out.extend(count(hostio_calls));
+            }
+            _ => out.extend([op]),
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "ink profiler"
+    }
+}
+
+/// The ink consumed by a function during execution.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FuncProfile {
+    /// The function's name, if known
+    pub name: String,
+    /// The module a hostio is imported from, or `None` for local functions
+    pub import: Option<String>,
+    /// The number of times the function was called
+    pub calls: u64,
+    /// The ink the function consumed, excluding that of its callees
+    pub ink: u64,
+    /// The ink charged for each of the function's first few basic blocks.
+    /// The last slot includes that of any blocks beyond.
+    pub blocks: Vec<u64>,
+}
+
+impl FuncProfile {
+    /// The function's demangled name, falling back to its index.
+    pub fn display_name(&self, index: u32) -> String {
+        match self.name.is_empty() {
+            true => format!("func {index}"),
+            false => rustc_demangle::demangle(&self.name).to_string(),
+        }
+    }
+}
+
+/// The ink a program consumed, by function.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InkProfile {
+    pub funcs: BTreeMap<u32, FuncProfile>,
+}
+
+impl InkProfile {
+    /// The total ink consumed across all functions.
+    pub fn total(&self) -> u64 {
+        self.funcs.values().map(|x| x.ink).sum()
+    }
+
+    /// Renders the profile in "folded" format, as used by tools like inferno-flamegraph.
+    /// Since call stacks aren't tracked, each function appears directly beneath its module.
+    pub fn folded(&self, program: &str) -> String {
+        let mut out = String::new();
+        for (index, func) in &self.funcs {
+            if func.ink == 0 {
+                continue;
+            }
+            let name = func.display_name(*index);
+            let module = func.import.as_deref().unwrap_or(program);
+            let path = format!("[module] {module};{name}");
+
+            if func.blocks.is_empty() {
+                out += &format!("{path} {}\n", func.ink);
+                continue;
+            }
+            for (block, ink) in func.blocks.iter().enumerate() {
+                if *ink > 0 {
+                    out += &format!("{path};block {block} {ink}\n");
+                }
+            }
+            let dynamic = func.ink.saturating_sub(func.blocks.iter().sum());
+            if dynamic > 0 {
+                out += &format!("{path};dynamic {dynamic}\n");
+            }
+        }
+        out
+    }
+}
+
+/// Machines whose programs were instrumented by the [`Profiler`].
+pub trait ProfilingMachine {
+    fn ink_profile(&mut self) -> Result<InkProfile>;
+}
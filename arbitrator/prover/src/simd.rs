@@ -0,0 +1,1195 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+//! A software implementation of the SIMD proposal.
+//!
+//! Neither WAVM nor singlepass have a 128-bit type, so before a program is parsed each `v128` is
+//! lowered to a pair of `i64`s, low half first. Loads, stores, and constants are lowered inline,
+//! while the remaining operators call implementations generated here and appended to the module.
+//! Both the prover and native compilation instrument the lowered binary, so their metering agrees.
+
+use crate::{
+    binary::{write_sleb, write_uleb, FEATURES},
+    value::{ArbValueType, IntegerValType},
+    wavm::{IBinOpType, IRelOpType, IUnOpType, Opcode},
+};
+use arbutil::operator::{OperatorCode, OperatorInfo};
+use eyre::{bail, Result};
+use std::borrow::Cow;
+use wasmparser::{
+    BinaryReader, BlockType, FuncValidator, FunctionBody, MemArg, Operator, Parser, Payload, Type,
+    TypeRef, ValType, ValidPayload, Validator, ValidatorResources, WasmFeatures,
+};
+
+/// Lowers a user program's SIMD if its Stylus version supports the proposal.
+pub fn lower_user(wasm: &[u8], version: u16) -> Result<Cow<'_, [u8]>> {
+    match version {
+        0 | 1 => Ok(Cow::Borrowed(wasm)),
+        _ => lower(wasm),
+    }
+}
+
+/// Lowers each `v128` of a binary to a pair of `i64`s, borrowing the binary if it doesn't use SIMD.
+pub fn lower(wasm: &[u8]) -> Result<Cow<'_, [u8]>> {
+    let features = WasmFeatures {
+        simd: true,
+        ..FEATURES
+    };
+    let mut validator = Validator::new_with_features(features);
+    let mut lowering = Lowering::default();
+
+    for payload in Parser::new(0).parse_all(wasm) {
+        let payload = payload?;
+        if let ValidPayload::Func(func, body) = validator.payload(&payload)? {
+            let func = func.into_validator(Default::default());
+            lowering.lower_func(wasm, func, body)?;
+        }
+
+        use Payload::*;
+        match payload {
+            TypeSection(mut reader) => {
+                for _ in 0..reader.get_count() {
+                    let Type::Func(ty) = reader.read()?;
+                    lowering.simd |= ty.params().iter().chain(ty.results()).any(is_v128);
+                    write_func_type(&mut lowering.types, ty.params(), ty.results());
+                    lowering.type_count += 1;
+                }
+            }
+            ImportSection(mut reader) => {
+                for _ in 0..reader.get_count() {
+                    match reader.read()?.ty {
+                        TypeRef::Func(_) => lowering.imports += 1,
+                        TypeRef::Global(ty) if ty.content_type == ValType::V128 => {
+                            bail!("v128 globals not supported")
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            FunctionSection(mut reader) => {
+                for _ in 0..reader.get_count() {
+                    lowering.funcs.push(reader.read()?);
+                }
+            }
+            GlobalSection(mut reader) => {
+                for _ in 0..reader.get_count() {
+                    if reader.read()?.ty.content_type == ValType::V128 {
+                        bail!("v128 globals not supported")
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if !lowering.simd {
+        return Ok(Cow::Borrowed(wasm));
+    }
+    lowering.splice(wasm).map(Cow::Owned)
+}
+
+/// The locals the lowering of a function uses as scratch space.
+const ADDR: u32 = 0;
+const LOW: u32 = 1;
+const HIGH: u32 = 2;
+
+/// The state of a binary's lowering, whose sections are replaced once each function is lowered.
+#[derive(Default)]
+struct Lowering {
+    /// Whether the binary uses SIMD, and so needs lowering.
+    simd: bool,
+    /// The lowered contents of the type section, less its count.
+    types: Vec<u8>,
+    /// The number of types in the binary.
+    type_count: u32,
+    /// Types appended after those of the binary, with the pair of `i64`s that blocks yield first.
+    extra_types: Vec<(Vec<ValType>, Vec<ValType>)>,
+    /// The number of functions the binary imports.
+    imports: u32,
+    /// The type of each local function.
+    funcs: Vec<u32>,
+    /// The lowered body of each local function.
+    bodies: Vec<Vec<u8>>,
+    /// The operators implemented by appended functions, in order, with each's type and body.
+    impls: Vec<(OperatorCode, u32, Vec<u8>)>,
+}
+
+impl Lowering {
+    /// Lowers a local function, recording its new body.
+    fn lower_func(
+        &mut self,
+        wasm: &[u8],
+        mut func: FuncValidator<ValidatorResources>,
+        body: FunctionBody,
+    ) -> Result<()> {
+        use Operator::*;
+
+        // the lowered index of each local, and whether it's a v128
+        let mut locals = vec![];
+        let mut next = 0;
+        let mut define = |locals: &mut Vec<_>, ty: ValType| {
+            locals.push((next, is_v128(&ty)));
+            next += 1 + is_v128(&ty) as u32;
+        };
+        for index in 0..func.len_locals() {
+            let ty = func.get_local_type(index).expect("missing param");
+            define(&mut locals, ty);
+        }
+
+        let mut decls = vec![];
+        let mut reader = body.get_locals_reader()?;
+        for _ in 0..reader.get_count() {
+            let offset = reader.original_position();
+            let (count, ty) = reader.read()?;
+            func.define_locals(offset, count, ty)?;
+            for _ in 0..count {
+                define(&mut locals, ty);
+            }
+            decls.push(match ty {
+                ValType::V128 => (2 * count, ValType::I64),
+                ty => (count, ty),
+            });
+        }
+        let scratch = next;
+        let mut scratch_uses = 0;
+        self.simd |= locals.iter().any(|x| x.1);
+
+        let mut g = Gen::new(scratch);
+        let mut reader = body.get_operators_reader()?;
+        while !reader.eof() {
+            let (op, offset) = reader.read_with_offset()?;
+            let raw = &wasm[offset..reader.original_position()];
+            let v128 = |depth| func.get_operand_type(depth) == Some(Some(ValType::V128));
+            self.simd |= op.code().is_simd();
+
+            macro_rules! call {
+                ($op:expr) => {{
+                    let func = self.implementation(OperatorCode::from($op))?;
+                    g.call(func);
+                }};
+            }
+            macro_rules! scratch {
+                ($local:expr) => {{
+                    scratch_uses += 1;
+                    scratch + $local
+                }};
+            }
+            macro_rules! load {
+                ($ty:ident, $bytes:expr, $memarg:expr) => {
+                    g.load(ArbValueType::$ty, $bytes, $memarg, 0)
+                };
+            }
+            macro_rules! store_vector {
+                () => {{
+                    g.set(scratch!(HIGH));
+                    g.set(scratch!(LOW));
+                }};
+            }
+            macro_rules! load_vector {
+                () => {{
+                    g.get(scratch!(LOW));
+                    g.get(scratch!(HIGH));
+                }};
+            }
+
+            match op {
+                LocalGet { local_index } => {
+                    let (index, wide) = locals[local_index as usize];
+                    g.get(index);
+                    if wide {
+                        g.get(index + 1);
+                    }
+                }
+                LocalSet { local_index } => {
+                    let (index, wide) = locals[local_index as usize];
+                    if wide {
+                        g.set(index + 1);
+                    }
+                    g.set(index);
+                }
+                LocalTee { local_index } => {
+                    let (index, wide) = locals[local_index as usize];
+                    match wide {
+                        true => {
+                            g.set(index + 1);
+                            g.tee(index);
+                            g.get(index + 1);
+                        }
+                        false => g.tee(index),
+                    }
+                }
+                Drop if v128(0) => {
+                    g.op(Opcode::Drop);
+                    g.op(Opcode::Drop);
+                }
+                Select if v128(1) => call!(&op),
+                Block {
+                    blockty: BlockType::Type(ValType::V128),
+                }
+                | Loop {
+                    blockty: BlockType::Type(ValType::V128),
+                }
+                | If {
+                    blockty: BlockType::Type(ValType::V128),
+                } => {
+                    self.simd = true;
+                    g.code.push(raw[0]);
+                    write_sleb(&mut g.code, self.pair_type().into());
+                }
+                V128Const { value } => {
+                    let (low, high) = value.bytes().split_at(8);
+                    g.i64(u64::from_le_bytes(low.try_into().unwrap()));
+                    g.i64(u64::from_le_bytes(high.try_into().unwrap()));
+                }
+                V128Load { memarg } if high_half_fits(&memarg) => {
+                    g.tee(scratch!(ADDR));
+                    load!(I64, 8, &memarg);
+                    g.get(scratch!(ADDR));
+                    g.load(ArbValueType::I64, 8, &memarg, 8);
+                }
+                V128Store { memarg } if high_half_fits(&memarg) => {
+                    // write the high half first, so that a failed store writes nothing
+                    store_vector!();
+                    g.set(scratch!(ADDR));
+                    g.get(scratch!(ADDR));
+                    g.get(scratch!(HIGH));
+                    g.store(ArbValueType::I64, 8, &memarg, 8);
+                    g.get(scratch!(ADDR));
+                    g.get(scratch!(LOW));
+                    g.store(ArbValueType::I64, 8, &memarg, 0);
+                }
+                V128Load { .. } | V128Store { .. } => {
+                    g.op(Opcode::Unreachable); // always out of bounds
+                }
+                V128Load8x8S { memarg }
+                | V128Load8x8U { memarg }
+                | V128Load16x4S { memarg }
+                | V128Load16x4U { memarg }
+                | V128Load32x2S { memarg }
+                | V128Load32x2U { memarg } => {
+                    load!(I64, 8, &memarg);
+                    g.i64(0);
+                    call!(match op {
+                        V128Load8x8S { .. } => I16x8ExtendLowI8x16S,
+                        V128Load8x8U { .. } => I16x8ExtendLowI8x16U,
+                        V128Load16x4S { .. } => I32x4ExtendLowI16x8S,
+                        V128Load16x4U { .. } => I32x4ExtendLowI16x8U,
+                        V128Load32x2S { .. } => I64x2ExtendLowI32x4S,
+                        _ => I64x2ExtendLowI32x4U,
+                    });
+                }
+                V128Load8Splat { memarg } => {
+                    load!(I32, 1, &memarg);
+                    call!(I8x16Splat);
+                }
+                V128Load16Splat { memarg } => {
+                    load!(I32, 2, &memarg);
+                    call!(I16x8Splat);
+                }
+                V128Load32Splat { memarg } => {
+                    load!(I32, 4, &memarg);
+                    call!(I32x4Splat);
+                }
+                V128Load64Splat { memarg } => {
+                    load!(I64, 8, &memarg);
+                    call!(I64x2Splat);
+                }
+                V128Load32Zero { memarg } => {
+                    load!(I64, 4, &memarg);
+                    g.i64(0);
+                }
+                V128Load64Zero { memarg } => {
+                    load!(I64, 8, &memarg);
+                    g.i64(0);
+                }
+                V128Load8Lane { memarg, lane }
+                | V128Load16Lane { memarg, lane }
+                | V128Load32Lane { memarg, lane }
+                | V128Load64Lane { memarg, lane } => {
+                    let bytes = 1 << memarg.max_align;
+                    store_vector!();
+                    load!(I64, bytes, &memarg);
+                    load_vector!();
+                    g.i32(lane.into());
+                    call!(&op);
+                }
+                V128Store8Lane { memarg, lane }
+                | V128Store16Lane { memarg, lane }
+                | V128Store32Lane { memarg, lane }
+                | V128Store64Lane { memarg, lane } => {
+                    let bytes = 1 << memarg.max_align;
+                    store_vector!();
+                    load_vector!();
+                    g.i32(lane.into());
+                    match bytes {
+                        1 => call!(&I8x16ExtractLaneU { lane }),
+                        2 => call!(&I16x8ExtractLaneU { lane }),
+                        4 => call!(&I32x4ExtractLane { lane }),
+                        _ => call!(&I64x2ExtractLane { lane }),
+                    }
+                    match bytes {
+                        8 => g.store(ArbValueType::I64, 8, &memarg, 0),
+                        _ => g.store(ArbValueType::I32, bytes, &memarg, 0),
+                    }
+                }
+                I8x16Shuffle { lanes } => {
+                    let (low, high) = lanes.split_at(8);
+                    g.i64(u64::from_le_bytes(low.try_into().unwrap()));
+                    g.i64(u64::from_le_bytes(high.try_into().unwrap()));
+                    call!(&op);
+                }
+                I8x16ExtractLaneS { lane }
+                | I8x16ExtractLaneU { lane }
+                | I16x8ExtractLaneS { lane }
+                | I16x8ExtractLaneU { lane }
+                | I32x4ExtractLane { lane }
+                | I64x2ExtractLane { lane }
+                | I8x16ReplaceLane { lane }
+                | I16x8ReplaceLane { lane }
+                | I32x4ReplaceLane { lane }
+                | I64x2ReplaceLane { lane } => {
+                    g.i32(lane.into());
+                    call!(&op);
+                }
+
+                // floats share the lanes of the integers of the same width
+                F32x4ExtractLane { lane } => {
+                    g.i32(lane.into());
+                    call!(&I32x4ExtractLane { lane });
+                    g.op(Opcode::Reinterpret(ArbValueType::F32, ArbValueType::I32));
+                }
+                F64x2ExtractLane { lane } => {
+                    g.i32(lane.into());
+                    call!(&I64x2ExtractLane { lane });
+                    g.op(Opcode::Reinterpret(ArbValueType::F64, ArbValueType::I64));
+                }
+                F32x4ReplaceLane { lane } => {
+                    g.op(Opcode::Reinterpret(ArbValueType::I32, ArbValueType::F32));
+                    g.i32(lane.into());
+                    call!(&I32x4ReplaceLane { lane });
+                }
+                F64x2ReplaceLane { lane } => {
+                    g.op(Opcode::Reinterpret(ArbValueType::I64, ArbValueType::F64));
+                    g.i32(lane.into());
+                    call!(&I64x2ReplaceLane { lane });
+                }
+                F32x4Splat => {
+                    g.op(Opcode::Reinterpret(ArbValueType::I32, ArbValueType::F32));
+                    call!(I32x4Splat);
+                }
+                F64x2Splat => {
+                    g.op(Opcode::Reinterpret(ArbValueType::I64, ArbValueType::F64));
+                    call!(I64x2Splat);
+                }
+                ref op if op.code().is_simd() => call!(op),
+                _ => g.code.extend(raw),
+            }
+            func.op(offset, &op)?;
+        }
+        func.finish(reader.original_position())?;
+
+        if scratch_uses > 0 {
+            decls.push((1, ValType::I32));
+            decls.push((2, ValType::I64));
+        }
+        let mut lowered = vec![];
+        write_uleb(&mut lowered, decls.len() as u64);
+        for (count, ty) in decls {
+            write_uleb(&mut lowered, count.into());
+            lowered.push(type_code(ty));
+        }
+        lowered.extend(g.code);
+        self.bodies.push(lowered);
+        Ok(())
+    }
+
+    /// Gets the function implementing an operator, appending it if needed.
+    fn implementation(&mut self, op: OperatorCode) -> Result<u32> {
+        let index = match self.impls.iter().position(|x| x.0 == op) {
+            Some(index) => index,
+            None => {
+                let (inputs, outputs, body) = Soft::implement(op)?;
+                let ty = self.extra_type(lowered(&inputs), lowered(&outputs));
+                self.impls.push((op, ty, body));
+                self.impls.len() - 1
+            }
+        };
+        Ok(self.imports + self.funcs.len() as u32 + index as u32)
+    }
+
+    /// Gets the index of an appended type, adding it if needed.
+    fn extra_type(&mut self, inputs: Vec<ValType>, outputs: Vec<ValType>) -> u32 {
+        let ty = (inputs, outputs);
+        let index = match self.extra_types.iter().position(|x| *x == ty) {
+            Some(index) => index,
+            None => {
+                self.extra_types.push(ty);
+                self.extra_types.len() - 1
+            }
+        };
+        self.type_count + index as u32
+    }
+
+    /// The type of blocks yielding a `v128`.
+    fn pair_type(&mut self) -> u32 {
+        self.extra_type(vec![], vec![ValType::I64; 2])
+    }
+
+    /// Produces the lowered binary, replacing the type, function, and code sections.
+    fn splice(self, wasm: &[u8]) -> Result<Vec<u8>> {
+        let mut reader = BinaryReader::new(wasm);
+        let mut output = reader.read_bytes(8)?.to_vec();
+        let funcs = self.funcs.len() + self.impls.len();
+
+        while !reader.eof() {
+            let id = reader.read_u8()?;
+            let size = reader.read_var_u32()? as usize;
+            let contents = reader.read_bytes(size)?;
+
+            let mut lowered = vec![];
+            match id {
+                1 => {
+                    let count = self.type_count as usize + self.extra_types.len();
+                    write_uleb(&mut lowered, count as u64);
+                    lowered.extend(&self.types);
+                    for (inputs, outputs) in &self.extra_types {
+                        write_func_type(&mut lowered, inputs, outputs);
+                    }
+                }
+                3 => {
+                    write_uleb(&mut lowered, funcs as u64);
+                    let impls = self.impls.iter().map(|x| x.1);
+                    for ty in self.funcs.iter().copied().chain(impls) {
+                        write_uleb(&mut lowered, ty.into());
+                    }
+                }
+                10 => {
+                    write_uleb(&mut lowered, funcs as u64);
+                    let impls = self.impls.iter().map(|x| &x.2);
+                    for body in self.bodies.iter().chain(impls) {
+                        write_uleb(&mut lowered, body.len() as u64);
+                        lowered.extend(body);
+                    }
+                }
+                _ => lowered.extend(contents),
+            }
+            output.push(id);
+            write_uleb(&mut output, lowered.len() as u64);
+            output.extend(lowered);
+        }
+        Ok(output)
+    }
+}
+
+/// Whether the offset of the high half of a `v128` in memory is representable.
+/// Accesses whose high half would lie beyond 4GB are always out of bounds.
+fn high_half_fits(memarg: &MemArg) -> bool {
+    memarg.offset + 8 <= u32::MAX.into()
+}
+
+fn is_v128(ty: &ValType) -> bool {
+    *ty == ValType::V128
+}
+
+/// The types representing some types once each `v128` is lowered to a pair of `i64`s.
+fn lowered(types: &[ValType]) -> Vec<ValType> {
+    let lower = |ty: &ValType| match ty {
+        ValType::V128 => vec![ValType::I64; 2],
+        ty => vec![*ty],
+    };
+    types.iter().flat_map(lower).collect()
+}
+
+fn type_code(ty: ValType) -> u8 {
+    match ty {
+        ValType::I32 => 0x7f,
+        ValType::I64 => 0x7e,
+        ValType::F32 => 0x7d,
+        ValType::F64 => 0x7c,
+        ValType::V128 => 0x7b,
+        ValType::FuncRef => 0x70,
+        ValType::ExternRef => 0x6f,
+    }
+}
+
+/// Writes a function type, lowering any `v128`s.
+fn write_func_type(out: &mut Vec<u8>, inputs: &[ValType], outputs: &[ValType]) {
+    out.push(0x60);
+    for types in [lowered(inputs), lowered(outputs)] {
+        write_uleb(out, types.len() as u64);
+        out.extend(types.into_iter().map(type_code));
+    }
+}
+
+/// The operations implemented in software, each parsed from the name of its operator.
+#[derive(Clone, Copy, Debug)]
+enum Soft {
+    Select,
+    Not,
+    /// The op, and whether to invert the right-hand side first.
+    Bitwise(IBinOpType, bool),
+    Bitselect,
+    AnyTrue,
+    /// Float abs and neg, which only touch the sign bits.
+    FloatSign(u32, IBinOpType),
+    Unary(u32, LaneUnOp),
+    Binary(u32, LaneBinOp),
+    Shift(u32, IBinOpType),
+    AllTrue(u32),
+    Bitmask(u32),
+    Splat(u32),
+    /// The lane width, and whether to sign-extend.
+    Extract(u32, bool),
+    Replace(u32),
+    LoadLane(u32),
+    Swizzle,
+    Shuffle,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum LaneUnOp {
+    Abs,
+    Neg,
+    Popcnt,
+    /// Whether to use the high lanes, and whether to sign-extend.
+    Extend(bool, bool),
+    ExtAddPairwise(bool),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum LaneBinOp {
+    Arith(IBinOpType),
+    Sat(IBinOpType, bool),
+    /// Lt for min and Gt for max.
+    MinMax(IRelOpType, bool),
+    Avgr,
+    Compare(IRelOpType, bool),
+    Q15MulrSat,
+    Narrow(bool),
+    /// Whether to use the high lanes, and whether to sign-extend.
+    ExtMul(bool, bool),
+    Dot,
+}
+
+/// A `v128` held in a pair of locals.
+type V = [u32; 2];
+
+impl Soft {
+    fn parse(op: OperatorCode) -> Option<Self> {
+        use IBinOpType::*;
+        use IRelOpType::*;
+        use LaneBinOp::*;
+        use Soft::*;
+
+        let name = op.to_string();
+        if name == "Select" {
+            return Some(Select);
+        }
+        let split = if name.starts_with("V128") { 4 } else { 5 };
+        let (shape, rest) = name.split_at(split.min(name.len()));
+        let signed = rest.ends_with('S');
+
+        let soft = match (shape, rest) {
+            ("V128", "Not") => Not,
+            ("V128", "And") => Bitwise(And, false),
+            ("V128", "AndNot") => Bitwise(And, true),
+            ("V128", "Or") => Bitwise(Or, false),
+            ("V128", "Xor") => Bitwise(Xor, false),
+            ("V128", "Bitselect") => Bitselect,
+            ("V128", "AnyTrue") => AnyTrue,
+            ("V128", "Load8Lane") => LoadLane(8),
+            ("V128", "Load16Lane") => LoadLane(16),
+            ("V128", "Load32Lane") => LoadLane(32),
+            ("V128", "Load64Lane") => LoadLane(64),
+            ("F32x4", "Abs") => FloatSign(32, And),
+            ("F32x4", "Neg") => FloatSign(32, Xor),
+            ("F64x2", "Abs") => FloatSign(64, And),
+            ("F64x2", "Neg") => FloatSign(64, Xor),
+            ("I8x16", "Swizzle") => Swizzle,
+            ("I8x16", "Shuffle") => Shuffle,
+            (shape, rest) => {
+                let width = match shape {
+                    "I8x16" => 8,
+                    "I16x8" => 16,
+                    "I32x4" => 32,
+                    "I64x2" => 64,
+                    _ => return None,
+                };
+                match rest {
+                    "Add" => Soft::Binary(width, Arith(Add)),
+                    "Sub" => Soft::Binary(width, Arith(Sub)),
+                    "Mul" => Soft::Binary(width, Arith(Mul)),
+                    "AddSatS" | "AddSatU" => Soft::Binary(width, Sat(Add, signed)),
+                    "SubSatS" | "SubSatU" => Soft::Binary(width, Sat(Sub, signed)),
+                    "MinS" | "MinU" => Soft::Binary(width, MinMax(Lt, signed)),
+                    "MaxS" | "MaxU" => Soft::Binary(width, MinMax(Gt, signed)),
+                    "AvgrU" => Soft::Binary(width, Avgr),
+                    "Eq" => Soft::Binary(width, Compare(Eq, false)),
+                    "Ne" => Soft::Binary(width, Compare(Ne, false)),
+                    "LtS" | "LtU" => Soft::Binary(width, Compare(Lt, signed)),
+                    "GtS" | "GtU" => Soft::Binary(width, Compare(Gt, signed)),
+                    "LeS" | "LeU" => Soft::Binary(width, Compare(Le, signed)),
+                    "GeS" | "GeU" => Soft::Binary(width, Compare(Ge, signed)),
+                    "Q15MulrSatS" => Soft::Binary(width, Q15MulrSat),
+                    "DotI16x8S" => Soft::Binary(width, Dot),
+                    "Abs" => Soft::Unary(width, LaneUnOp::Abs),
+                    "Neg" => Soft::Unary(width, LaneUnOp::Neg),
+                    "Popcnt" => Soft::Unary(width, LaneUnOp::Popcnt),
+                    "Shl" => Shift(width, Shl),
+                    "ShrS" => Shift(width, ShrS),
+                    "ShrU" => Shift(width, ShrU),
+                    "AllTrue" => AllTrue(width),
+                    "Bitmask" => Bitmask(width),
+                    "Splat" => Splat(width),
+                    "ExtractLane" | "ExtractLaneS" | "ExtractLaneU" => Extract(width, signed),
+                    "ReplaceLane" => Replace(width),
+                    _ if rest.starts_with("Narrow") => Soft::Binary(width, Narrow(signed)),
+                    _ if rest.starts_with("ExtMulLow") => {
+                        Soft::Binary(width, ExtMul(false, signed))
+                    }
+                    _ if rest.starts_with("ExtMulHigh") => {
+                        Soft::Binary(width, ExtMul(true, signed))
+                    }
+                    _ if rest.starts_with("ExtendLow") => {
+                        Soft::Unary(width, LaneUnOp::Extend(false, signed))
+                    }
+                    _ if rest.starts_with("ExtendHigh") => {
+                        Soft::Unary(width, LaneUnOp::Extend(true, signed))
+                    }
+                    _ if rest.starts_with("ExtAddPairwise") => {
+                        Soft::Unary(width, LaneUnOp::ExtAddPairwise(signed))
+                    }
+                    _ => return None,
+                }
+            }
+        };
+        Some(soft)
+    }
+
+    /// Builds the function implementing an operator, returning its signature and body.
+    fn implement(op: OperatorCode) -> Result<(Vec<ValType>, Vec<ValType>, Vec<u8>)> {
+        let Some(soft) = Self::parse(op) else {
+            bail!("SIMD operator {op} not supported")
+        };
+        let (inputs, outputs) = soft.signature();
+        let mut gen = Gen::new(lowered(&inputs).len() as u32);
+        soft.generate(&mut gen);
+
+        let mut body = vec![];
+        match gen.temps {
+            0 => body.push(0),
+            temps => {
+                body.extend([1, temps as u8]);
+                body.push(type_code(ValType::I64));
+            }
+        }
+        body.extend(gen.code);
+        body.push(0x0B);
+        Ok((inputs, outputs, body))
+    }
+
+    /// The wasm signature of the implementation, with any immediates as trailing params.
+    fn signature(self) -> (Vec<ValType>, Vec<ValType>) {
+        use Soft::*;
+        use ValType::*;
+
+        let scalar = |width| if width == 64 { I64 } else { I32 };
+        match self {
+            Select => (vec![V128, V128, I32], vec![V128]),
+            Not | FloatSign(..) | Soft::Unary(..) => (vec![V128], vec![V128]),
+            Bitwise(..) | Soft::Binary(..) | Swizzle => (vec![V128, V128], vec![V128]),
+            Bitselect => (vec![V128, V128, V128], vec![V128]),
+            AnyTrue | AllTrue(_) | Bitmask(_) => (vec![V128], vec![I32]),
+            Shift(..) => (vec![V128, I32], vec![V128]),
+            Splat(width) => (vec![scalar(width)], vec![V128]),
+            Extract(width, _) => (vec![V128, I32], vec![scalar(width)]),
+            Replace(width) => (vec![V128, scalar(width), I32], vec![V128]),
+            LoadLane(_) => (vec![I64, V128, I32], vec![V128]),
+            Shuffle => (vec![V128, V128, I64, I64], vec![V128]),
+        }
+    }
+
+    /// Emits the body of the implementation, whose params are laid out as in its signature.
+    fn generate(self, g: &mut Gen) {
+        use IBinOpType::*;
+        use IRelOpType::*;
+        use IntegerValType::*;
+
+        const A: V = [0, 1];
+        const B: V = [2, 3];
+        const C: V = [4, 5];
+
+        match self {
+            Soft::Select => {
+                for half in 0..2 {
+                    g.get(A[half]);
+                    g.get(B[half]);
+                    g.get(4);
+                    g.op(Opcode::Select);
+                }
+            }
+            Soft::Not => {
+                for half in A {
+                    g.get(half);
+                    g.i64(u64::MAX);
+                    g.bin(Xor);
+                }
+            }
+            Soft::Bitwise(op, invert) => {
+                for half in 0..2 {
+                    g.get(A[half]);
+                    g.get(B[half]);
+                    if invert {
+                        g.i64(u64::MAX);
+                        g.bin(Xor);
+                    }
+                    g.bin(op);
+                }
+            }
+            Soft::Bitselect => {
+                for half in 0..2 {
+                    g.get(A[half]);
+                    g.get(C[half]);
+                    g.bin(And);
+                    g.get(B[half]);
+                    g.get(C[half]);
+                    g.i64(u64::MAX);
+                    g.bin(Xor);
+                    g.bin(And);
+                    g.bin(Or);
+                }
+            }
+            Soft::AnyTrue => {
+                g.get(A[0]);
+                g.get(A[1]);
+                g.bin(Or);
+                g.i64(0);
+                g.rel(Ne, false);
+            }
+            Soft::FloatSign(width, op) => {
+                let sign = match width {
+                    32 => 0x8000_0000_8000_0000,
+                    _ => 1 << 63,
+                };
+                let mask = if op == And { !sign } else { sign };
+                for half in A {
+                    g.get(half);
+                    g.i64(mask);
+                    g.bin(op);
+                }
+            }
+            Soft::Unary(width, op) => g.lanes(width, |g, i| match op {
+                LaneUnOp::Abs => {
+                    let t = g.temp(2);
+                    g.lane(A, width, i, true);
+                    g.set(t);
+                    g.i64(0);
+                    g.get(t);
+                    g.bin(Sub);
+                    g.get(t);
+                    g.get(t);
+                    g.i64(0);
+                    g.rel(Lt, true);
+                    g.op(Opcode::Select);
+                }
+                LaneUnOp::Neg => {
+                    g.i64(0);
+                    g.lane(A, width, i, false);
+                    g.bin(Sub);
+                }
+                LaneUnOp::Popcnt => {
+                    g.lane(A, width, i, false);
+                    g.op(Opcode::IUnOp(I64, IUnOpType::Popcnt));
+                }
+                LaneUnOp::Extend(high, signed) => {
+                    let offset = if high { 128 / width } else { 0 };
+                    g.lane(A, width / 2, i + offset, signed);
+                }
+                LaneUnOp::ExtAddPairwise(signed) => {
+                    g.lane(A, width / 2, 2 * i, signed);
+                    g.lane(A, width / 2, 2 * i + 1, signed);
+                    g.bin(Add);
+                }
+            }),
+            Soft::Binary(width, op) => g.lanes(width, |g, i| match op {
+                LaneBinOp::Arith(op) => {
+                    g.lane(A, width, i, false);
+                    g.lane(B, width, i, false);
+                    g.bin(op);
+                }
+                LaneBinOp::Sat(op, signed) => {
+                    g.lane(A, width, i, signed);
+                    g.lane(B, width, i, signed);
+                    g.bin(op);
+                    g.saturate(width, signed);
+                }
+                LaneBinOp::MinMax(rel, signed) => {
+                    let (a, b) = (g.temp(2), g.temp(3));
+                    g.lane(A, width, i, signed);
+                    g.set(a);
+                    g.lane(B, width, i, signed);
+                    g.set(b);
+                    g.get(a);
+                    g.get(b);
+                    g.get(a);
+                    g.get(b);
+                    g.rel(rel, true); // lanes are extended, so a signed comparison suffices
+                    g.op(Opcode::Select);
+                }
+                LaneBinOp::Avgr => {
+                    g.lane(A, width, i, false);
+                    g.lane(B, width, i, false);
+                    g.bin(Add);
+                    g.i64(1);
+                    g.bin(Add);
+                    g.i64(1);
+                    g.bin(ShrU);
+                }
+                LaneBinOp::Compare(rel, signed) => {
+                    g.lane(A, width, i, signed);
+                    g.lane(B, width, i, signed);
+                    g.rel(rel, signed);
+                    g.op(Opcode::I64ExtendI32(false));
+                    g.i64(u64::MAX);
+                    g.bin(Mul);
+                }
+                LaneBinOp::Q15MulrSat => {
+                    g.lane(A, width, i, true);
+                    g.lane(B, width, i, true);
+                    g.bin(Mul);
+                    g.i64(0x4000);
+                    g.bin(Add);
+                    g.i64(15);
+                    g.bin(ShrS);
+                    g.saturate(width, true);
+                }
+                LaneBinOp::Narrow(signed) => {
+                    let lanes = 64 / width;
+                    match i < lanes {
+                        true => g.lane(A, 2 * width, i, true),
+                        false => g.lane(B, 2 * width, i - lanes, true),
+                    }
+                    g.saturate(width, signed);
+                }
+                LaneBinOp::ExtMul(high, signed) => {
+                    let offset = if high { 128 / width } else { 0 };
+                    g.lane(A, width / 2, i + offset, signed);
+                    g.lane(B, width / 2, i + offset, signed);
+                    g.bin(Mul);
+                }
+                LaneBinOp::Dot => {
+                    for lane in [2 * i, 2 * i + 1] {
+                        g.lane(A, width / 2, lane, true);
+                        g.lane(B, width / 2, lane, true);
+                        g.bin(Mul);
+                    }
+                    g.bin(Add);
+                }
+            }),
+            Soft::Shift(width, op) => g.lanes(width, |g, i| {
+                g.lane(A, width, i, op == ShrS);
+                g.get(2);
+                g.op(Opcode::I64ExtendI32(false));
+                g.i64((width - 1).into());
+                g.bin(And);
+                g.bin(op);
+            }),
+            Soft::AllTrue(width) => {
+                for i in 0..128 / width {
+                    g.lane(A, width, i, false);
+                    g.i64(0);
+                    g.rel(Ne, false);
+                    if i > 0 {
+                        g.op(Opcode::IBinOp(I32, And));
+                    }
+                }
+            }
+            Soft::Bitmask(width) => {
+                for i in 0..128 / width {
+                    g.lane(A, width, i, true);
+                    g.i64(0);
+                    g.rel(Lt, true);
+                    if i > 0 {
+                        g.i32(i);
+                        g.op(Opcode::IBinOp(I32, Shl));
+                        g.op(Opcode::IBinOp(I32, Or));
+                    }
+                }
+            }
+            Soft::Splat(width) => g.lanes(width, |g, _| {
+                g.get(0);
+                if width < 64 {
+                    g.op(Opcode::I64ExtendI32(false));
+                }
+            }),
+            Soft::Extract(width, signed) => {
+                g.get(A[1]);
+                g.get(A[0]);
+                g.get(2);
+                g.i32(64 / width);
+                g.op(Opcode::IRelOp(I32, Ge, false));
+                g.op(Opcode::Select);
+                g.lane_shift(width, 2);
+                g.bin(ShrU);
+                if width < 32 {
+                    g.extend(width, signed);
+                }
+                if width < 64 {
+                    g.op(Opcode::I32WrapI64);
+                }
+            }
+            Soft::Replace(width) => g.replace(A, 2, 3, width, width < 64),
+            Soft::LoadLane(width) => g.replace([1, 2], 0, 3, width, false),
+            Soft::Swizzle => g.lanes(8, |g, i| {
+                let t = g.temp(2);
+                g.lane(B, 8, i, false);
+                g.set(t);
+                g.get(A[1]);
+                g.get(A[0]);
+                g.get(t);
+                g.i64(8);
+                g.rel(Ge, false);
+                g.op(Opcode::Select);
+                g.get(t);
+                g.i64(7);
+                g.bin(And);
+                g.i64(8);
+                g.bin(Mul);
+                g.bin(ShrU);
+
+                // out of range indices produce 0
+                g.i64(0);
+                g.get(t);
+                g.i64(16);
+                g.rel(Lt, false);
+                g.op(Opcode::Select);
+            }),
+            Soft::Shuffle => g.lanes(8, |g, i| {
+                let t = g.temp(2);
+                g.lane(C, 8, i, false);
+                g.set(t);
+                for v in [B, A] {
+                    g.get(v[1]);
+                    g.get(v[0]);
+                    g.get(t);
+                    g.i64(8);
+                    g.bin(And);
+                    g.i64(0);
+                    g.rel(Ne, false);
+                    g.op(Opcode::Select);
+                }
+                g.get(t);
+                g.i64(16);
+                g.rel(Ge, false);
+                g.op(Opcode::Select);
+                g.get(t);
+                g.i64(7);
+                g.bin(And);
+                g.i64(8);
+                g.bin(Mul);
+                g.bin(ShrU);
+            }),
+        }
+    }
+}
+
+/// Emits wasm that operates on the `i64` halves of `v128`s.
+struct Gen {
+    code: Vec<u8>,
+    /// The number of params, after which come the scratch locals.
+    params: u32,
+    /// The number of scratch locals, each an `i64`.
+    temps: u32,
+}
+
+impl Gen {
+    fn new(params: u32) -> Self {
+        Self {
+            code: vec![],
+            params,
+            temps: 0,
+        }
+    }
+
+    fn op(&mut self, opcode: Opcode) {
+        self.code.push(opcode.repr() as u8);
+    }
+
+    fn with_index(&mut self, opcode: u8, index: u32) {
+        self.code.push(opcode);
+        write_uleb(&mut self.code, index.into());
+    }
+
+    fn get(&mut self, local: u32) {
+        self.with_index(0x20, local);
+    }
+
+    fn set(&mut self, local: u32) {
+        self.with_index(0x21, local);
+    }
+
+    fn tee(&mut self, local: u32) {
+        self.with_index(0x22, local);
+    }
+
+    fn call(&mut self, func: u32) {
+        self.with_index(0x10, func);
+    }
+
+    fn i32(&mut self, value: u32) {
+        self.code.push(0x41);
+        write_sleb(&mut self.code, value as i32 as i64);
+    }
+
+    fn i64(&mut self, value: u64) {
+        self.code.push(0x42);
+        write_sleb(&mut self.code, value as i64);
+    }
+
+    /// Writes a memarg, offset further and with an alignment no greater than the access's.
+    fn memarg(&mut self, bytes: u8, memarg: &MemArg, offset: u64) {
+        let align = memarg.align.min(bytes.trailing_zeros() as u8);
+        write_uleb(&mut self.code, align.into());
+        write_uleb(&mut self.code, memarg.offset + offset);
+    }
+
+    /// Loads a value, zero-extending it if fewer bytes are read than its type holds.
+    fn load(&mut self, ty: ArbValueType, bytes: u8, memarg: &MemArg, offset: u64) {
+        let signed = false;
+        self.op(Opcode::MemoryLoad { ty, bytes, signed });
+        self.memarg(bytes, memarg, offset);
+    }
+
+    fn store(&mut self, ty: ArbValueType, bytes: u8, memarg: &MemArg, offset: u64) {
+        self.op(Opcode::MemoryStore { ty, bytes });
+        self.memarg(bytes, memarg, offset);
+    }
+
+    fn bin(&mut self, op: IBinOpType) {
+        self.op(Opcode::IBinOp(IntegerValType::I64, op));
+    }
+
+    /// Compares two i64s, producing an i32.
+    fn rel(&mut self, op: IRelOpType, signed: bool) {
+        self.op(Opcode::IRelOp(IntegerValType::I64, op, signed));
+    }
+
+    /// Gets the nth scratch local, allocating it if needed.
+    fn temp(&mut self, n: u32) -> u32 {
+        self.temps = self.temps.max(n + 1);
+        self.params + n
+    }
+
+    /// Pushes a lane of a vector as an i64, sign- or zero-extending it.
+    fn lane(&mut self, v: V, width: u32, lane: u32, signed: bool) {
+        let bit = lane * width;
+        self.get(v[(bit / 64) as usize]);
+        if bit % 64 != 0 {
+            self.i64((bit % 64).into());
+            self.bin(IBinOpType::ShrU);
+        }
+        self.extend(width, signed);
+    }
+
+    /// Sign- or zero-extends the low bits of the i64 atop the stack.
+    fn extend(&mut self, width: u32, signed: bool) {
+        match (width, signed) {
+            (64, _) => {}
+            (_, true) => self.op(Opcode::I64ExtendS(width as u8)),
+            (_, false) => {
+                self.i64(mask(width));
+                self.bin(IBinOpType::And);
+            }
+        }
+    }
+
+    /// Pushes a vector, each of whose lanes `f` computes by pushing an i64.
+    fn lanes(&mut self, width: u32, mut f: impl FnMut(&mut Self, u32)) {
+        let acc = [self.temp(0), self.temp(1)];
+        for lane in 0..128 / width {
+            f(self, lane);
+
+            let bit = lane * width;
+            let half = acc[(bit / 64) as usize];
+            if width < 64 {
+                self.i64(mask(width));
+                self.bin(IBinOpType::And);
+            }
+            if bit % 64 != 0 {
+                self.i64((bit % 64).into());
+                self.bin(IBinOpType::Shl);
+                self.get(half);
+                self.bin(IBinOpType::Or);
+            }
+            self.set(half);
+        }
+        self.get(acc[0]);
+        self.get(acc[1]);
+    }
+
+    /// Clamps the i64 atop the stack to the range of a lane.
+    fn saturate(&mut self, width: u32, signed: bool) {
+        let (min, max): (i64, i64) = match signed {
+            true => (-(1 << (width - 1)), (1 << (width - 1)) - 1),
+            false => (0, (1 << width) - 1),
+        };
+        let t = self.temp(4);
+        for (bound, rel) in [(max, IRelOpType::Gt), (min, IRelOpType::Lt)] {
+            self.set(t);
+            self.i64(bound as u64);
+            self.get(t);
+            self.get(t);
+            self.i64(bound as u64);
+            self.rel(rel, true);
+            self.op(Opcode::Select);
+        }
+    }
+
+    /// Pushes the bit offset of a dynamic lane within its half as an i64.
+    fn lane_shift(&mut self, width: u32, lane: u32) {
+        self.get(lane);
+        self.op(Opcode::I64ExtendI32(false));
+        self.i64((64 / width - 1).into());
+        self.bin(IBinOpType::And);
+        self.i64(width.into());
+        self.bin(IBinOpType::Mul);
+    }
+
+    /// Pushes a vector with a dynamic lane replaced by a scalar.
+    fn replace(&mut self, v: V, scalar: u32, lane: u32, width: u32, is_i32: bool) {
+        use IBinOpType::*;
+
+        for (half, local) in (0..).zip(v) {
+            self.get(local);
+            self.i64(mask(width));
+            self.lane_shift(width, lane);
+            self.bin(Shl);
+            self.i64(u64::MAX);
+            self.bin(Xor);
+            self.bin(And);
+
+            self.get(scalar);
+            if is_i32 {
+                self.op(Opcode::I64ExtendI32(false));
+            }
+            self.i64(mask(width));
+            self.bin(And);
+            self.lane_shift(width, lane);
+            self.bin(Shl);
+            self.bin(Or);
+
+            // keep the half as is when the lane is in the other one
+            self.get(local);
+            self.get(lane);
+            self.i32(64 / width);
+            self.op(Opcode::IBinOp(IntegerValType::I32, DivU));
+            self.i32(half);
+            self.op(Opcode::IRelOp(IntegerValType::I32, IRelOpType::Eq, false));
+            self.op(Opcode::Select);
+        }
+    }
+}
+
+/// The low bits of a lane.
+fn mask(width: u32) -> u64 {
+    u64::MAX >> (64 - width)
+}
@@ -0,0 +1,202 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+//! Compressed, incremental snapshots of a machine's state.
+//!
+//! Replay machines have gigabytes of memory, little of which changes between snapshots. So rather
+//! than dump each memory in full, a snapshot stores only the leaves that differ from those at its
+//! parent, which comparing memory merkle trees finds without scanning the memory. The writer keeps
+//! a copy of each tree as of the parent, updating only the paths that changed rather than cloning
+//! the tree anew. Snapshots form a chain whose first is relative to zeroed memories, and are
+//! restored by applying the chain in order.
+
+use crate::{
+    machine::{Machine, MachineState},
+    memory::Memory,
+    merkle::Merkle,
+};
+use arbutil::{crypto, Bytes32};
+use brotli::Dictionary;
+use eyre::{bail, ensure, Result};
+use serde::{Deserialize, Serialize};
+
+/// Snapshots are taken often, so favor speed over ratio.
+const COMPRESSION_LEVEL: u32 = 4;
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot<'a> {
+    /// The keccak of the parent snapshot, if any.
+    parent: Option<Bytes32>,
+    /// The machine's hash when snapshotted.
+    hash: Bytes32,
+    /// Everything that changes as the machine executes, save its memories.
+    state: MachineState<'a>,
+    /// Each module's memory, relative to the parent's.
+    memories: Vec<MemoryDelta>,
+}
+
+/// The leaves of a memory that changed since the parent snapshot.
+#[derive(Serialize, Deserialize)]
+struct MemoryDelta {
+    size: u64,
+    max_size: u64,
+    leaves: Vec<(u64, [u8; Memory::LEAF_SIZE])>,
+}
+
+impl MemoryDelta {
+    /// Ensures the leaves fit in the memory, so that they may be applied without error.
+    fn validate(&self) -> Result<()> {
+        let leaf_size = Memory::LEAF_SIZE as u64;
+        for (leaf, data) in &self.leaves {
+            let Some(offset) = leaf.checked_mul(leaf_size).filter(|x| *x < self.size) else {
+                bail!("leaf {leaf} out of bounds for memory of size {}", self.size);
+            };
+            let size = (self.size - offset).min(leaf_size) as usize;
+            ensure!(
+                data[size..].iter().all(|x| *x == 0),
+                "leaf {leaf} writes past the end of memory",
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Writes a chain of snapshots of a machine as it executes.
+pub struct SnapshotWriter {
+    initial_hash: Bytes32,
+    /// The keccak of the last snapshot, if any.
+    parent: Option<Bytes32>,
+    /// Each module's memory merkle as of the last snapshot.
+    merkles: Vec<Merkle>,
+}
+
+impl SnapshotWriter {
+    /// Starts a chain of snapshots for the given machine.
+    pub fn new(machine: &Machine) -> Self {
+        let empty = Memory::default().merkelize().into_owned();
+        Self {
+            initial_hash: machine.get_initial_hash(),
+            parent: None,
+            merkles: vec![empty; machine.memories().count()],
+        }
+    }
+
+    /// Snapshots the machine, producing the next link of the chain.
+    pub fn snapshot(&mut self, machine: &Machine) -> Result<Vec<u8>> {
+        ensure!(
+            machine.get_initial_hash() == self.initial_hash,
+            "attempted to snapshot machine with initial hash {} into chain for {}",
+            machine.get_initial_hash(),
+            self.initial_hash,
+        );
+        ensure!(
+            machine.memories().count() == self.merkles.len(),
+            "machine has {} modules but the chain has {}",
+            machine.memories().count(),
+            self.merkles.len(),
+        );
+
+        let mut changes = vec![];
+        let mut memories = vec![];
+        for (memory, prior) in machine.memories().zip(&self.merkles) {
+            let merkle = memory.merkelize();
+            let dirty = merkle.diff(prior);
+            let leaves = dirty.iter().map(|&x| (x as u64, memory.get_leaf_data(x)));
+
+            memories.push(MemoryDelta {
+                size: memory.size(),
+                max_size: memory.max_size,
+                leaves: leaves.collect(),
+            });
+            changes.push((merkle, dirty));
+        }
+
+        let snapshot = Snapshot {
+            parent: self.parent,
+            hash: machine.hash(),
+            state: machine.state(false),
+            memories,
+        };
+        let snapshot = bincode::serialize(&snapshot)?;
+        let window = brotli::DEFAULT_WINDOW_SIZE;
+        let Ok(output) = brotli::compress(&snapshot, COMPRESSION_LEVEL, window, Dictionary::Empty)
+        else {
+            bail!("failed to compress snapshot");
+        };
+
+        // only now that the snapshot can't fail, bring the trees up to date
+        for (prior, (merkle, dirty)) in self.merkles.iter_mut().zip(changes) {
+            prior.copy_paths(&merkle, &dirty);
+        }
+        self.parent = Some(crypto::keccak(&output).into());
+        Ok(output)
+    }
+}
+
+/// Restores a machine to the last snapshot of a chain, which must start with its first.
+///
+/// Each snapshot must be of this machine and follow the one before it, and the restored machine
+/// must hash to what was snapshotted. Only a failure of this last check leaves `machine` mutated,
+/// in which case its state is unspecified.
+pub fn restore<T: AsRef<[u8]>>(machine: &mut Machine, chain: &[T]) -> Result<()> {
+    let mut parent = None;
+    let mut last = None;
+    let mut deltas = vec![];
+    let modules = machine.memories().count();
+
+    for (i, data) in chain.iter().map(AsRef::as_ref).enumerate() {
+        let Ok(snapshot) = brotli::decompress(data, Dictionary::Empty) else {
+            bail!("failed to decompress snapshot {i}");
+        };
+        let snapshot: Snapshot = bincode::deserialize(&snapshot)?;
+        ensure!(
+            snapshot.parent == parent,
+            "snapshot {i} doesn't follow its predecessor in the chain",
+        );
+        ensure!(
+            snapshot.memories.len() == modules,
+            "snapshot {i} has {} memories but the machine has {modules}",
+            snapshot.memories.len(),
+        );
+        for delta in &snapshot.memories {
+            delta.validate()?;
+        }
+        parent = Some(crypto::keccak(data).into());
+        deltas.push(snapshot.memories);
+        last = Some((snapshot.hash, snapshot.state));
+    }
+    let Some((hash, state)) = last else {
+        bail!("no snapshots to restore");
+    };
+
+    // checks the initial hash, failing before mutating anything
+    machine.replace_state(state, false)?;
+
+    // replay the memories, starting from zero
+    let mut fresh = true;
+    for delta in deltas {
+        for (memory, delta) in machine.memories_mut().zip(delta) {
+            if fresh {
+                *memory = Memory::new(delta.size as usize, delta.max_size);
+            } else {
+                memory.resize(delta.size as usize);
+                memory.max_size = delta.max_size;
+            }
+            for (leaf, data) in delta.leaves {
+                let stored = memory.set_leaf_data(leaf as usize, data);
+                assert!(stored, "leaf {leaf} not validated");
+            }
+        }
+        fresh = false;
+    }
+
+    if machine.is_caching_merkles() {
+        machine.start_merkle_caching();
+    }
+    ensure!(
+        machine.hash() == hash,
+        "restored machine hash {} doesn't match snapshot hash {hash}",
+        machine.hash(),
+    );
+    Ok(())
+}
@@ -3,7 +3,7 @@
 
 #![cfg(test)]
 
-use crate::binary;
+use crate::{binary, value::ArbValueType};
 use brotli::Dictionary;
 use eyre::Result;
 use std::path::Path;
@@ -69,3 +69,945 @@ pub fn test_compress() -> Result<()> {
     }
     Ok(())
 }
+
+#[test]
+pub fn test_pricing_tables() -> Result<()> {
+    use crate::programs::config::{CompilePricingParams, OpCosts, SigMap};
+    use wasmparser::Operator;
+
+    let v1 = CompilePricingParams::v1();
+    let sigs = SigMap::default();
+    assert_eq!(v1.costs.price(&Operator::I32Add, &sigs), 70);
+    assert_eq!(v1.costs.price(&Operator::F32Add, &sigs), u64::MAX);
+    assert_eq!(v1.ink_header_cost, 2450);
+
+    // tables round trip through json, and identical tables share a digest
+    let json = serde_json::to_string(&v1)?;
+    let parsed = CompilePricingParams::from_json(&json)?;
+    assert_eq!(parsed, v1);
+    assert_eq!(parsed.digest(), v1.digest());
+
+    let toml = r#"
+        ink_header_cost = 10
+        memory_fill_ink = 1
+        memory_copy_ink = 1
+
+        [costs]
+        fallback = 5
+
+        [costs.ops]
+        I32Add = 100
+    "#;
+    let custom = CompilePricingParams::from_toml(toml)?;
+    assert_eq!(custom.costs.price(&Operator::I32Add, &sigs), 100);
+    assert_eq!(custom.costs.price(&Operator::F32Add, &sigs), 5);
+    assert_ne!(custom.digest(), v1.digest());
+    assert_ne!(custom.costs, OpCosts::uniform(5));
+
+    let unknown = toml.replace("I32Add", "I32Frobnicate");
+    assert!(CompilePricingParams::from_toml(&unknown).is_err());
+    Ok(())
+}
+
+#[test]
+pub fn test_pricing_v1_unchanged() -> Result<()> {
+    use crate::programs::{config::CompilePricingParams, ModuleMod};
+    use arbutil::operator::OperatorInfo;
+    use std::collections::HashSet;
+    use wasmparser::Operator::*;
+
+    // every operator v1 prices, followed by some it doesn't
+    let wasm = as_wasm(
+        r#"
+        (module
+            (type $pair (func (param i32 i64)))
+            (memory 1 1)
+            (table 1 funcref)
+            (global $g (mut i32) (i32.const 0))
+            (data $seg "")
+            (func $void)
+            (func $f (param $x i32) (param $y i64) (result i32)
+                (local $z f32)
+                nop
+                (drop (i64.const 0))
+                (block (br 0))
+                (loop (br_if 0 (local.get $x)))
+                (if (local.get $x) (then nop) (else nop))
+                (block (block (br_table 0 1 1 (local.get $x))))
+                (drop (select (local.get $x) (i32.const 1) (i32.const 2)))
+                (call $void)
+                (call_indirect (type $pair) (i32.const 0) (i64.const 0) (i32.const 0))
+                (local.set $x (local.tee $x (global.get $g)))
+                (global.set $g (local.get $x))
+
+                (drop (i32.load (i32.const 0)))
+                (drop (i32.load8_s (i32.const 0)))
+                (drop (i32.load8_u (i32.const 0)))
+                (drop (i32.load16_s (i32.const 0)))
+                (drop (i32.load16_u (i32.const 0)))
+                (drop (i64.load (i32.const 0)))
+                (drop (i64.load8_s (i32.const 0)))
+                (drop (i64.load8_u (i32.const 0)))
+                (drop (i64.load16_s (i32.const 0)))
+                (drop (i64.load16_u (i32.const 0)))
+                (drop (i64.load32_s (i32.const 0)))
+                (drop (i64.load32_u (i32.const 0)))
+                (i32.store (i32.const 0) (local.get $x))
+                (i32.store8 (i32.const 0) (local.get $x))
+                (i32.store16 (i32.const 0) (local.get $x))
+                (i64.store (i32.const 0) (local.get $y))
+                (i64.store8 (i32.const 0) (local.get $y))
+                (i64.store16 (i32.const 0) (local.get $y))
+                (i64.store32 (i32.const 0) (local.get $y))
+                (drop (memory.grow (memory.size)))
+                (memory.copy (i32.const 0) (i32.const 0) (i32.const 0))
+                (memory.fill (i32.const 0) (i32.const 0) (i32.const 0))
+
+                (drop (i32.eqz (local.get $x)))
+                (drop (i32.eq (local.get $x) (local.get $x)))
+                (drop (i32.ne (local.get $x) (local.get $x)))
+                (drop (i32.lt_s (local.get $x) (local.get $x)))
+                (drop (i32.lt_u (local.get $x) (local.get $x)))
+                (drop (i32.gt_s (local.get $x) (local.get $x)))
+                (drop (i32.gt_u (local.get $x) (local.get $x)))
+                (drop (i32.le_s (local.get $x) (local.get $x)))
+                (drop (i32.le_u (local.get $x) (local.get $x)))
+                (drop (i32.ge_s (local.get $x) (local.get $x)))
+                (drop (i32.ge_u (local.get $x) (local.get $x)))
+                (drop (i64.eqz (local.get $y)))
+                (drop (i64.eq (local.get $y) (local.get $y)))
+                (drop (i64.ne (local.get $y) (local.get $y)))
+                (drop (i64.lt_s (local.get $y) (local.get $y)))
+                (drop (i64.lt_u (local.get $y) (local.get $y)))
+                (drop (i64.gt_s (local.get $y) (local.get $y)))
+                (drop (i64.gt_u (local.get $y) (local.get $y)))
+                (drop (i64.le_s (local.get $y) (local.get $y)))
+                (drop (i64.le_u (local.get $y) (local.get $y)))
+                (drop (i64.ge_s (local.get $y) (local.get $y)))
+                (drop (i64.ge_u (local.get $y) (local.get $y)))
+
+                (drop (i32.clz (local.get $x)))
+                (drop (i32.ctz (local.get $x)))
+                (drop (i32.popcnt (local.get $x)))
+                (drop (i32.add (local.get $x) (local.get $x)))
+                (drop (i32.sub (local.get $x) (local.get $x)))
+                (drop (i32.mul (local.get $x) (local.get $x)))
+                (drop (i32.div_s (local.get $x) (local.get $x)))
+                (drop (i32.div_u (local.get $x) (local.get $x)))
+                (drop (i32.rem_s (local.get $x) (local.get $x)))
+                (drop (i32.rem_u (local.get $x) (local.get $x)))
+                (drop (i32.and (local.get $x) (local.get $x)))
+                (drop (i32.or (local.get $x) (local.get $x)))
+                (drop (i32.xor (local.get $x) (local.get $x)))
+                (drop (i32.shl (local.get $x) (local.get $x)))
+                (drop (i32.shr_s (local.get $x) (local.get $x)))
+                (drop (i32.shr_u (local.get $x) (local.get $x)))
+                (drop (i32.rotl (local.get $x) (local.get $x)))
+                (drop (i32.rotr (local.get $x) (local.get $x)))
+                (drop (i64.clz (local.get $y)))
+                (drop (i64.ctz (local.get $y)))
+                (drop (i64.popcnt (local.get $y)))
+                (drop (i64.add (local.get $y) (local.get $y)))
+                (drop (i64.sub (local.get $y) (local.get $y)))
+                (drop (i64.mul (local.get $y) (local.get $y)))
+                (drop (i64.div_s (local.get $y) (local.get $y)))
+                (drop (i64.div_u (local.get $y) (local.get $y)))
+                (drop (i64.rem_s (local.get $y) (local.get $y)))
+                (drop (i64.rem_u (local.get $y) (local.get $y)))
+                (drop (i64.and (local.get $y) (local.get $y)))
+                (drop (i64.or (local.get $y) (local.get $y)))
+                (drop (i64.xor (local.get $y) (local.get $y)))
+                (drop (i64.shl (local.get $y) (local.get $y)))
+                (drop (i64.shr_s (local.get $y) (local.get $y)))
+                (drop (i64.shr_u (local.get $y) (local.get $y)))
+                (drop (i64.rotl (local.get $y) (local.get $y)))
+                (drop (i64.rotr (local.get $y) (local.get $y)))
+
+                (drop (i32.wrap_i64 (local.get $y)))
+                (drop (i64.extend_i32_s (local.get $x)))
+                (drop (i64.extend_i32_u (local.get $x)))
+                (drop (i32.extend8_s (local.get $x)))
+                (drop (i32.extend16_s (local.get $x)))
+                (drop (i64.extend8_s (local.get $y)))
+                (drop (i64.extend16_s (local.get $y)))
+                (drop (i64.extend32_s (local.get $y)))
+
+                (local.set $z (f32.add (f32.const 1) (f32.load (i32.const 0))))
+                (drop (i32.trunc_f32_s (local.get $z)))
+                (drop (f64.promote_f32 (local.get $z)))
+                (drop (i32.reinterpret_f32 (local.get $z)))
+                (memory.init $seg (i32.const 0) (i32.const 0) (i32.const 0))
+                (data.drop $seg)
+                (return (local.get $x))
+                unreachable
+            )
+        )"#,
+    );
+    let bin = binary::parse(&wasm, Path::new(""))?;
+    let sigs = bin.all_signatures()?;
+    let v1 = CompilePricingParams::v1().costs;
+    assert_eq!(v1.fallback, None);
+
+    let mut seen = HashSet::new();
+    for op in bin.codes.iter().flat_map(|code| &code.expr) {
+        assert_eq!(v1.price(op, &sigs), pricing_v1(op, &sigs), "{op:?}");
+        if pricing_v1(op, &sigs) != u64::MAX {
+            seen.insert(op.code());
+        }
+    }
+    assert_eq!(seen, v1.ops.keys().copied().collect::<HashSet<_>>());
+
+    // the per-target and per-input terms
+    let ops = &bin.codes[1].expr;
+    let br_table = ops.iter().find(|op| matches!(op, BrTable { .. })).unwrap();
+    let call_indirect = ops
+        .iter()
+        .find(|op| matches!(op, CallIndirect { .. }))
+        .unwrap();
+    assert_eq!(v1.price(br_table, &sigs), 2400 + 325 * 2);
+    assert_eq!(v1.price(call_indirect, &sigs), 13610 + 650 * 2);
+
+    // operators the wat above can't express
+    let unsupported = [
+        F64Add,
+        I32x4Add,
+        AtomicFence,
+        RefIsNull,
+        TableSize { table: 0 },
+        ReturnCall { function_index: 0 },
+        ElemDrop { elem_index: 0 },
+    ];
+    for op in unsupported {
+        assert_eq!(v1.price(&op, &sigs), u64::MAX, "{op:?}");
+    }
+    Ok(())
+}
+
+/// The v1 pricing as it was hardcoded before the tables.
+fn pricing_v1(op: &wasmparser::Operator, tys: &crate::programs::config::SigMap) -> u64 {
+    use wasmer_types::SignatureIndex;
+    use wasmparser::Operator::*;
+
+    macro_rules! op {
+        ($first:ident $(,$opcode:ident)*) => {
+            $first $(| $opcode)*
+        };
+    }
+    macro_rules! dot {
+        ($first:ident $(,$opcode:ident)*) => {
+            $first { .. } $(| $opcode { .. })*
+        };
+    }
+
+    #[rustfmt::skip]
+    let ink = match op {
+        op!(Unreachable, Return) => 1,
+        op!(Nop) | dot!(I32Const, I64Const) => 1,
+
+        op!(Drop) => 9,
+
+        dot!(Block, Loop) | op!(Else, End) => 1,
+        dot!(Br, BrIf, If) => 765,
+        dot!(Select) => 1250,
+        dot!(Call) => 3800,
+        dot!(LocalGet, LocalTee) => 75,
+        dot!(LocalSet) => 210,
+        dot!(GlobalGet) => 225,
+        dot!(GlobalSet) => 575,
+        dot!(I32Load, I32Load8S, I32Load8U, I32Load16S, I32Load16U) => 670,
+        dot!(I64Load, I64Load8S, I64Load8U, I64Load16S, I64Load16U, I64Load32S, I64Load32U) => 680,
+        dot!(I32Store, I32Store8, I32Store16) => 825,
+        dot!(I64Store, I64Store8, I64Store16, I64Store32) => 950,
+        dot!(MemorySize) => 3000,
+        dot!(MemoryGrow) => 1,
+
+        op!(I32Eqz, I32Eq, I32Ne, I32LtS, I32LtU, I32GtS, I32GtU, I32LeS, I32LeU, I32GeS, I32GeU) => 170,
+        op!(I64Eqz, I64Eq, I64Ne, I64LtS, I64LtU, I64GtS, I64GtU, I64LeS, I64LeU, I64GeS, I64GeU) => 225,
+
+        op!(I32Clz, I32Ctz) => 210,
+        op!(I32Add, I32Sub) => 70,
+        op!(I32Mul) => 160,
+        op!(I32DivS, I32DivU, I32RemS, I32RemU) => 1120,
+        op!(I32And, I32Or, I32Xor, I32Shl, I32ShrS, I32ShrU, I32Rotl, I32Rotr) => 70,
+
+        op!(I64Clz, I64Ctz) => 210,
+        op!(I64Add, I64Sub) => 100,
+        op!(I64Mul) => 160,
+        op!(I64DivS, I64DivU, I64RemS, I64RemU) => 1270,
+        op!(I64And, I64Or, I64Xor, I64Shl, I64ShrS, I64ShrU, I64Rotl, I64Rotr) => 100,
+
+        op!(I32Popcnt) => 2650,
+        op!(I64Popcnt) => 6000,
+
+        op!(I32WrapI64, I64ExtendI32S, I64ExtendI32U) => 100,
+        op!(I32Extend8S, I32Extend16S, I64Extend8S, I64Extend16S, I64Extend32S) => 100,
+        dot!(MemoryCopy) => 950,
+        dot!(MemoryFill) => 950,
+
+        BrTable { targets } => {
+            2400 + 325 * targets.len() as u64
+        },
+        CallIndirect { type_index, .. } => {
+            let ty = tys.get(&SignatureIndex::from_u32(*type_index)).expect("no type");
+            13610 + 650 * ty.inputs.len() as u64
+        },
+
+        _ => u64::MAX,
+    };
+    ink
+}
+
+#[test]
+pub fn test_simd_activation() -> Result<()> {
+    use crate::{machine::Module, simd};
+    use std::borrow::Cow;
+
+    let wasm = as_wasm(
+        r#"
+        (module
+            (memory (export "memory") 1 1)
+            (func (export "user_entrypoint") (param i32) (result i32)
+                (local v128)
+                (local.set 1 (i32x4.add (v128.load (i32.const 0)) (i32x4.splat (local.get 0))))
+                (i32x4.extract_lane 3 (local.get 1))
+            )
+        )"#,
+    );
+    let scalar = as_wasm(
+        r#"
+        (module
+            (memory (export "memory") 1 1)
+            (func (export "user_entrypoint") (param i32) (result i32)
+                (i32.add (i32.load (i32.const 12)) (local.get 0))
+            )
+        )"#,
+    );
+    assert!(matches!(simd::lower(&scalar)?, Cow::Borrowed(_)));
+
+    // SIMD is only lowered from version 2 onward
+    let mut gas = u64::MAX;
+    assert!(Module::activate(&wasm, 1, 128, false, &mut gas).is_err());
+    assert!(matches!(simd::lower_user(&wasm, 1)?, Cow::Borrowed(_)));
+
+    // v128s become pairs of i64s, with each vector operator implemented by an appended function
+    let lowered = simd::lower_user(&wasm, 2)?;
+    let bin = binary::parse(&lowered, Path::new("user"))?;
+    assert_eq!(bin.codes.len(), 4);
+
+    let (simd, _) = Module::activate(&wasm, 2, 128, false, &mut gas)?;
+    let (scalar, _) = Module::activate(&scalar, 2, 128, false, &mut gas)?;
+    assert_eq!(simd.funcs.len(), scalar.funcs.len() + 3);
+    Ok(())
+}
+
+#[test]
+pub fn test_memory_init() -> Result<()> {
+    use crate::{
+        machine::{GlobalState, Machine, MachineStatus},
+        value::Value,
+        verify::{self, ExecutionContext},
+    };
+    use std::{collections::HashMap, sync::Arc};
+
+    // compares a passive segment, copied to an unaligned address, with an active copy of it
+    let bytes: String = (1..=19).map(|x| format!("\\{x:02x}")).collect();
+    let wasm = as_wasm(&format!(
+        r#"
+        (module
+            (memory 1 1)
+            (data $bytes "{bytes}")
+            (data (i32.const 0x200) "{bytes}")
+            (func (export "test") (param $source i32) (param $size i32) (result i32)
+                (local $i i32)
+                (memory.init $bytes (i32.const 0x101) (local.get $source) (local.get $size))
+                (loop $next
+                    (if (i32.ne
+                            (i32.load8_u (i32.add (i32.const 0x101) (local.get $i)))
+                            (select
+                                (i32.load8_u (i32.add (i32.const 0x200) (i32.add (local.get $source) (local.get $i))))
+                                (i32.const 0)
+                                (i32.lt_u (local.get $i) (local.get $size))))
+                        (then (return (i32.const 0))))
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br_if $next (i32.lt_u (local.get $i) (i32.const 24)))
+                )
+                (i32.const 1)
+            )
+        )"#
+    ));
+    let machine = |source: u32, size: u32| -> Result<Machine> {
+        let bin = binary::parse(&wasm, Path::new("user"))?;
+        let mut mach = Machine::from_binaries(
+            &[],
+            bin,
+            false,
+            false,
+            true,
+            false,
+            false,
+            GlobalState::default(),
+            HashMap::default(),
+            Arc::new(|_, _| panic!("tried to read preimage")),
+            None,
+        )?;
+        let (module, func) = mach.find_module_func("user", "test")?;
+        let args = vec![Value::I32(source), Value::I32(size)];
+        mach.jump_into_func(module, func, args)?;
+        Ok(mach)
+    };
+
+    for source in 0..=19 {
+        for size in 0..=(19 - source) {
+            let mut mach = machine(source, size)?;
+            mach.step_n(u64::MAX)?;
+            assert_eq!(mach.get_final_result()?, vec![Value::I32(1)]);
+        }
+    }
+
+    // copying past the segment's end traps
+    let mut mach = machine(12, 8)?;
+    mach.step_n(u64::MAX)?;
+    assert_eq!(mach.get_status(), MachineStatus::Errored);
+
+    // the data is held in code, so each step is provable
+    let mut mach = machine(3, 13)?;
+    while !mach.is_halted() {
+        let context = ExecutionContext::new(&mach);
+        let before = mach.hash();
+        let proof = mach.serialize_proof();
+        mach.step_n(1)?;
+        assert_eq!(verify::verify(&context, before, &proof)?, mach.hash());
+    }
+    assert_eq!(mach.get_final_result()?, vec![Value::I32(1)]);
+    Ok(())
+}
+
+#[test]
+pub fn test_extended_const() -> Result<()> {
+    use crate::{programs::config::CompileConfig, value::Value};
+    use std::borrow::Cow;
+
+    let wasm = as_wasm(
+        r#"
+        (module
+            (memory (export "memory") 1 1)
+            (global $base i32 (i32.const 0x100))
+            (global $data i32 (i32.add (i32.const 0x100) (i32.const 0x20)))
+            (global $wide i64 (i64.mul (i64.const -3) (i64.const 7)))
+            (data (i32.sub (i32.const 0x120) (i32.const 1)) "\aa")
+            (func (export "user_entrypoint") (param i32) (result i32)
+                i32.const 0
+            )
+        )"#,
+    );
+    let bin = binary::parse(&wasm, Path::new(""))?;
+    let globals = [
+        Value::I32(0x100),
+        Value::I32(0x120),
+        Value::I64(-21_i64 as u64),
+    ];
+    assert_eq!(bin.globals, globals);
+    assert!(bin.extended_consts);
+
+    // folding leaves a single constant in each expression
+    let folded = binary::fold_const_exprs(&wasm)?;
+    assert!(matches!(folded, Cow::Owned(_)));
+    assert!(folded.len() < wasm.len());
+
+    let bin = binary::parse(&folded, Path::new(""))?;
+    assert_eq!(bin.globals, globals);
+    assert!(!bin.extended_consts);
+
+    let folded = folded.into_owned();
+    assert!(matches!(
+        binary::fold_const_exprs(&folded)?,
+        Cow::Borrowed(_)
+    ));
+
+    // only version 2 programs may use extended constants
+    let mut bin = binary::parse(&wasm, Path::new(""))?;
+    assert!(bin.instrument(&CompileConfig::version(1, false)).is_err());
+    bin.instrument(&CompileConfig::version(2, false))?;
+    Ok(())
+}
+
+#[test]
+pub fn test_checkpoints() -> Result<()> {
+    use crate::{
+        checkpoint::CheckpointedMachine,
+        machine::{GlobalState, Machine},
+        value::Value,
+    };
+    use std::{collections::HashMap, sync::Arc};
+
+    // sums numbers as it writes memory and a global, exercising state beyond the stacks
+    let wasm = as_wasm(
+        r#"
+        (module
+            (memory 1 1)
+            (global $last (mut i32) (i32.const 0))
+            (func (export "test") (param $n i32) (result i32)
+                (local $sum i32)
+                (loop $next
+                    (global.set $last (local.get $n))
+                    (i32.store (i32.shl (local.get $n) (i32.const 2)) (local.get $sum))
+                    (local.set $sum (i32.add (local.get $sum) (local.get $n)))
+                    (local.tee $n (i32.sub (local.get $n) (i32.const 1)))
+                    (br_if $next)
+                )
+                (local.get $sum)
+            )
+        )"#,
+    );
+    let machine = || -> Result<Machine> {
+        let bin = binary::parse(&wasm, Path::new("user"))?;
+        let mut mach = Machine::from_binaries(
+            &[],
+            bin,
+            false,
+            false,
+            true,
+            false,
+            false,
+            GlobalState::default(),
+            HashMap::default(),
+            Arc::new(|_, _| panic!("tried to read preimage")),
+            None,
+        )?;
+        let (module, func) = mach.find_module_func("user", "test")?;
+        mach.jump_into_func(module, func, vec![Value::I32(20)])?;
+        Ok(mach)
+    };
+
+    // step through once for reference
+    let mut reference = machine()?;
+    let mut expected = vec![];
+    while !reference.is_halted() {
+        expected.push((reference.hash(), reference.serialize_proof()));
+        reference.step_n(1)?;
+    }
+    let halted = (reference.hash(), reference.serialize_proof());
+    assert_eq!(reference.get_final_result()?, vec![Value::I32(210)]);
+
+    // query out of order, as bisection does
+    let mut checkpointed = CheckpointedMachine::new(machine()?, 16, 64)?;
+    let last = expected.len() as u64;
+    for step in [last / 2, last / 4, 3 * last / 8, 0, last - 1, 1, 17, 16, 15] {
+        let (hash, proof) = &expected[step as usize];
+        assert_eq!(checkpointed.hash_at(step)?, *hash);
+        assert_eq!(checkpointed.proof_at(step)?, *proof);
+    }
+    assert!(checkpointed.checkpoints().all(|step| step % 16 == 0));
+
+    // past the end, the machine stays halted
+    assert_eq!(checkpointed.hash_at(last + 100)?, halted.0);
+    assert_eq!(checkpointed.proof_at(last + 100)?, halted.1);
+    assert_eq!(checkpointed.hash_at(3)?, expected[3].0);
+
+    // with too many snapshots, they're thinned to stay evenly spread
+    let mut thinned = CheckpointedMachine::new(machine()?, 1, 4)?;
+    assert_eq!(thinned.hash_at(last)?, halted.0);
+    let checkpoints: Vec<_> = thinned.checkpoints().collect();
+    let interval = checkpoints[1];
+    assert!(checkpoints.len() <= 4);
+    assert!(checkpoints.iter().all(|step| step % interval == 0));
+    assert!(interval.is_power_of_two() && interval * 4 >= last);
+    for step in [last - 1, 5, 0, interval + 1] {
+        assert_eq!(thinned.proof_at(step)?, expected[step as usize].1);
+    }
+    assert!(thinned.checkpoints().count() <= 4);
+    Ok(())
+}
+
+#[test]
+pub fn test_merkle_copy_paths() {
+    use crate::merkle::{Merkle, MerkleType};
+    use arbutil::Bytes32;
+
+    let leaf = |x: u8| Bytes32::from([x; 32]);
+    let empty = Bytes32::default();
+    let tree =
+        |leaves: &[Bytes32]| Merkle::new_advanced(MerkleType::Memory, leaves.to_vec(), empty, 6);
+
+    // the other tree has changed leaves, and has grown with both empty and nonempty leaves
+    let mut prior = tree(&[leaf(1), leaf(2), leaf(3), leaf(4), leaf(5)]);
+    let other = tree(&[
+        leaf(1),
+        leaf(9),
+        leaf(3),
+        leaf(4),
+        leaf(5),
+        empty,
+        leaf(7),
+        empty,
+    ]);
+    let dirty = other.diff(&prior);
+    assert_eq!(dirty, vec![1, 6]);
+
+    prior.copy_paths(&other, &dirty);
+    assert_eq!(prior, other);
+}
+
+#[test]
+pub fn test_snapshots() -> Result<()> {
+    use crate::{
+        machine::{GlobalState, Machine},
+        snapshot::{self, SnapshotWriter},
+        value::Value,
+    };
+    use std::{collections::HashMap, sync::Arc};
+
+    // grows memory, then scatters writes across it
+    let wat = r#"
+        (module
+            (memory 1 4)
+            (func (export "test") (param $n i32) (result i32)
+                (local $sum i32)
+                (drop (memory.grow (i32.const 1)))
+                (loop $next
+                    (i32.store (i32.mul (local.get $n) (i32.const 4099)) (local.get $sum))
+                    (local.set $sum (i32.add (local.get $sum) (local.get $n)))
+                    (local.tee $n (i32.sub (local.get $n) (i32.const 1)))
+                    (br_if $next)
+                )
+                (local.get $sum)
+            )
+        )"#;
+    let machine = |wat: &str, n: u32| -> Result<Machine> {
+        let wasm = as_wasm(wat);
+        let bin = binary::parse(&wasm, Path::new("user"))?;
+        let mut mach = Machine::from_binaries(
+            &[],
+            bin,
+            false,
+            false,
+            true,
+            false,
+            false,
+            GlobalState::default(),
+            HashMap::default(),
+            Arc::new(|_, _| panic!("tried to read preimage")),
+            None,
+        )?;
+        let (module, func) = mach.find_module_func("user", "test")?;
+        mach.jump_into_func(module, func, vec![Value::I32(n)])?;
+        Ok(mach)
+    };
+
+    let mut mach = machine(wat, 20)?;
+    let mut writer = SnapshotWriter::new(&mach);
+    let mut chain = vec![];
+    let mut states = vec![];
+    while !mach.is_halted() {
+        chain.push(writer.snapshot(&mach)?);
+        states.push((mach.get_steps(), mach.hash()));
+        mach.step_n(10)?;
+    }
+    chain.push(writer.snapshot(&mach)?);
+    states.push((mach.get_steps(), mach.hash()));
+
+    // each prefix of the chain restores the machine to that point
+    let mut restored = machine(wat, 20)?;
+    for (i, (steps, hash)) in states.iter().enumerate() {
+        snapshot::restore(&mut restored, &chain[..=i])?;
+        assert_eq!(restored.get_steps(), *steps);
+        assert_eq!(restored.hash(), *hash);
+    }
+
+    // a restored machine runs on as the original did
+    snapshot::restore(&mut restored, &chain[..5])?;
+    while !restored.is_halted() {
+        restored.step_n(1)?;
+    }
+    assert_eq!(restored.hash(), mach.hash());
+    assert_eq!(restored.get_final_result()?, vec![Value::I32(210)]);
+
+    // chains must be unbroken and of the same machine
+    let skipped = [&chain[0], &chain[2]];
+    assert!(snapshot::restore(&mut restored, &skipped).is_err());
+    assert!(snapshot::restore(&mut restored, &chain[1..]).is_err());
+    let mut other = machine(&wat.replace("4099", "4097"), 20)?;
+    assert!(snapshot::restore(&mut other, &chain).is_err());
+    assert_eq!(restored.hash(), mach.hash());
+    Ok(())
+}
+
+#[test]
+pub fn test_verify() -> Result<()> {
+    use crate::{
+        machine::{GlobalState, Machine},
+        value::Value,
+        verify::{self, ExecutionContext},
+    };
+    use std::{collections::HashMap, sync::Arc};
+
+    // mixes memory, tables, and calls, storing across leaf boundaries
+    let wasm = as_wasm(
+        r#"
+        (module
+            (memory 1 2)
+            (type $unary (func (param i32) (result i32)))
+            (table 4 funcref)
+            (elem (i32.const 0) func $inc $double $double)
+            (func $inc (param i32) (result i32) (i32.add (local.get 0) (i32.const 1)))
+            (func $double (param i32) (result i32) (i32.mul (local.get 0) (i32.const 2)))
+            (func (export "test") (param $n i32) (result i32)
+                (local $acc i32)
+                (drop (memory.grow (i32.const 1)))
+                (loop $next
+                    (local.set $acc (call_indirect (type $unary)
+                        (local.get $acc)
+                        (select (i32.const 0) (i32.const 2) (i32.and (local.get $n) (i32.const 1)))))
+                    (i64.store (i32.add (i32.mul (local.get $n) (i32.const 4099)) (i32.const 30))
+                        (i64.extend_i32_u (local.get $acc)))
+                    (local.tee $n (i32.sub (local.get $n) (i32.const 1)))
+                    (br_if $next)
+                )
+                (i32.wrap_i64 (i64.load (i32.const 4129)))
+            )
+        )"#,
+    );
+    let bin = binary::parse(&wasm, Path::new("user"))?;
+    let mut mach = Machine::from_binaries(
+        &[],
+        bin,
+        false,
+        false,
+        true,
+        false,
+        false,
+        GlobalState::default(),
+        HashMap::default(),
+        Arc::new(|_, _| panic!("tried to read preimage")),
+        None,
+    )?;
+    let (module, func) = mach.find_module_func("user", "test")?;
+    mach.jump_into_func(module, func, vec![Value::I32(8)])?;
+
+    loop {
+        let context = ExecutionContext::new(&mach);
+        let before = mach.hash();
+        let proof = mach.serialize_proof();
+        mach.step_n(1)?;
+        assert_eq!(verify::verify(&context, before, &proof)?, mach.hash());
+
+        // proofs must be of the claimed machine
+        let mut wrong = before;
+        wrong[0] ^= 1;
+        assert!(verify::verify(&context, wrong, &proof).is_err());
+
+        if before == mach.hash() {
+            break;
+        }
+
+        // as on-chain, only halted proofs may stop short
+        assert!(verify::verify(&context, before, &proof[..proof.len() - 1]).is_err());
+    }
+    assert_eq!(mach.get_final_result()?, vec![Value::I32(15)]);
+    Ok(())
+}
+
+#[test]
+pub fn test_opcode_reprs() {
+    use crate::wavm::Opcode;
+
+    for repr in 0..=u16::MAX {
+        if let Some(opcode) = Opcode::from_repr(repr) {
+            assert_eq!(opcode.repr(), repr, "{opcode:?}");
+        }
+    }
+    assert_eq!(Opcode::from_repr(0x8002), Some(Opcode::InitFrame));
+    assert_eq!(Opcode::from_repr(0x8001), None);
+}
+
+#[test]
+pub fn test_explain_proof() -> Result<()> {
+    use crate::{
+        explain::{self, ProofPart},
+        machine::{GlobalState, Machine},
+        value::Value,
+        wavm::Opcode,
+    };
+    use std::{collections::HashMap, sync::Arc};
+
+    let wasm = as_wasm(
+        r#"
+        (module
+            (memory 1 1)
+            (global $count (mut i32) (i32.const 0))
+            (func (export "test") (param $n i32) (result i32)
+                (i32.store (i32.const 30) (local.get $n))
+                (global.set $count (i32.load (i32.const 30)))
+                (global.get $count)
+            )
+        )"#,
+    );
+    let bin = binary::parse(&wasm, Path::new("user"))?;
+    let mut mach = Machine::from_binaries(
+        &[],
+        bin,
+        false,
+        false,
+        true,
+        false,
+        false,
+        GlobalState::default(),
+        HashMap::default(),
+        Arc::new(|_, _| panic!("tried to read preimage")),
+        None,
+    )?;
+    let (module, func) = mach.find_module_func("user", "test")?;
+    mach.jump_into_func(module, func, vec![Value::I32(7)])?;
+
+    let mut opcodes = vec![];
+    while !mach.is_halted() {
+        let inst = mach.get_next_instruction().unwrap();
+        let explained = explain::explain(&mach.serialize_proof())?;
+        let step = explained.step.as_ref().unwrap();
+        assert_eq!(step.inst.opcode, inst.opcode);
+        assert_eq!(explained.machine.pc, mach.get_pc().unwrap());
+
+        // stores and loads prove both leaves they might touch
+        let leaves = step.extra.iter().filter_map(|part| match part {
+            ProofPart::MemoryLeaf(leaf) => Some(leaf.index),
+            _ => None,
+        });
+        match inst.opcode {
+            Opcode::MemoryLoad { .. } | Opcode::MemoryStore { .. } => {
+                assert_eq!(leaves.collect::<Vec<_>>(), vec![0, 1])
+            }
+            Opcode::GlobalSet => {
+                let global = &step.extra[0];
+                assert!(matches!(global, ProofPart::Global { index: 0, .. }));
+            }
+            _ => {}
+        }
+        assert!(!format!("{explained}").is_empty());
+        opcodes.push(inst.opcode);
+        mach.step_n(1)?;
+    }
+    assert!(opcodes.contains(&Opcode::GlobalGet));
+
+    // halted proofs end with the module
+    let explained = explain::explain(&mach.serialize_proof())?;
+    assert!(explained.step.is_none());
+
+    let mut proof = mach.serialize_proof();
+    proof.push(0);
+    assert!(explain::explain(&proof).is_err());
+    Ok(())
+}
+
+#[test]
+pub fn test_debugger() -> Result<()> {
+    use crate::{
+        debugger::{Breakpoint, Debugger, Stop},
+        machine::{GlobalState, Machine},
+        value::Value,
+        wavm::Opcode,
+    };
+    use std::{collections::HashMap, sync::Arc};
+
+    let wasm = as_wasm(
+        r#"
+        (module
+            (memory 1 1)
+            (func $inc (param i32) (result i32)
+                (i32.store (i32.const 8) (local.get 0))
+                (i32.add (local.get 0) (i32.const 1)))
+            (func (export "test") (param $n i32) (result i32)
+                (local $acc i32)
+                (loop $next
+                    (local.set $acc (call $inc (local.get $acc)))
+                    (local.tee $n (i32.sub (local.get $n) (i32.const 1)))
+                    (br_if $next)
+                )
+                (local.get $acc)
+            )
+            (func $nop)
+        )"#,
+    );
+    let bin = binary::parse(&wasm, Path::new("user"))?;
+    let mut mach = Machine::from_binaries(
+        &[],
+        bin,
+        false,
+        false,
+        true,
+        false,
+        false,
+        GlobalState::default(),
+        HashMap::default(),
+        Arc::new(|_, _| panic!("tried to read preimage")),
+        None,
+    )?;
+    let (module, func) = mach.find_module_func("user", "test")?;
+    mach.jump_into_func(module, func, vec![Value::I32(3)])?;
+    let mut debugger = Debugger::new(mach, 16, 64)?;
+
+    // stop on entering inc, then return from it
+    debugger.add_breakpoint(Breakpoint::Func("inc".into()));
+    assert_eq!(debugger.resume()?, Stop::Breakpoint(0));
+    let entry = debugger.machine().get_pc().unwrap();
+    assert_eq!(entry.inst, 0);
+    assert_eq!(debugger.step(1)?, Stop::Done);
+    assert_eq!(debugger.machine().get_locals()[0], Value::I32(0));
+    assert_eq!(debugger.finish()?, Stop::Done);
+    let after = debugger.machine().get_pc().unwrap();
+    assert_ne!(after.func, entry.func);
+    assert_eq!(debugger.machine().get_call_depth(), 1);
+
+    // the call is stepped over, stopping at the breakpoint within
+    let calls = |debugger: &Debugger| {
+        let inst = debugger.machine().get_next_instruction();
+        inst.map_or(false, |x| x.opcode == Opcode::Call)
+    };
+    while !calls(&debugger) {
+        assert_eq!(debugger.step(1)?, Stop::Done);
+    }
+    assert_eq!(debugger.step_over()?, Stop::Breakpoint(0));
+    assert_eq!(
+        debugger.remove_breakpoint(0),
+        Some(Breakpoint::Func("inc".into()))
+    );
+    assert_eq!(debugger.step_over()?, Stop::Done);
+    assert_eq!(debugger.machine().get_call_depth(), 2);
+
+    // rewinding returns to the last stop
+    let steps = debugger.machine().get_steps();
+    let hash = debugger.machine().hash();
+    assert_eq!(debugger.step(5)?, Stop::Done);
+    assert_eq!(debugger.back()?, steps);
+    assert_eq!(debugger.machine().hash(), hash);
+
+    // opcode breakpoints ignore the opcode's fields
+    let store = Opcode::MemoryStore {
+        ty: ArbValueType::I64,
+        bytes: 8,
+    };
+    debugger.add_breakpoint(Breakpoint::Opcode(store));
+    assert_eq!(debugger.resume()?, Stop::Breakpoint(0));
+    assert_eq!(debugger.step(1)?, Stop::Done);
+    let memory = debugger.machine().read_memory(entry.module, 8, 4)?;
+    assert_eq!(memory, 1_u32.to_le_bytes());
+
+    debugger.remove_breakpoint(0);
+    assert_eq!(debugger.resume()?, Stop::Halted);
+    assert_eq!(debugger.step(1)?, Stop::Halted);
+
+    // moving back past several stops replays from the nearest checkpoint
+    let halted = debugger.machine().get_steps();
+    debugger.goto(steps)?;
+    assert_eq!(debugger.machine().hash(), hash);
+    debugger.goto(halted)?;
+    assert!(debugger.machine().is_halted());
+
+    // names shared by a function and an opcode must be qualified
+    debugger.repl("break nop\nbreak func nop\nbreak op nop\n".as_bytes())?;
+    let breakpoints = [
+        Breakpoint::Func("nop".into()),
+        Breakpoint::Opcode(Opcode::Nop),
+    ];
+    assert_eq!(debugger.breakpoints(), breakpoints);
+    Ok(())
+}
@@ -50,6 +50,24 @@ impl TryFrom<ValType> for ArbValueType {
     }
 }
 
+impl TryFrom<u8> for ArbValueType {
+    type Error = eyre::Error;
+
+    fn try_from(ty: u8) -> Result<ArbValueType> {
+        use ArbValueType::*;
+        Ok(match ty {
+            0 => I32,
+            1 => I64,
+            2 => F32,
+            3 => F64,
+            4 => RefNull,
+            5 => FuncRef,
+            6 => InternalRef,
+            x => bail!("unknown value type {x}"),
+        })
+    }
+}
+
 impl From<ArbValueType> for ValType {
     fn from(ty: ArbValueType) -> Self {
         use ArbValueType as V;
@@ -136,6 +154,19 @@ impl ProgramCounter {
         Bytes32(b)
     }
 
+    /// Inverts `serialize`, rejecting data with bytes set outside the counter.
+    pub fn deserialize(data: Bytes32) -> Result<Self> {
+        if data[..20].iter().any(|x| *x != 0) {
+            bail!("invalid program counter {data}");
+        }
+        let word = |i: usize| u32::from_be_bytes(data[i..i + 4].try_into().unwrap());
+        Ok(Self {
+            module: word(20),
+            func: word(24),
+            inst: word(28),
+        })
+    }
+
     // These casts are safe because we checked above that a usize is at least as big as a u32
 
     pub fn module(self) -> usize {
@@ -219,6 +250,27 @@ impl Value {
         ret
     }
 
+    /// Inverts `serialize_for_proof`, rejecting contents too wide for the value's type.
+    pub fn deserialize_for_proof(data: [u8; 33]) -> Result<Self> {
+        let ty = ArbValueType::try_from(data[0])?;
+        let contents = Bytes32::try_from(&data[1..])?;
+        let word = |bytes: usize| -> Result<u64> {
+            if contents[..32 - bytes].iter().any(|x| *x != 0) {
+                bail!("invalid {ty} contents {contents}");
+            }
+            Ok(u64::from_be_bytes(contents[24..].try_into().unwrap()))
+        };
+        Ok(match ty {
+            ArbValueType::I32 => Value::I32(word(4)? as u32),
+            ArbValueType::I64 => Value::I64(word(8)?),
+            ArbValueType::F32 => Value::F32(f32::from_bits(word(4)? as u32)),
+            ArbValueType::F64 => Value::F64(f64::from_bits(word(8)?)),
+            ArbValueType::RefNull => word(0).map(|_| Value::RefNull)?,
+            ArbValueType::FuncRef => Value::FuncRef(word(4)? as u32),
+            ArbValueType::InternalRef => Value::InternalRef(ProgramCounter::deserialize(contents)?),
+        })
+    }
+
     pub fn is_i32_zero(self) -> bool {
         match self {
             Value::I32(0) => true,
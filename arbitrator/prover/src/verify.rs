@@ -0,0 +1,1313 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+//! Checks one-step proofs without an EVM, mirroring the on-chain `OneStepProver*` contracts.
+//!
+//! A proof reveals just enough of a machine to execute its next instruction: the tops of its
+//! stacks, its current module, and whichever locals, globals, table elements, and memory leaves
+//! the instruction touches, each alongside a merkle proof. Verifying a proof means checking that
+//! this partial machine hashes to the before-hash, executing the instruction on it, and rehashing.
+
+use crate::{
+    machine::{
+        argument_data_to_inbox, exec_ibin_op, exec_irel_op, exec_iun_op, GlobalState,
+        InboxIdentifier, Machine, MachineStatus, ThreadState, GLOBAL_STATE_BYTES32_NUM,
+        GLOBAL_STATE_U64_NUM,
+    },
+    memory::Memory,
+    merkle::{hash_node, MerkleType},
+    value::{ArbValueType, IntegerValType, ProgramCounter, Value},
+    wavm::{self, IBinOpType, Instruction, Opcode},
+};
+use arbutil::{crypto, math, Bytes32};
+use digest::Digest;
+use eyre::{bail, ensure, eyre, Result};
+use sha3::Keccak256;
+
+const LEAF_SIZE: usize = Memory::LEAF_SIZE;
+
+/// What a proof commits to without revealing, akin to the on-chain prover's execution context.
+#[derive(Clone, Debug)]
+pub struct ExecutionContext {
+    /// The instruction being proven, of which the proof only includes the opcode's repr and hash
+    /// of the argument. `None` once the machine has halted.
+    pub inst: Option<Instruction>,
+    /// The first sequencer message beyond the inbox, reading which leaves the machine too far.
+    pub first_too_far: u64,
+}
+
+impl ExecutionContext {
+    /// The context of the machine's next step.
+    pub fn new(machine: &Machine) -> Self {
+        Self {
+            inst: machine.get_next_instruction(),
+            first_too_far: machine.get_first_too_far(),
+        }
+    }
+}
+
+/// Verifies a proof of executing a single step from the machine with the given hash, returning
+/// the hash of the machine after it.
+pub fn verify(context: &ExecutionContext, before: Bytes32, proof: &[u8]) -> Result<Bytes32> {
+    let mut proof = Reader::new(proof);
+    let mut mach = ProvenMachine::read(&mut proof)?;
+    ensure!(
+        mach.hash() == before,
+        "proof is of machine {} rather than {before}",
+        mach.hash(),
+    );
+    if mach.status != MachineStatus::Running {
+        return Ok(before);
+    }
+
+    let module = mach.module.hash();
+    let module_proof = proof.merkle_proof()?;
+    let modules_root = merkle_root(MerkleType::Module, mach.pc.module(), module, &module_proof)?;
+    ensure!(modules_root == mach.modules_root, "module not in machine");
+
+    let Some(inst) = context.inst else {
+        bail!("no instruction to execute");
+    };
+    let inst_data: [u8; 34] = proof.bytes()?;
+    ensure!(
+        inst.serialize_for_proof() == inst_data,
+        "instruction {:?} isn't the one proven",
+        inst.opcode,
+    );
+    let mut h = Keccak256::new();
+    h.update("Instruction:");
+    h.update(inst_data);
+    let inst_proof = proof.merkle_proof()?;
+    let code_root = merkle_root(
+        MerkleType::Instruction,
+        mach.pc.inst(),
+        h.finalize().into(),
+        &inst_proof,
+    )?;
+
+    let mut h = Keccak256::new();
+    h.update("Function:");
+    h.update(code_root);
+    let func_proof = proof.merkle_proof()?;
+    let funcs_root = merkle_root(
+        MerkleType::Function,
+        mach.pc.func(),
+        h.finalize().into(),
+        &func_proof,
+    )?;
+    ensure!(
+        funcs_root == mach.module.funcs_root,
+        "instruction not in module"
+    );
+
+    let module_index = mach.pc.module();
+    mach.step(inst, context, &mut proof)?;
+
+    if mach.module.hash() != module {
+        let module = mach.module.hash();
+        mach.modules_root = merkle_root(MerkleType::Module, module_index, module, &module_proof)?;
+    }
+    Ok(mach.hash())
+}
+
+/// Reads a proof front to back.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N]> {
+        ensure!(self.data.len() >= N, "proof too short");
+        let (bytes, rest) = self.data.split_at(N);
+        self.data = rest;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.bytes()?))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.bytes()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(self.bytes()?))
+    }
+
+    pub(crate) fn bytes32(&mut self) -> Result<Bytes32> {
+        Ok(self.bytes::<32>()?.into())
+    }
+
+    pub(crate) fn value(&mut self) -> Result<Value> {
+        Value::deserialize_for_proof(self.bytes()?)
+    }
+
+    /// Reads the siblings of a leaf, as produced by `Merkle::prove`.
+    pub(crate) fn merkle_proof(&mut self) -> Result<Vec<Bytes32>> {
+        let count = self.u8()?;
+        (0..count).map(|_| self.bytes32()).collect()
+    }
+
+    pub(crate) fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.data)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+/// Computes the root of a merkle tree from one of its leaves and the leaf's siblings.
+fn merkle_root(ty: MerkleType, index: usize, leaf: Bytes32, proof: &[Bytes32]) -> Result<Bytes32> {
+    let mut index = index;
+    let mut hash = leaf;
+    for sibling in proof {
+        hash = match index & 1 {
+            0 => hash_node(ty, hash, *sibling),
+            _ => hash_node(ty, *sibling, hash),
+        };
+        index >>= 1;
+    }
+    ensure!(index == 0, "merkle proof too short for leaf");
+    Ok(hash)
+}
+
+fn keccak<const N: usize>(prefix: &str, parts: [&[u8]; N]) -> Bytes32 {
+    let mut h = Keccak256::new();
+    h.update(prefix);
+    for part in parts {
+        h.update(part);
+    }
+    h.finalize().into()
+}
+
+/// An item of a stack hashed into the machine.
+pub trait StackItem {
+    const PREFIX: &'static str;
+
+    fn hash(&self) -> Bytes32;
+}
+
+impl StackItem for Value {
+    const PREFIX: &'static str = "Value stack:";
+
+    fn hash(&self) -> Bytes32 {
+        Value::hash(*self)
+    }
+}
+
+/// A stack frame, whose locals are only committed to.
+#[derive(Clone, Copy, Debug)]
+pub struct Frame {
+    pub return_ref: Value,
+    pub locals_root: Bytes32,
+    pub caller_module: u32,
+    pub caller_module_internals: u32,
+}
+
+impl Frame {
+    fn read(proof: &mut Reader) -> Result<Self> {
+        Ok(Self {
+            return_ref: proof.value()?,
+            locals_root: proof.bytes32()?,
+            caller_module: proof.u32()?,
+            caller_module_internals: proof.u32()?,
+        })
+    }
+}
+
+impl StackItem for Frame {
+    const PREFIX: &'static str = "Stack frame stack:";
+
+    fn hash(&self) -> Bytes32 {
+        keccak(
+            "Stack frame:",
+            [
+                &*self.return_ref.hash(),
+                &*self.locals_root,
+                &self.caller_module.to_be_bytes(),
+                &self.caller_module_internals.to_be_bytes(),
+            ],
+        )
+    }
+}
+
+/// The top of a stack, atop the hash of the rest.
+#[derive(Clone, Debug)]
+pub struct Window<T> {
+    pub below: Bytes32,
+    /// The proven items, from the bottom up.
+    pub items: Vec<T>,
+}
+
+impl<T: StackItem> Window<T> {
+    fn hash(&self) -> Bytes32 {
+        let mut hash = self.below;
+        for item in &self.items {
+            hash = keccak(T::PREFIX, [&*item.hash(), &*hash]);
+        }
+        hash
+    }
+
+    fn push(&mut self, item: T) {
+        self.items.push(item);
+    }
+
+    fn pop(&mut self) -> Result<T> {
+        self.items
+            .pop()
+            .ok_or_else(|| eyre!("stack deeper than proven"))
+    }
+
+    fn last_mut(&mut self) -> Result<&mut T> {
+        self.items
+            .last_mut()
+            .ok_or_else(|| eyre!("stack deeper than proven"))
+    }
+}
+
+impl Window<Value> {
+    /// Reads the values atop a stack, as produced by `prove_stack`.
+    fn read(proof: &mut Reader) -> Result<Self> {
+        let below = proof.bytes32()?;
+        let count = proof.bytes32()?;
+        ensure!(count[..31].iter().all(|x| *x == 0), "too many stack values");
+        let items = (0..count[31])
+            .map(|_| proof.value())
+            .collect::<Result<_>>()?;
+        Ok(Self { below, items })
+    }
+}
+
+impl Window<Frame> {
+    /// Reads the frame atop a stack, as produced by `prove_window`.
+    fn read(proof: &mut Reader) -> Result<Self> {
+        let below = proof.bytes32()?;
+        let items = match proof.u8()? {
+            0 => vec![],
+            1 => vec![Frame::read(proof)?],
+            x => bail!("invalid frame window {x}"),
+        };
+        Ok(Self { below, items })
+    }
+}
+
+/// The active stack of a multistack, alongside the hashes of the others.
+#[derive(Clone, Debug)]
+pub struct Multistack<T> {
+    pub active: Window<T>,
+    /// Whether the active stack is the last, in which case the inactive one is the first.
+    pub cothread: bool,
+    pub inactive: Bytes32,
+    /// The hash of the stacks between the first and last.
+    pub rest: Bytes32,
+}
+
+impl<T: StackItem> Multistack<T> {
+    /// Reads a multistack as produced by `prove_multistack`, assuming the main thread is active.
+    fn read(proof: &mut Reader, window: fn(&mut Reader) -> Result<Window<T>>) -> Result<Self> {
+        Ok(Self {
+            active: window(proof)?,
+            cothread: false,
+            inactive: proof.bytes32()?,
+            rest: proof.bytes32()?,
+        })
+    }
+
+    fn hash(&self) -> Bytes32 {
+        let active = self.active.hash();
+        let (first, last) = match self.cothread {
+            true => (self.inactive, active),
+            false => (active, self.inactive),
+        };
+        keccak("multistack:", [&*first, &*last, &*self.rest])
+    }
+
+    /// Adds an empty cothread, which must be done from the main thread.
+    fn push_cothread(&mut self) {
+        assert!(!self.cothread);
+        if self.inactive != Machine::NO_STACK_HASH {
+            self.rest = keccak("cothread:", [&*self.inactive, &*self.rest]);
+        }
+        self.inactive = Bytes32::default();
+    }
+
+    /// Drops the last cothread, which must be done from the main thread, given the hashes of
+    /// the new last stack and those between.
+    fn pop_cothread(&mut self, last: Bytes32, rest: Bytes32) -> Result<()> {
+        assert!(!self.cothread);
+        ensure!(
+            self.inactive != Machine::NO_STACK_HASH,
+            "no cothread to pop"
+        );
+        let prior = match last == Machine::NO_STACK_HASH {
+            true => rest,
+            false => keccak("cothread:", [&*last, &*rest]),
+        };
+        ensure!(prior == self.rest, "cothreads don't match the proof");
+        self.inactive = last;
+        self.rest = rest;
+        Ok(())
+    }
+}
+
+/// A module, of which a proof reveals only the roots of its parts.
+#[derive(Clone, Debug)]
+pub struct ModuleProof {
+    pub globals_root: Bytes32,
+    pub memory_size: u64,
+    pub memory_max_size: u64,
+    pub memory_root: Bytes32,
+    pub tables_root: Bytes32,
+    pub funcs_root: Bytes32,
+    pub internals_offset: u32,
+}
+
+impl ModuleProof {
+    pub(crate) fn read(proof: &mut Reader) -> Result<Self> {
+        Ok(Self {
+            globals_root: proof.bytes32()?,
+            memory_size: proof.u64()?,
+            memory_max_size: proof.u64()?,
+            memory_root: proof.bytes32()?,
+            tables_root: proof.bytes32()?,
+            funcs_root: proof.bytes32()?,
+            internals_offset: proof.u32()?,
+        })
+    }
+
+    fn hash(&self) -> Bytes32 {
+        let memory = keccak(
+            "Memory:",
+            [
+                &self.memory_size.to_be_bytes(),
+                &self.memory_max_size.to_be_bytes(),
+                &*self.memory_root,
+            ],
+        );
+        keccak(
+            "Module:",
+            [
+                &*self.globals_root,
+                &*memory,
+                &*self.tables_root,
+                &*self.funcs_root,
+                &self.internals_offset.to_be_bytes(),
+            ],
+        )
+    }
+
+    /// Whether the bytes at `[offset, offset + len)` are in bounds.
+    fn in_bounds(&self, offset: u64, len: u64) -> bool {
+        offset
+            .checked_add(len)
+            .map_or(false, |end| end <= self.memory_size)
+    }
+}
+
+/// A leaf of memory and its siblings.
+#[derive(Clone, Debug)]
+pub struct MemoryLeaf {
+    pub index: usize,
+    pub data: [u8; LEAF_SIZE],
+    /// Empty for leaves beyond the tree, which memory grown that far would deepen.
+    pub proof: Vec<Bytes32>,
+}
+
+impl MemoryLeaf {
+    /// Reads a leaf of memory, whose siblings are only proven for leaves within the tree.
+    pub(crate) fn read(proof: &mut Reader, index: usize, memory_size: u64) -> Result<Self> {
+        let data = proof.bytes()?;
+        let size = memory_size as usize;
+        let leaves = ((size + LEAF_SIZE - 1) / LEAF_SIZE)
+            .max(1)
+            .next_power_of_two();
+        let proof = match index < leaves {
+            true => proof.merkle_proof()?,
+            false => vec![],
+        };
+        Ok(Self { index, data, proof })
+    }
+
+    fn root(&self) -> Result<Bytes32> {
+        let leaf = keccak("Memory leaf:", [&self.data]);
+        merkle_root(MerkleType::Memory, self.index, leaf, &self.proof)
+    }
+}
+
+/// A table, whose elements are only committed to, and its siblings.
+#[derive(Clone, Debug)]
+pub struct TableProof {
+    pub index: usize,
+    pub ty: u8,
+    pub len: u64,
+    pub root: Bytes32,
+    pub proof: Vec<Bytes32>,
+}
+
+impl TableProof {
+    pub(crate) fn read(proof: &mut Reader, index: usize) -> Result<Self> {
+        Ok(Self {
+            index,
+            ty: proof.u8()?,
+            len: proof.u64()?,
+            root: proof.bytes32()?,
+            proof: proof.merkle_proof()?,
+        })
+    }
+
+    fn hash(&self) -> Bytes32 {
+        keccak("Table:", [&[self.ty], &self.len.to_be_bytes(), &*self.root])
+    }
+}
+
+/// An element of a table and its siblings.
+#[derive(Clone, Debug)]
+pub struct ElementProof {
+    pub index: usize,
+    /// The hash of the function type, or of the empty type for null refs.
+    pub func_ty: Bytes32,
+    pub val: Value,
+    pub proof: Vec<Bytes32>,
+}
+
+impl ElementProof {
+    pub(crate) fn read(proof: &mut Reader, index: usize) -> Result<Self> {
+        Ok(Self {
+            index,
+            func_ty: proof.bytes32()?,
+            val: proof.value()?,
+            proof: proof.merkle_proof()?,
+        })
+    }
+}
+
+/// The last linked module, with the proof of the empty leaf after it when the tree isn't full.
+#[derive(Clone, Debug)]
+pub struct LastModule {
+    pub module: ModuleProof,
+    pub index: usize,
+    pub siblings: Vec<Bytes32>,
+    pub next_proof: Option<Vec<Bytes32>>,
+}
+
+impl LastModule {
+    pub(crate) fn read(proof: &mut Reader) -> Result<Self> {
+        let module = ModuleProof::read(proof)?;
+        let index = proof.u32()? as usize;
+        let siblings = proof.merkle_proof()?;
+        let next_proof = match math::is_power_of_2(index + 1) {
+            true => None,
+            false => Some(proof.merkle_proof()?),
+        };
+        Ok(Self {
+            module,
+            index,
+            siblings,
+            next_proof,
+        })
+    }
+}
+
+pub(crate) fn read_global_state(proof: &mut Reader) -> Result<GlobalState> {
+    let mut state = GlobalState::default();
+    for item in &mut state.bytes32_vals {
+        *item = proof.bytes32()?;
+    }
+    for item in &mut state.u64_vals {
+        *item = proof.u64()?;
+    }
+    Ok(state)
+}
+
+fn hash_element(func_ty: Bytes32, val: Value) -> Bytes32 {
+    keccak("Table element:", [&*func_ty, &*val.hash()])
+}
+
+/// The parts of a machine a proof reveals.
+#[derive(Clone, Debug)]
+pub struct ProvenMachine {
+    pub status: MachineStatus,
+    pub values: Multistack<Value>,
+    pub internals: Window<Value>,
+    pub frames: Multistack<Frame>,
+    pub global_state: Bytes32,
+    pub pc: ProgramCounter,
+    pub thread_state: ThreadState,
+    pub modules_root: Bytes32,
+    /// The module at the pc, as of the start of the step.
+    pub module: ModuleProof,
+}
+
+impl ProvenMachine {
+    /// Reads the machine as produced by the start of `serialize_proof`.
+    pub(crate) fn read(proof: &mut Reader) -> Result<Self> {
+        let status = match proof.u8()? {
+            0 => MachineStatus::Running,
+            1 => MachineStatus::Finished,
+            2 => MachineStatus::Errored,
+            3 => MachineStatus::TooFar,
+            x => bail!("unknown machine status {x}"),
+        };
+
+        let mut values = Multistack::read(proof, Window::<Value>::read)?;
+        let internals = Window::<Value>::read(proof)?;
+        let mut frames = Multistack::read(proof, Window::<Frame>::read)?;
+        let global_state = proof.bytes32()?;
+        let pc = ProgramCounter {
+            module: proof.u32()?,
+            func: proof.u32()?,
+            inst: proof.u32()?,
+        };
+        let thread_state = match proof.bytes32()? {
+            data if data == ThreadState::Main.serialize() => ThreadState::Main,
+            data => ThreadState::CoThread(ProgramCounter::deserialize(data)?),
+        };
+        values.cothread = thread_state.is_cothread();
+        frames.cothread = thread_state.is_cothread();
+
+        Ok(Self {
+            status,
+            values,
+            internals,
+            frames,
+            global_state,
+            pc,
+            thread_state,
+            modules_root: proof.bytes32()?,
+            module: ModuleProof::read(proof)?,
+        })
+    }
+
+    fn hash(&self) -> Bytes32 {
+        let mut h = Keccak256::new();
+        match self.status {
+            MachineStatus::Running => {
+                h.update("Machine running:");
+                h.update(self.values.hash());
+                h.update(self.internals.hash());
+                h.update(self.frames.hash());
+                h.update(self.global_state);
+                h.update(self.pc.module.to_be_bytes());
+                h.update(self.pc.func.to_be_bytes());
+                h.update(self.pc.inst.to_be_bytes());
+                h.update(self.thread_state.serialize());
+                h.update(self.modules_root);
+            }
+            MachineStatus::Finished => {
+                h.update("Machine finished:");
+                h.update(self.global_state);
+            }
+            MachineStatus::Errored => h.update("Machine errored:"),
+            MachineStatus::TooFar => h.update("Machine too far:"),
+        }
+        h.finalize().into()
+    }
+
+    fn push(&mut self, value: impl Into<Value>) {
+        self.values.active.push(value.into());
+    }
+
+    fn pop(&mut self) -> Result<Value> {
+        self.values.active.pop()
+    }
+
+    fn pop_u32(&mut self) -> Result<u32> {
+        match self.pop()? {
+            Value::I32(x) => Ok(x),
+            x => bail!("WASM validation failed: expected an i32 but found {x:?}"),
+        }
+    }
+
+    fn pop_u64(&mut self) -> Result<u64> {
+        match self.pop()? {
+            Value::I64(x) => Ok(x),
+            x => bail!("WASM validation failed: expected an i64 but found {x:?}"),
+        }
+    }
+
+    fn frame(&mut self) -> Result<&mut Frame> {
+        self.frames.active.last_mut()
+    }
+
+    /// Pushes the return data for a call that doesn't change the caller module.
+    fn push_call(&mut self) -> Result<()> {
+        let frame = *self.frame()?;
+        self.push(self.pc);
+        self.push(frame.caller_module);
+        self.push(frame.caller_module_internals);
+        Ok(())
+    }
+
+    /// Pushes the return data for a call into another module.
+    fn push_cross_module_call(&mut self) {
+        self.push(self.pc);
+        self.push(self.pc.module);
+        self.push(self.module.internals_offset);
+    }
+
+    /// Halts the machine, unless in a cothread, which instead recovers to the main thread.
+    fn error(&mut self) {
+        match self.thread_state {
+            ThreadState::CoThread(recovery) => {
+                self.thread_state = ThreadState::Main;
+                self.pc = recovery;
+            }
+            ThreadState::Main => self.status = MachineStatus::Errored,
+        }
+    }
+
+    fn read_global_state(&self, proof: &mut Reader) -> Result<GlobalState> {
+        let state = read_global_state(proof)?;
+        ensure!(state.hash() == self.global_state, "wrong global state");
+        Ok(state)
+    }
+
+    fn read_leaf(&self, proof: &mut Reader, index: usize) -> Result<MemoryLeaf> {
+        MemoryLeaf::read(proof, index, self.module.memory_size)
+    }
+
+    /// Reads the leaf at a pointer proven only when aligned.
+    fn read_aligned_leaf(&self, proof: &mut Reader, ptr: u32) -> Result<Option<MemoryLeaf>> {
+        let ptr = ptr as usize;
+        match ptr % LEAF_SIZE {
+            0 => Ok(Some(self.read_leaf(proof, ptr / LEAF_SIZE)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Checks a leaf is in memory, which must be true of any leaf read or written.
+    fn check_leaf(&self, leaf: &MemoryLeaf) -> Result<()> {
+        ensure!(
+            leaf.root()? == self.module.memory_root,
+            "memory leaf {} not in memory",
+            leaf.index,
+        );
+        Ok(())
+    }
+
+    /// The aligned leaf at the pointer, if in bounds.
+    fn load_aligned(&self, leaf: Option<MemoryLeaf>, ptr: u32) -> Result<Option<MemoryLeaf>> {
+        match leaf {
+            Some(leaf) if self.module.in_bounds(ptr.into(), LEAF_SIZE as u64) => {
+                self.check_leaf(&leaf)?;
+                Ok(Some(leaf))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn read_table(&self, proof: &mut Reader, index: u32) -> Result<TableProof> {
+        let index = index as usize;
+        let table = TableProof::read(proof, index)?;
+        let root = merkle_root(MerkleType::Table, index, table.hash(), &table.proof)?;
+        ensure!(
+            root == self.module.tables_root,
+            "table {index} not in module"
+        );
+        Ok(table)
+    }
+
+    /// Reads an element of a table, which is only proven when in bounds.
+    fn read_element(
+        proof: &mut Reader,
+        table: &TableProof,
+        index: u32,
+    ) -> Result<Option<ElementProof>> {
+        if u64::from(index) >= table.len {
+            return Ok(None);
+        }
+        let index = index as usize;
+        let elem = ElementProof::read(proof, index)?;
+        let hash = hash_element(elem.func_ty, elem.val);
+        let root = merkle_root(MerkleType::TableElement, index, hash, &elem.proof)?;
+        ensure!(
+            root == table.root,
+            "element {index} not in table {}",
+            table.index
+        );
+        Ok(Some(elem))
+    }
+
+    /// Reads the module last in the machine, checking that none follow it.
+    fn read_last_module(&self, proof: &mut Reader) -> Result<LastModule> {
+        let last = LastModule::read(proof)?;
+        let (index, siblings) = (last.index, &last.siblings);
+        let root = merkle_root(MerkleType::Module, index, last.module.hash(), siblings)?;
+        ensure!(root == self.modules_root, "module {index} not in machine");
+
+        // the last module either completes the tree or is followed by an empty leaf
+        let next = index + 1;
+        match &last.next_proof {
+            None => ensure!(next == 1 << siblings.len(), "module {index} isn't last"),
+            Some(empty) => {
+                let root = merkle_root(MerkleType::Module, next, Bytes32::default(), empty)?;
+                ensure!(root == self.modules_root, "module {index} isn't last");
+            }
+        }
+        Ok(last)
+    }
+
+    fn step(
+        &mut self,
+        inst: Instruction,
+        context: &ExecutionContext,
+        proof: &mut Reader,
+    ) -> Result<()> {
+        let arg = inst.argument_data;
+        self.pc.inst += 1;
+
+        macro_rules! error {
+            () => {{
+                self.error();
+                return Ok(());
+            }};
+        }
+
+        use Opcode::*;
+        match inst.opcode {
+            Unreachable => error!(),
+            Nop => {}
+            InitFrame => {
+                let caller_module_internals = self.pop_u32()?;
+                let caller_module = self.pop_u32()?;
+                let return_ref = self.pop()?;
+                self.frames.active.push(Frame {
+                    return_ref,
+                    locals_root: inst.get_proving_argument_data(),
+                    caller_module,
+                    caller_module_internals,
+                });
+            }
+            ArbitraryJump => self.pc.inst = arg as u32,
+            ArbitraryJumpIf => {
+                if self.pop_u32()? != 0 {
+                    self.pc.inst = arg as u32;
+                }
+            }
+            Return => match self.frames.active.pop()?.return_ref {
+                Value::RefNull => error!(),
+                Value::InternalRef(pc) => self.pc = pc,
+                v => bail!("attempted to return into an invalid reference: {v:?}"),
+            },
+            Call => {
+                self.push_call()?;
+                self.pc.func = arg as u32;
+                self.pc.inst = 0;
+            }
+            CrossModuleCall | CrossModuleForward => {
+                match inst.opcode {
+                    CrossModuleCall => self.push_cross_module_call(),
+                    _ => self.push_call()?,
+                }
+                let (module, func) = wavm::unpack_cross_module_call(arg);
+                self.pc = ProgramCounter {
+                    module,
+                    func,
+                    inst: 0,
+                };
+            }
+            CrossModuleInternalCall => {
+                let call_module = self.pop_u32()?;
+                self.push_cross_module_call();
+
+                let module = ModuleProof::read(proof)?;
+                let siblings = proof.merkle_proof()?;
+                let root = merkle_root(
+                    MerkleType::Module,
+                    call_module as usize,
+                    module.hash(),
+                    &siblings,
+                )?;
+                ensure!(
+                    root == self.modules_root,
+                    "module {call_module} not in machine"
+                );
+
+                let Some(func) = module.internals_offset.checked_add(arg as u32) else {
+                    bail!("internal call function index overflow");
+                };
+                self.pc = ProgramCounter {
+                    module: call_module,
+                    func,
+                    inst: 0,
+                };
+            }
+            CallerModuleInternalCall => {
+                self.push_cross_module_call();
+                let frame = *self.frame()?;
+                if frame.caller_module_internals == 0 {
+                    error!()
+                }
+                let func = u32::try_from(arg)
+                    .ok()
+                    .and_then(|x| frame.caller_module_internals.checked_add(x));
+                let Some(func) = func else {
+                    bail!("internal call function index overflow");
+                };
+                self.pc = ProgramCounter {
+                    module: frame.caller_module,
+                    func,
+                    inst: 0,
+                };
+            }
+            CallIndirect => {
+                let index = self.pop_u32()?;
+                let (table, _) = wavm::unpack_call_indirect(arg);
+                ensure!(proof.u64()? == u64::from(table), "proof of wrong table");
+                let ty = proof.bytes32()?;
+                let data = keccak("Call indirect:", [&u64::from(table).to_be_bytes(), &*ty]);
+                ensure!(
+                    data == inst.get_proving_argument_data(),
+                    "call indirect data doesn't match the instruction",
+                );
+                let table = self.read_table(proof, table)?;
+                let Some(elem) = Self::read_element(proof, &table, index)? else {
+                    error!()
+                };
+                if elem.func_ty != ty {
+                    error!()
+                }
+                match elem.val {
+                    Value::FuncRef(func) => {
+                        self.push_call()?;
+                        self.pc.func = func;
+                        self.pc.inst = 0;
+                    }
+                    Value::RefNull => error!(),
+                    v => bail!("invalid table element value {v:?}"),
+                }
+            }
+            LocalGet | LocalSet => {
+                let value = match inst.opcode {
+                    LocalSet => Some(self.pop()?),
+                    _ => None,
+                };
+                let local = proof.value()?;
+                let siblings = proof.merkle_proof()?;
+                let frame = self.frame()?;
+                let root = merkle_root(MerkleType::Value, arg as usize, local.hash(), &siblings)?;
+                ensure!(root == frame.locals_root, "local {arg} not in frame");
+                match value {
+                    Some(value) => {
+                        let root =
+                            merkle_root(MerkleType::Value, arg as usize, value.hash(), &siblings);
+                        frame.locals_root = root?;
+                    }
+                    None => self.push(local),
+                }
+            }
+            GlobalGet | GlobalSet => {
+                let value = match inst.opcode {
+                    GlobalSet => Some(self.pop()?),
+                    _ => None,
+                };
+                let global = proof.value()?;
+                let siblings = proof.merkle_proof()?;
+                let root = merkle_root(MerkleType::Value, arg as usize, global.hash(), &siblings)?;
+                ensure!(
+                    root == self.module.globals_root,
+                    "global {arg} not in module"
+                );
+                match value {
+                    Some(value) => {
+                        let root =
+                            merkle_root(MerkleType::Value, arg as usize, value.hash(), &siblings);
+                        self.module.globals_root = root?;
+                    }
+                    None => self.push(global),
+                }
+            }
+            MemoryLoad { ty, bytes, signed } => {
+                let base = match self.pop()? {
+                    Value::I32(x) => x,
+                    x => bail!("WASM validation failed: top of stack before memory load is {x:?}"),
+                };
+                let Some(index) = arg.checked_add(base.into()) else {
+                    error!()
+                };
+                if !self.module.in_bounds(index, bytes.into()) {
+                    error!()
+                }
+                let (leaf, offset) = (index as usize / LEAF_SIZE, index as usize % LEAF_SIZE);
+                let first = self.read_leaf(proof, leaf)?;
+                let second = self.read_leaf(proof, leaf + 1)?;
+                self.check_leaf(&first)?;
+
+                let mut data = [0; 2 * LEAF_SIZE];
+                data[..LEAF_SIZE].copy_from_slice(&first.data);
+                if offset + bytes as usize > LEAF_SIZE {
+                    self.check_leaf(&second)?;
+                    data[LEAF_SIZE..].copy_from_slice(&second.data);
+                }
+
+                let mut raw = [0; 8];
+                raw[..bytes.into()].copy_from_slice(&data[offset..][..bytes.into()]);
+                let mut contents = u64::from_le_bytes(raw);
+                if signed {
+                    let shift = 64 - 8 * u32::from(bytes);
+                    contents = ((contents << shift) as i64 >> shift) as u64;
+                }
+                self.push(match ty {
+                    ArbValueType::I32 => Value::I32(contents as u32),
+                    ArbValueType::I64 => Value::I64(contents),
+                    ArbValueType::F32 => Value::F32(f32::from_bits(contents as u32)),
+                    ArbValueType::F64 => Value::F64(f64::from_bits(contents)),
+                    _ => bail!("invalid memory load output type {ty:?}"),
+                });
+            }
+            MemoryStore { ty: _, bytes } => {
+                let value = match self.pop()? {
+                    Value::I32(x) => x.into(),
+                    Value::I64(x) => x,
+                    Value::F32(x) => x.to_bits().into(),
+                    Value::F64(x) => x.to_bits(),
+                    x => bail!("WASM validation failed: attempted to memory store type {x:?}"),
+                };
+                let base = match self.pop()? {
+                    Value::I32(x) => x,
+                    x => bail!(
+                        "WASM validation failed: attempted to memory store with index type {x:?}"
+                    ),
+                };
+                let Some(index) = arg.checked_add(base.into()) else {
+                    error!()
+                };
+                if !self.module.in_bounds(index, bytes.into()) {
+                    error!()
+                }
+                let (leaf, offset) = (index as usize / LEAF_SIZE, index as usize % LEAF_SIZE);
+                let mut first = self.read_leaf(proof, leaf)?;
+                let mut second = self.read_leaf(proof, leaf + 1)?;
+                self.check_leaf(&first)?;
+
+                let mut data = [0; 2 * LEAF_SIZE];
+                data[..LEAF_SIZE].copy_from_slice(&first.data);
+                data[LEAF_SIZE..].copy_from_slice(&second.data);
+                data[offset..][..bytes.into()]
+                    .copy_from_slice(&value.to_le_bytes()[..bytes.into()]);
+                first.data.copy_from_slice(&data[..LEAF_SIZE]);
+                self.module.memory_root = first.root()?;
+
+                // the second leaf is proven against the memory with the first written
+                if offset + bytes as usize > LEAF_SIZE {
+                    self.check_leaf(&second)?;
+                    second.data.copy_from_slice(&data[LEAF_SIZE..]);
+                    self.module.memory_root = second.root()?;
+                }
+            }
+            I32Const => self.push(arg as u32),
+            I64Const => self.push(arg),
+            F32Const => self.push(f32::from_bits(arg as u32)),
+            F64Const => self.push(f64::from_bits(arg)),
+            I32Eqz => {
+                let value = self.pop_u32()?;
+                self.push((value == 0) as u32);
+            }
+            I64Eqz => {
+                let value = self.pop_u64()?;
+                self.push((value == 0) as u32);
+            }
+            IRelOp(t, op, signed) => {
+                let vb = self.pop()?;
+                let va = self.pop()?;
+                let value = match (t, va, vb) {
+                    (IntegerValType::I32, Value::I32(a), Value::I32(b)) => match signed {
+                        true => exec_irel_op(a as i32, b as i32, op),
+                        false => exec_irel_op(a, b, op),
+                    },
+                    (IntegerValType::I64, Value::I64(a), Value::I64(b)) => match signed {
+                        true => exec_irel_op(a as i64, b as i64, op),
+                        false => exec_irel_op(a, b, op),
+                    },
+                    _ => bail!("WASM validation failed: wrong types for {t:?}relop"),
+                };
+                self.push(value);
+            }
+            Drop => {
+                self.pop()?;
+            }
+            Select => {
+                let selector = self.pop_u32()?;
+                let val2 = self.pop()?;
+                let val1 = self.pop()?;
+                self.push(if selector == 0 { val2 } else { val1 });
+            }
+            MemorySize => {
+                let pages = self.module.memory_size / Memory::PAGE_SIZE;
+                self.push(pages as u32);
+            }
+            MemoryGrow => {
+                let old_size = self.module.memory_size;
+                let adding_pages = self.pop_u32()?;
+                let page_size = Memory::PAGE_SIZE;
+                let max_size = self.module.memory_max_size.saturating_mul(page_size);
+
+                let new_size = u64::from(adding_pages)
+                    .checked_mul(page_size)
+                    .and_then(|x| old_size.checked_add(x))
+                    .filter(|x| *x <= max_size);
+
+                // the memory merkle tree has a fixed depth, so only the size changes
+                match new_size {
+                    Some(new_size) => {
+                        self.module.memory_size = new_size;
+                        self.push((old_size / page_size) as u32);
+                    }
+                    None => self.push(u32::MAX),
+                }
+            }
+            IUnOp(w, op) => match (w, self.pop()?) {
+                (IntegerValType::I32, Value::I32(value)) => self.push(exec_iun_op(value, op)),
+                (IntegerValType::I64, Value::I64(value)) => {
+                    self.push(exec_iun_op(value, op) as u64)
+                }
+                _ => bail!("WASM validation failed: wrong types for {w:?}unop"),
+            },
+            IBinOp(w, op) => {
+                let vb = self.pop()?;
+                let va = self.pop()?;
+                let value = match (w, va, vb) {
+                    (IntegerValType::I32, Value::I32(a), Value::I32(b)) => {
+                        if op == IBinOpType::DivS && a as i32 == i32::MIN && b as i32 == -1 {
+                            error!()
+                        }
+                        exec_ibin_op(a, b, op).map(Value::from)
+                    }
+                    (IntegerValType::I64, Value::I64(a), Value::I64(b)) => {
+                        if op == IBinOpType::DivS && a as i64 == i64::MIN && b as i64 == -1 {
+                            error!()
+                        }
+                        exec_ibin_op(a, b, op).map(Value::from)
+                    }
+                    _ => bail!("WASM validation failed: wrong types for {w:?}binop"),
+                };
+                let Some(value) = value else { error!() };
+                self.push(value);
+            }
+            I32WrapI64 => {
+                let value = self.pop_u64()?;
+                self.push(value as u32);
+            }
+            I64ExtendI32(signed) => {
+                let value = self.pop_u32()?;
+                self.push(match signed {
+                    true => value as i32 as i64 as u64,
+                    false => value as u64,
+                });
+            }
+            Reinterpret(dest, source) => {
+                let value = self.pop()?;
+                ensure!(
+                    value.ty() == source,
+                    "bad reinterpret: val {value:?} source {source:?}",
+                );
+                let value: Value = match (value, dest) {
+                    (Value::I32(x), ArbValueType::F32) => f32::from_bits(x).into(),
+                    (Value::I64(x), ArbValueType::F64) => f64::from_bits(x).into(),
+                    (Value::F32(x), ArbValueType::I32) => x.to_bits().into(),
+                    (Value::F64(x), ArbValueType::I64) => x.to_bits().into(),
+                    _ => bail!("unsupported reinterpret of {source:?} as {dest:?}"),
+                };
+                self.push(value);
+            }
+            I32ExtendS(b) => {
+                let mut x = self.pop_u32()?;
+                let mask = (1u32 << b) - 1;
+                x &= mask;
+                if x & (1 << (b - 1)) != 0 {
+                    x |= !mask;
+                }
+                self.push(x);
+            }
+            I64ExtendS(b) => {
+                let mut x = self.pop_u64()?;
+                let mask = (1u64 << b) - 1;
+                x &= mask;
+                if x & (1 << (b - 1)) != 0 {
+                    x |= !mask;
+                }
+                self.push(x);
+            }
+            MoveFromStackToInternal => {
+                let value = self.pop()?;
+                self.internals.push(value);
+            }
+            MoveFromInternalToStack => {
+                let value = self.internals.pop()?;
+                self.push(value);
+            }
+            Dup => {
+                let value = self.pop()?;
+                self.push(value);
+                self.push(value);
+            }
+            GetGlobalStateBytes32 => {
+                let ptr = self.pop_u32()?;
+                let index = self.pop_u32()? as usize;
+                let state = self.read_global_state(proof)?;
+                let leaf = self.read_aligned_leaf(proof, ptr)?;
+                if index >= GLOBAL_STATE_BYTES32_NUM {
+                    error!()
+                }
+                let Some(mut leaf) = self.load_aligned(leaf, ptr)? else {
+                    error!()
+                };
+                leaf.data = state.bytes32_vals[index].0;
+                self.module.memory_root = leaf.root()?;
+            }
+            SetGlobalStateBytes32 => {
+                let ptr = self.pop_u32()?;
+                let index = self.pop_u32()? as usize;
+                let mut state = self.read_global_state(proof)?;
+                let leaf = self.read_aligned_leaf(proof, ptr)?;
+                if index >= GLOBAL_STATE_BYTES32_NUM {
+                    error!()
+                }
+                let Some(leaf) = self.load_aligned(leaf, ptr)? else {
+                    error!()
+                };
+                state.bytes32_vals[index] = leaf.data.into();
+                self.global_state = state.hash();
+            }
+            GetGlobalStateU64 => {
+                let index = self.pop_u32()? as usize;
+                let state = self.read_global_state(proof)?;
+                if index >= GLOBAL_STATE_U64_NUM {
+                    error!()
+                }
+                self.push(state.u64_vals[index]);
+            }
+            SetGlobalStateU64 => {
+                let value = self.pop_u64()?;
+                let index = self.pop_u32()? as usize;
+                let mut state = self.read_global_state(proof)?;
+                if index >= GLOBAL_STATE_U64_NUM {
+                    error!()
+                }
+                state.u64_vals[index] = value;
+                self.global_state = state.hash();
+            }
+            ReadPreImage => {
+                let offset = self.pop_u32()? as usize;
+                let ptr = self.pop_u32()?;
+                let leaf = self.read_aligned_leaf(proof, ptr)?;
+                let Some(mut leaf) = self.load_aligned(leaf, ptr)? else {
+                    error!()
+                };
+                ensure!(proof.u8()? == 0, "unknown preimage proof type");
+                let preimage = proof.rest();
+                let hash = Bytes32(leaf.data);
+                ensure!(
+                    hash == crypto::keccak(preimage).into(),
+                    "preimage doesn't match hash {hash}",
+                );
+
+                let len = preimage.len().saturating_sub(offset).min(32);
+                let read = preimage.get(offset..(offset + len)).unwrap_or_default();
+                leaf.data[..read.len()].copy_from_slice(read);
+                self.module.memory_root = leaf.root()?;
+                self.push(len as u32);
+            }
+            ReadInboxMessage => {
+                let offset = self.pop_u32()? as usize;
+                let ptr = self.pop_u32()?;
+                let msg_num = self.pop_u64()?;
+                let Some(inbox) = argument_data_to_inbox(arg) else {
+                    bail!("bad inbox identifier {arg}")
+                };
+                if inbox == InboxIdentifier::Sequencer && msg_num >= context.first_too_far {
+                    self.status = MachineStatus::TooFar;
+                    return Ok(());
+                }
+                let leaf = self.read_aligned_leaf(proof, ptr)?;
+                let Some(mut leaf) = self.load_aligned(leaf, ptr)? else {
+                    error!()
+                };
+                ensure!(proof.u8()? == 0, "unknown inbox proof type");
+                let message = proof.rest();
+
+                let len = message.len().saturating_sub(offset).min(32);
+                let read = message.get(offset..(offset + len)).unwrap_or_default();
+                leaf.data[..read.len()].copy_from_slice(read);
+                self.module.memory_root = leaf.root()?;
+                self.push(len as u32);
+            }
+            LinkModule => {
+                let ptr = self.pop_u32()?;
+                let leaf = self.read_leaf(proof, ptr as usize / LEAF_SIZE)?;
+                let leaf = (ptr as usize % LEAF_SIZE == 0).then_some(leaf);
+                let Some(leaf) = self.load_aligned(leaf, ptr)? else {
+                    error!()
+                };
+                let module = Bytes32(leaf.data);
+                let last = self.read_last_module(proof)?;
+                let index = last.index + 1;
+
+                if let Some(empty) = last.next_proof {
+                    self.modules_root = merkle_root(MerkleType::Module, index, module, &empty)?;
+                } else {
+                    // the tree deepens, with the new module leftmost in the new half
+                    let (mut node, mut empty) = (module, Bytes32::default());
+                    for _ in &last.siblings {
+                        node = hash_node(MerkleType::Module, node, empty);
+                        empty = hash_node(MerkleType::Module, empty, empty);
+                    }
+                    self.modules_root = hash_node(MerkleType::Module, self.modules_root, node);
+                }
+                self.push(index as u32);
+            }
+            UnlinkModule => {
+                let LastModule {
+                    index, siblings, ..
+                } = self.read_last_module(proof)?;
+                self.modules_root = match siblings.last() {
+                    None => Bytes32::default(),
+                    // the tree shallows, leaving just its left half
+                    Some(left) if math::is_power_of_2(index) => *left,
+                    Some(_) => {
+                        let empty = Bytes32::default();
+                        merkle_root(MerkleType::Module, index, empty, &siblings)?
+                    }
+                };
+            }
+            HaltAndSetFinished => self.status = MachineStatus::Finished,
+            NewCoThread => {
+                if self.thread_state.is_cothread() {
+                    error!()
+                }
+                self.values.push_cothread();
+                self.frames.push_cothread();
+            }
+            PopCoThread => {
+                if self.thread_state.is_cothread() {
+                    error!()
+                }
+                let (last, rest) = (proof.bytes32()?, proof.bytes32()?);
+                self.values.pop_cothread(last, rest)?;
+                let (last, rest) = (proof.bytes32()?, proof.bytes32()?);
+                self.frames.pop_cothread(last, rest)?;
+            }
+            SwitchThread => {
+                let next = match arg {
+                    0 => ThreadState::Main,
+                    x => ThreadState::CoThread(self.pc + (x - 1) as u32),
+                };
+                if next.is_cothread() == self.thread_state.is_cothread() {
+                    error!()
+                }
+                self.thread_state = next;
+            }
+        }
+        Ok(())
+    }
+}
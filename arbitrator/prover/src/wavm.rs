@@ -3,6 +3,7 @@
 
 use crate::{
     binary::FloatInstruction,
+    bulk::BulkOps,
     host::InternalFunc,
     value::{ArbValueType, FunctionType, IntegerValType},
 };
@@ -293,6 +294,128 @@ impl Opcode {
         }
     }
 
+    /// Inverts `repr`, returning `None` for values no opcode represents.
+    pub fn from_repr(repr: u16) -> Option<Opcode> {
+        use ArbValueType::{F32, F64, I32, I64};
+        use IntegerValType as W;
+        use Opcode::*;
+
+        let load = |ty, bytes, signed| MemoryLoad { ty, bytes, signed };
+        let store = |ty, bytes| MemoryStore { ty, bytes };
+        let rel_op = |w, offset| {
+            let (op, signed) = match offset {
+                0 => (IRelOpType::Eq, false),
+                1 => (IRelOpType::Ne, false),
+                2 => (IRelOpType::Lt, true),
+                3 => (IRelOpType::Lt, false),
+                4 => (IRelOpType::Gt, true),
+                5 => (IRelOpType::Gt, false),
+                6 => (IRelOpType::Le, true),
+                7 => (IRelOpType::Le, false),
+                8 => (IRelOpType::Ge, true),
+                _ => (IRelOpType::Ge, false),
+            };
+            IRelOp(w, op, signed)
+        };
+        let un_op = |w, offset| {
+            let ops = [IUnOpType::Clz, IUnOpType::Ctz, IUnOpType::Popcnt];
+            IUnOp(w, ops[usize::from(offset)])
+        };
+        let bin_op = |w, offset| {
+            use IBinOpType::*;
+            let ops = [
+                Add, Sub, Mul, DivS, DivU, RemS, RemU, And, Or, Xor, Shl, ShrS, ShrU, Rotl, Rotr,
+            ];
+            IBinOp(w, ops[usize::from(offset)])
+        };
+
+        Some(match repr {
+            0x00 => Unreachable,
+            0x01 => Nop,
+            0x0F => Return,
+            0x10 => Call,
+            0x11 => CallIndirect,
+            0x1A => Drop,
+            0x1B => Select,
+            0x20 => LocalGet,
+            0x21 => LocalSet,
+            0x23 => GlobalGet,
+            0x24 => GlobalSet,
+            0x28 => load(I32, 4, false),
+            0x29 => load(I64, 8, false),
+            0x2A => load(F32, 4, false),
+            0x2B => load(F64, 8, false),
+            0x2C => load(I32, 1, true),
+            0x2D => load(I32, 1, false),
+            0x2E => load(I32, 2, true),
+            0x2F => load(I32, 2, false),
+            0x30 => load(I64, 1, true),
+            0x31 => load(I64, 1, false),
+            0x32 => load(I64, 2, true),
+            0x33 => load(I64, 2, false),
+            0x34 => load(I64, 4, true),
+            0x35 => load(I64, 4, false),
+            0x36 => store(I32, 4),
+            0x37 => store(I64, 8),
+            0x38 => store(F32, 4),
+            0x39 => store(F64, 8),
+            0x3A => store(I32, 1),
+            0x3B => store(I32, 2),
+            0x3C => store(I64, 1),
+            0x3D => store(I64, 2),
+            0x3E => store(I64, 4),
+            0x3F => MemorySize,
+            0x40 => MemoryGrow,
+            0x41 => I32Const,
+            0x42 => I64Const,
+            0x43 => F32Const,
+            0x44 => F64Const,
+            0x45 => I32Eqz,
+            0x46..=0x4F => rel_op(W::I32, repr - 0x46),
+            0x50 => I64Eqz,
+            0x51..=0x5A => rel_op(W::I64, repr - 0x51),
+            0x67..=0x69 => un_op(W::I32, repr - 0x67),
+            0x6A..=0x78 => bin_op(W::I32, repr - 0x6A),
+            0x79..=0x7B => un_op(W::I64, repr - 0x79),
+            0x7C..=0x8A => bin_op(W::I64, repr - 0x7C),
+            0xA7 => I32WrapI64,
+            0xAC => I64ExtendI32(true),
+            0xAD => I64ExtendI32(false),
+            0xBC => Reinterpret(I32, F32),
+            0xBD => Reinterpret(I64, F64),
+            0xBE => Reinterpret(F32, I32),
+            0xBF => Reinterpret(F64, I64),
+            0xC0 => I32ExtendS(8),
+            0xC1 => I32ExtendS(16),
+            0xC2 => I64ExtendS(8),
+            0xC3 => I64ExtendS(16),
+            0xC4 => I64ExtendS(32),
+            0x8002 => InitFrame,
+            0x8003 => ArbitraryJump,
+            0x8004 => ArbitraryJumpIf,
+            0x8005 => MoveFromStackToInternal,
+            0x8006 => MoveFromInternalToStack,
+            0x8008 => Dup,
+            0x8009 => CrossModuleCall,
+            0x800A => CallerModuleInternalCall,
+            0x800B => CrossModuleForward,
+            0x800C => CrossModuleInternalCall,
+            0x8010 => GetGlobalStateBytes32,
+            0x8011 => SetGlobalStateBytes32,
+            0x8012 => GetGlobalStateU64,
+            0x8013 => SetGlobalStateU64,
+            0x8020 => ReadPreImage,
+            0x8021 => ReadInboxMessage,
+            0x8022 => HaltAndSetFinished,
+            0x8023 => LinkModule,
+            0x8024 => UnlinkModule,
+            0x8030 => NewCoThread,
+            0x8031 => PopCoThread,
+            0x8032 => SwitchThread,
+            _ => return None,
+        })
+    }
+
     pub fn is_host_io(self) -> bool {
         matches!(
             self,
@@ -469,6 +592,7 @@ pub fn wasm_to_wavm(
     all_types: &[FunctionType],
     all_types_func_idx: u32,
     internals_offset: u32,
+    bulk: &BulkOps,
     name: &str,
 ) -> Result<()> {
     use Operator::*;
@@ -1025,11 +1149,17 @@ pub fn wasm_to_wavm(
                 call!(internals_offset + InternalFunc::MemoryCopy as u32)
             },
 
+            MemoryInit { mem, .. } => {
+                ensure!(*mem == 0, "multi-memory proposal not supported");
+                opcode!(Call, bulk.implementation(op)?.into(), @pop 3)
+            }
+            DataDrop { data_index } => {
+                opcode!(I32Const, 0, @push 1);
+                opcode!(GlobalSet, bulk.data_len(*data_index).into(), @pop 1);
+            }
+
             unsupported @ (
-                dot!(
-                    MemoryInit, DataDrop, TableInit, ElemDrop,
-                    TableCopy, TableFill, TableGet, TableSet, TableGrow, TableSize
-                )
+                dot!(TableInit, ElemDrop, TableCopy, TableFill, TableGet, TableSet, TableGrow, TableSize)
             ) => bail!("bulk-memory-operations extension not fully supported {:?}", unsupported),
 
             unsupported @ (
@@ -0,0 +1,234 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+//! Runs a Stylus program natively against an in-memory world state.
+//!
+//! ```text
+//! stylus-run program.wasm --calldata 0x70a08231... --evm-data evm.json --storage storage.json
+//! ```
+
+use arbutil::{evm::EvmData, Bytes20, Bytes32, Color};
+use eyre::{bail, Context, Result};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::PathBuf,
+};
+use structopt::StructOpt;
+use stylus::{
+    mock::MockEvmApi,
+    prover::programs::{config::CompilePricingParams, prelude::*, profiler::ProfilingMachine},
+    run::RunProgram,
+};
+
+#[derive(StructOpt)]
+#[structopt(name = "stylus-run")]
+struct Opts {
+    /// the program to run, as wasm or wat
+    wasm: PathBuf,
+    /// the calldata as hex
+    #[structopt(short, long, default_value = "")]
+    calldata: String,
+    /// the Stylus version to compile and run the program with
+    #[structopt(long, default_value = "1")]
+    version: u16,
+    /// a toml or json pricing table to compile the program with instead of the version's
+    #[structopt(long)]
+    pricing: Option<PathBuf>,
+    /// the max stack depth
+    #[structopt(long, default_value = "262144")]
+    max_depth: u32,
+    /// the price of ink in gas
+    #[structopt(long, default_value = "10000")]
+    ink_price: u32,
+    /// the gas to run the program with
+    #[structopt(long, default_value = "30000000")]
+    gas: u64,
+    /// enable debug hostios like console.log
+    #[structopt(long)]
+    debug: bool,
+    /// a json file of EVM context data, with snake_case fields matching `EvmData`
+    #[structopt(long)]
+    evm_data: Option<PathBuf>,
+    /// a json file mapping the program's storage keys to values
+    #[structopt(long)]
+    storage: Option<PathBuf>,
+    /// print the ink each function consumed
+    #[structopt(long)]
+    profile: bool,
+    /// the ink profile written in "folded" format (use as input for e.g. inferno-flamegraph)
+    #[structopt(long)]
+    profile_output: Option<PathBuf>,
+    /// the number of basic blocks per function to profile individually
+    #[structopt(long, default_value = "0")]
+    profile_blocks: u32,
+}
+
+/// The json form of `EvmData`, in which words and addresses are hex strings.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct EvmDataJson {
+    block_basefee: String,
+    block_blob_basefee: String,
+    chainid: u64,
+    block_coinbase: String,
+    block_gas_limit: u64,
+    block_number: u64,
+    block_prevrandao: String,
+    block_timestamp: u64,
+    contract_address: String,
+    msg_sender: String,
+    msg_value: String,
+    tx_gas_price: String,
+    tx_origin: String,
+    reentrant: u32,
+}
+
+impl EvmDataJson {
+    fn parse(self) -> Result<EvmData> {
+        Ok(EvmData {
+            block_basefee: word(&self.block_basefee)?,
+            block_blob_basefee: word(&self.block_blob_basefee)?,
+            chainid: self.chainid,
+            block_coinbase: address(&self.block_coinbase)?,
+            block_gas_limit: self.block_gas_limit,
+            block_number: self.block_number,
+            block_prevrandao: word(&self.block_prevrandao)?,
+            block_timestamp: self.block_timestamp,
+            contract_address: address(&self.contract_address)?,
+            msg_sender: address(&self.msg_sender)?,
+            msg_value: word(&self.msg_value)?,
+            tx_gas_price: word(&self.tx_gas_price)?,
+            tx_origin: address(&self.tx_origin)?,
+            reentrant: self.reentrant,
+            return_data_len: 0,
+            tracing: false,
+        })
+    }
+}
+
+/// Decodes hex with an optional `0x` prefix.
+fn decode(text: &str) -> Result<Vec<u8>> {
+    let text = text.trim();
+    let text = text.strip_prefix("0x").unwrap_or(text);
+    hex::decode(text).wrap_err_with(|| format!("invalid hex {}", text.red()))
+}
+
+/// Decodes a word, left-padding with zeros.
+fn word(text: &str) -> Result<Bytes32> {
+    let data = decode(text)?;
+    let mut word = Bytes32::default();
+    let Some(start) = 32_usize.checked_sub(data.len()) else {
+        bail!("word {} is too long", text.red())
+    };
+    word[start..].copy_from_slice(&data);
+    Ok(word)
+}
+
+/// Decodes an address, left-padding with zeros.
+fn address(text: &str) -> Result<Bytes20> {
+    let word = word(text)?;
+    if word[..12].iter().any(|x| *x != 0) {
+        bail!("address {} is too long", text.red())
+    }
+    Ok(word[12..].try_into().unwrap())
+}
+
+fn main() -> Result<()> {
+    let opts = Opts::from_args();
+
+    let wasm = fs::read(&opts.wasm).wrap_err("failed to read program")?;
+    let calldata = decode(&opts.calldata).wrap_err("failed to parse calldata")?;
+
+    let evm_data = match &opts.evm_data {
+        Some(path) => {
+            let json: EvmDataJson = serde_json::from_slice(&fs::read(path)?)?;
+            json.parse().wrap_err("failed to parse evm data")?
+        }
+        None => EvmData::default(),
+    };
+
+    let mut storage = BTreeMap::new();
+    if let Some(path) = &opts.storage {
+        let json: BTreeMap<String, String> = serde_json::from_slice(&fs::read(path)?)?;
+        for (key, value) in json {
+            storage.insert(word(&key)?, word(&value)?);
+        }
+    }
+
+    let mut compile = CompileConfig::version(opts.version, opts.debug);
+    if let Some(path) = &opts.pricing {
+        compile.pricing = CompilePricingParams::load(path)?;
+    }
+    compile.debug.profile_ink = opts.profile || opts.profile_output.is_some();
+    compile.debug.profile_blocks = opts.profile_blocks;
+    let config = StylusConfig::new(opts.version, opts.max_depth, opts.ink_price);
+    let program = evm_data.contract_address;
+
+    let (mut evm, _) = MockEvmApi::new(compile);
+    evm.set_evm_data(evm_data);
+    evm.deploy_wasm(program, &wasm, config)?;
+    for (key, value) in &storage {
+        evm.world().set_storage(program, *key, *value);
+    }
+
+    let mut native = evm.instance(program)?;
+    let ink = config.pricing.gas_to_ink(opts.gas);
+    let outcome = native.run_main(&calldata, config, ink)?;
+    let ink_left: u64 = native.ink_left().into();
+    let ink_used = ink - ink_left;
+
+    println!("{} {outcome}", "outcome".grey());
+    println!(
+        "{} {ink_used} ({} gas)",
+        "ink used".grey(),
+        config.pricing.ink_to_gas(ink_used)
+    );
+
+    for log in evm.logs() {
+        let topics: Vec<_> = log.topics.iter().map(|x| format!("{x}")).collect();
+        println!(
+            "{} {} topics [{}] data {}",
+            "log".grey(),
+            log.address,
+            topics.join(", "),
+            hex::encode(&log.data)
+        );
+    }
+
+    if compile.debug.profile_ink {
+        let profile = native.ink_profile()?;
+        if opts.profile {
+            let mut funcs: Vec<_> = profile.funcs.iter().collect();
+            funcs.sort_by_key(|(_, func)| std::cmp::Reverse(func.ink));
+
+            println!("{}", "profile".grey());
+            for (index, func) in funcs {
+                let (name, calls) = (func.display_name(*index), func.calls);
+                println!("  {} {} ink in {calls} calls", name.mint(), func.ink);
+                for (block, ink) in func.blocks.iter().enumerate() {
+                    println!("    block {block} {ink} ink");
+                }
+            }
+        }
+        if let Some(path) = &opts.profile_output {
+            let name = opts.wasm.file_stem().unwrap_or_default().to_string_lossy();
+            fs::write(path, profile.folded(&name)).wrap_err("failed to write profile")?;
+        }
+    }
+
+    let world = evm.world();
+    let slots = world.accounts.get(&program).map(|x| &x.storage);
+    let slots: BTreeMap<_, _> = slots.into_iter().flatten().collect();
+    let keys: BTreeSet<_> = storage.keys().chain(slots.keys().copied()).collect();
+
+    for key in keys {
+        let before = storage.get(key).copied().unwrap_or_default();
+        let after = slots.get(key).map(|x| **x).unwrap_or_default();
+        if before != after {
+            println!("{} {key}: {before} -> {}", "storage".grey(), after.mint());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,387 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+//! Tools for inspecting Stylus programs.
+//!
+//! ```text
+//! stylus activate program.wasm --report --json
+//! stylus activate program.wasm --compiler cranelift-speed --cache-dir artifacts
+//! stylus estimate program.wasm
+//! stylus calibrate --ops I32Add,I64Mul  # with the calibrate feature
+//! ```
+
+use arbutil::Color;
+use eyre::{Context, Result};
+use serde_json::json;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+use structopt::StructOpt;
+use stylus::{
+    cache::ArtifactCache,
+    native,
+    prover::{
+        binary::{self, ActivationError, ActivationErrors, LimitUsage},
+        machine::Module as ProverModule,
+        programs::{
+            config::{CompileConfig, CompilePricingParams, NativeCompiler},
+            estimate::InkEstimate,
+            StylusData,
+        },
+        simd,
+    },
+};
+#[cfg(feature = "calibrate")]
+use {
+    arbutil::operator::OperatorCode,
+    stylus::calibrate::{self, CalibrationParams},
+};
+
+#[derive(StructOpt)]
+#[structopt(name = "stylus")]
+enum Opts {
+    /// activates a program, printing its module hash
+    Activate(ActivateOpts),
+    /// statically bounds the ink each of a program's functions consumes
+    Estimate(EstimateOpts),
+    /// times each priced operator, comparing its ink to how long it takes
+    #[cfg(feature = "calibrate")]
+    Calibrate(CalibrateOpts),
+}
+
+#[derive(StructOpt)]
+struct ActivateOpts {
+    /// the program to activate, as wasm or wat
+    wasm: PathBuf,
+    /// the Stylus version to activate the program with
+    #[structopt(long, default_value = "1")]
+    version: u16,
+    /// the pages of memory remaining in the tx
+    #[structopt(long, default_value = "128")]
+    page_limit: u16,
+    /// enable debug hostios like console.log
+    #[structopt(long)]
+    debug: bool,
+    /// the gas available for activation, unlimited by default
+    #[structopt(long)]
+    gas: Option<u64>,
+    /// print every limit with the program's usage, along with its stylus data and costs
+    #[structopt(long)]
+    report: bool,
+    /// print the report as json
+    #[structopt(long)]
+    json: bool,
+    /// also compile native code with this compiler, like cranelift-speed
+    #[structopt(long)]
+    compiler: Option<NativeCompiler>,
+    /// cache compiled native code in this directory
+    #[structopt(long)]
+    cache_dir: Option<PathBuf>,
+}
+
+#[derive(StructOpt)]
+struct EstimateOpts {
+    /// the program to estimate, as wasm or wat
+    wasm: PathBuf,
+    /// the Stylus version whose pricing to use
+    #[structopt(long, default_value = "1")]
+    version: u16,
+    /// a toml or json pricing table to use instead of the version's
+    #[structopt(long)]
+    pricing: Option<PathBuf>,
+}
+
+#[cfg(feature = "calibrate")]
+#[derive(StructOpt)]
+struct CalibrateOpts {
+    /// the Stylus version whose pricing to check
+    #[structopt(long, default_value = "1")]
+    version: u16,
+    /// a toml or json pricing table to check instead of the version's
+    #[structopt(long)]
+    pricing: Option<PathBuf>,
+    /// the operators to time, all of those priced by default
+    #[structopt(long, use_delimiter = true)]
+    ops: Vec<OperatorCode>,
+    /// the number of loop iterations per benchmark
+    #[structopt(long, default_value = "100000")]
+    iters: u32,
+    /// the number of times to run each benchmark, keeping the fastest
+    #[structopt(long, default_value = "5")]
+    trials: usize,
+}
+
+fn main() -> Result<()> {
+    match Opts::from_args() {
+        Opts::Activate(opts) => activate(opts),
+        Opts::Estimate(opts) => estimate(opts),
+        #[cfg(feature = "calibrate")]
+        Opts::Calibrate(opts) => calibrate(opts),
+    }
+}
+
+fn read_wasm(path: &Path) -> Result<Vec<u8>> {
+    let wasm = fs::read(path).wrap_err("failed to read program")?;
+    Ok(wasmer::wat2wasm(&wasm)?.to_vec())
+}
+
+fn activate(opts: ActivateOpts) -> Result<()> {
+    let wasm = read_wasm(&opts.wasm)?;
+    let (version, page_limit, debug) = (opts.version, opts.page_limit, opts.debug);
+
+    let start_gas = opts.gas.unwrap_or(u64::MAX);
+    let mut gas = start_gas;
+    let activation = ProverModule::activate(&wasm, version, page_limit, debug, &mut gas);
+    let activation_gas = start_gas - gas;
+
+    if !opts.report {
+        let (module, _) = activation?;
+        println!("{} {}", "module hash".grey(), module.hash());
+        if let Some(compiler) = opts.compiler {
+            compile_native(&wasm, version, debug, compiler, &opts)?;
+        }
+        return Ok(());
+    }
+
+    // parse separately so that limits are reported even when activation fails
+    let compile = CompileConfig::version(version, debug);
+    let lowered = simd::lower_user(&wasm, version)?;
+    let mut bin = binary::parse(&lowered, Path::new("user"))?;
+    let stylus_data = bin.instrument(&compile).ok();
+    let limits = bin.limit_usage(page_limit);
+
+    let error = activation.as_ref().err();
+    let violations = error.and_then(|x| x.downcast_ref::<ActivationErrors>());
+
+    let report = Report {
+        limits,
+        stylus_data,
+        activation_gas,
+        module_hash: activation.as_ref().ok().map(|x| x.0.hash().to_string()),
+        violations: violations.map(|x| x.0.clone()).unwrap_or_default(),
+        error: error.map(|x| format!("{x:#}")),
+    };
+    match opts.json {
+        true => println!("{}", serde_json::to_string_pretty(&report.json())?),
+        false => report.print(),
+    }
+    activation.map(|_| ())
+}
+
+fn compile_native(
+    wasm: &[u8],
+    version: u16,
+    debug: bool,
+    compiler: NativeCompiler,
+    opts: &ActivateOpts,
+) -> Result<()> {
+    let mut compile = CompileConfig::version(version, debug);
+    compile.compiler = compiler;
+
+    let cache = opts
+        .cache_dir
+        .as_ref()
+        .map(ArtifactCache::new)
+        .transpose()?;
+    let key = ArtifactCache::key(wasm, &compile);
+    let cached = cache.as_ref().and_then(|x| x.get(key)).is_some();
+
+    let start = Instant::now();
+    let asm = native::compile(wasm, compile, cache.as_ref())?;
+    let elapsed = start.elapsed();
+
+    let source = match cached {
+        true => "cached".mint(),
+        false => "compiled".yellow(),
+    };
+    println!("{} {} {source}", "compiler".grey(), compiler.name());
+    println!("{} {} bytes in {elapsed:.2?}", "asm".grey(), asm.len());
+    if cache.is_some() {
+        println!("{} {key}", "artifact".grey());
+    }
+    Ok(())
+}
+
+fn estimate(opts: EstimateOpts) -> Result<()> {
+    let wasm = read_wasm(&opts.wasm)?;
+    let wasm = simd::lower_user(&wasm, opts.version)?;
+    let bin = binary::parse(&wasm, Path::new("user"))?;
+    let mut compile = CompileConfig::version(opts.version, false);
+    if let Some(path) = &opts.pricing {
+        compile.pricing = CompilePricingParams::load(path)?;
+    }
+    let estimate = InkEstimate::new(&bin, &compile.pricing)?;
+    println!("{} {}", "pricing".grey(), compile.pricing.digest());
+
+    let bound = |ink: Option<u64>| match ink {
+        Some(ink) => format!("{ink}"),
+        None => "unbounded".red(),
+    };
+
+    println!("{}", "exports".grey());
+    for (name, func) in &estimate.exports {
+        let Some(func) = estimate.funcs.get(func) else {
+            continue; // exported hostio
+        };
+        let (min, max) = (func.min_ink, bound(func.max_ink));
+        println!("  {} {min} to {max} ink", name.mint());
+    }
+
+    println!("{}", "funcs".grey());
+    for (index, func) in &estimate.funcs {
+        let (min, max) = (func.min_ink, bound(func.max_ink));
+        println!("  func {index} {min} to {max} ink");
+        for (op, ink) in &func.loops {
+            println!("    loop at op {op} {} ink per iteration", bound(*ink));
+        }
+    }
+    println!(
+        "{}",
+        "note: hostios and bulk memory operations are priced dynamically and aren't included".dim()
+    );
+    Ok(())
+}
+
+#[cfg(feature = "calibrate")]
+fn calibrate(opts: CalibrateOpts) -> Result<()> {
+    let mut pricing = CompileConfig::version(opts.version, false).pricing;
+    if let Some(path) = &opts.pricing {
+        pricing = CompilePricingParams::load(path)?;
+    }
+    let costs = &pricing.costs;
+
+    let mut ops = opts.ops;
+    if ops.is_empty() {
+        ops = costs.ops.keys().copied().collect();
+    }
+    let params = CalibrationParams {
+        iters: opts.iters,
+        trials: opts.trials,
+        ..Default::default()
+    };
+    let calibrations = calibrate::calibrate(costs, &ops, params)?;
+
+    // operators are mispriced relative to the others when far from the median ratio
+    let mut ratios: Vec<_> = calibrations.iter().filter_map(|x| x.ink_per_ns()).collect();
+    ratios.sort_by(f64::total_cmp);
+    let median = ratios.get(ratios.len() / 2).copied().unwrap_or_default();
+
+    let time = |ns: Option<f64>| match ns {
+        Some(ns) => format!("{ns:>9.3} ns"),
+        None => format!("{:>12}", "n/a"),
+    };
+
+    println!("{} {}", "pricing".grey(), pricing.digest());
+    println!(
+        "{}",
+        format!(
+            "{:24} {:>12} {:>12} {:>8} {:>9}",
+            "op", "singlepass", "cranelift", "ink", "ink/ns"
+        )
+        .grey()
+    );
+    for calibration in &calibrations {
+        let (single, crane) = (time(calibration.singlepass), time(calibration.cranelift));
+        let ratio = calibration.ink_per_ns();
+        let text = match ratio {
+            Some(ratio) => format!("{ratio:>9.1}"),
+            None => format!("{:>9}", "n/a"),
+        };
+        let text = match ratio {
+            Some(ratio) if ratio < median / 2. => format!("{} underpriced", text.red()),
+            Some(ratio) if ratio > median * 2. => format!("{} overpriced", text.yellow()),
+            _ => text,
+        };
+        let op = format!("{:24}", calibration.op.to_string());
+        println!("{op} {single} {crane} {:>8} {text}", calibration.ink);
+    }
+    println!("{} {median:.1} ink/ns", "median".grey());
+    println!(
+        "{}",
+        "note: timings are machine dependent, and Stylus compiles with singlepass".dim()
+    );
+    Ok(())
+}
+
+/// Everything learned while activating a program.
+struct Report {
+    limits: Vec<LimitUsage>,
+    stylus_data: Option<StylusData>,
+    activation_gas: u64,
+    module_hash: Option<String>,
+    violations: Vec<ActivationError>,
+    error: Option<String>,
+}
+
+impl Report {
+    fn json(&self) -> serde_json::Value {
+        let limits: Vec<_> = self
+            .limits
+            .iter()
+            .map(|x| {
+                json!({
+                    "name": x.name,
+                    "kind": x.kind,
+                    "limit": x.limit,
+                    "actual": x.actual,
+                    "headroom": x.headroom(),
+                    "exceeded": x.exceeded(),
+                })
+            })
+            .collect();
+
+        let stylus_data = self.stylus_data.map(|data| {
+            json!({
+                "footprint": data.footprint,
+                "init_gas": data.init_gas,
+                "asm_estimate": data.asm_estimate,
+            })
+        });
+
+        json!({
+            "limits": limits,
+            "stylus_data": stylus_data,
+            "activation_gas": self.activation_gas,
+            "module_hash": self.module_hash,
+            "violations": self.violations,
+            "error": self.error,
+        })
+    }
+
+    fn print(&self) {
+        let width = self.limits.iter().map(|x| x.name.len()).max().unwrap_or(0);
+
+        println!("{}", "limits".grey());
+        for usage in &self.limits {
+            let name = format!("{:width$}", usage.name);
+            let (actual, limit) = (usage.actual, usage.limit);
+            let text = format!("{name} {actual:>8} / {limit:<8} {} left", usage.headroom());
+            match usage.exceeded() {
+                true => println!("  {}", text.red()),
+                false => println!("  {text}"),
+            }
+        }
+
+        if let Some(data) = &self.stylus_data {
+            println!("{}", "stylus data".grey());
+            println!("  footprint    {} pages", data.footprint);
+            println!("  init gas     {}", data.init_gas);
+            println!("  asm estimate {} bytes", data.asm_estimate);
+        }
+
+        println!("{} {}", "activation gas".grey(), self.activation_gas);
+        if let Some(hash) = &self.module_hash {
+            println!("{} {}", "module hash".grey(), hash.mint());
+        }
+        if !self.violations.is_empty() {
+            println!("{}", "violations".grey());
+            for violation in &self.violations {
+                println!("  {violation}");
+            }
+        } else if let Some(error) = &self.error {
+            println!("{} {}", "error".grey(), error.red());
+        }
+    }
+}
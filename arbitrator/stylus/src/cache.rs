@@ -0,0 +1,117 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+//! A content-addressed, on-disk cache of compiled programs.
+//!
+//! Optimizing compilers are too slow to run upon each activation, so hot programs are compiled
+//! once and their artifacts reused. Each is keyed by everything that determines its contents: the
+//! hash of the program's wasm, the compile config, the compiler, and the target.
+//!
+//! Artifacts are native code that is run without verification, so the cache trusts whoever can
+//! write to its directory. It creates that directory, and each artifact, readable only by the
+//! current user. Each artifact is checksummed together with its key, which catches corruption
+//! and files renamed to another key, but does not stop a writer bent on tampering.
+//!
+//! Only the `stylus` library and CLI use the cache so far; the node always activates with
+//! singlepass.
+
+use arbutil::{crypto, Bytes32};
+use eyre::{Context, Result};
+use prover::programs::config::CompileConfig;
+use std::{
+    fs::{self, DirBuilder, OpenOptions},
+    io::Write,
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+    process,
+};
+use wasmer::Target;
+
+#[derive(Clone, Debug)]
+pub struct ArtifactCache {
+    dir: PathBuf,
+}
+
+impl ArtifactCache {
+    /// Opens the cache in the given directory, creating it if needed.
+    /// Any directories created are accessible only to the current user.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&dir)
+            .wrap_err("failed to create artifact cache")?;
+        Ok(Self { dir })
+    }
+
+    /// The directory holding the artifacts.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Identifies the artifact for a program compiled on this machine.
+    pub fn key(wasm: &[u8], compile: &CompileConfig) -> Bytes32 {
+        let target = Target::default();
+        let target = format!("{} {:?}", target.triple(), target.cpu_features());
+        let bounds = &compile.bounds;
+        let debug = &compile.debug;
+
+        let mut preimage = b"Stylus artifact:".to_vec();
+        preimage.extend(crypto::keccak(wasm));
+        preimage.extend(compile.version.to_be_bytes());
+        preimage.extend(compile.pricing.digest());
+        preimage.extend(bounds.heap_bound.0.to_be_bytes());
+        preimage.extend(bounds.max_frame_size.to_be_bytes());
+        preimage.extend(bounds.max_frame_contention.to_be_bytes());
+        preimage.extend([debug.debug_funcs, debug.count_ops, debug.profile_ink].map(u8::from));
+        preimage.extend(debug.profile_blocks.to_be_bytes());
+
+        for text in [compile.native_compiler().name(), &target, wasmer::VERSION] {
+            preimage.extend((text.len() as u32).to_be_bytes());
+            preimage.extend(text.as_bytes());
+        }
+        crypto::keccak(preimage).into()
+    }
+
+    fn path(&self, key: Bytes32) -> PathBuf {
+        self.dir.join(format!("{key}.bin"))
+    }
+
+    /// Binds an artifact to its key, so that it can't be read under any other.
+    fn checksum(key: Bytes32, asm: &[u8]) -> [u8; 32] {
+        let mut preimage = key.to_vec();
+        preimage.extend(asm);
+        crypto::keccak(preimage)
+    }
+
+    /// Reads an artifact, discarding it if corrupt or stored under the wrong key.
+    pub fn get(&self, key: Bytes32) -> Option<Vec<u8>> {
+        let path = self.path(key);
+        let data = fs::read(&path).ok()?;
+        if data.len() < 32 || data[..32] != Self::checksum(key, &data[32..]) {
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+        Some(data[32..].to_vec())
+    }
+
+    /// Writes an artifact, atomically replacing any prior one.
+    pub fn insert(&self, key: Bytes32, asm: &[u8]) -> Result<()> {
+        let path = self.path(key);
+        let temp = path.with_extension(format!("tmp{}", process::id()));
+
+        let mut data = Self::checksum(key, asm).to_vec();
+        data.extend(asm);
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&temp)
+            .wrap_err("failed to create artifact")?;
+        file.write_all(&data).wrap_err("failed to write artifact")?;
+        fs::rename(&temp, &path).wrap_err("failed to move artifact into place")
+    }
+}
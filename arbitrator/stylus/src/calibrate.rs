@@ -0,0 +1,265 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+//! Times each priced operator in a micro-benchmark, so that its ink price can be compared
+//! against how long it actually takes to execute.
+//!
+//! Each benchmark is a loop that repeats a snippet exercising the operator. Where possible,
+//! the snippet feeds the operator's result back into its inputs to keep the compiler from
+//! hoisting or deduplicating it. The time of an otherwise identical empty loop is subtracted,
+//! so measurements include only the snippet, whose few local accesses are attributed to the op.
+
+use arbutil::operator::OperatorCode;
+use eyre::{bail, Result, WrapErr};
+use prover::programs::config::OpCosts;
+use std::time::{Duration, Instant};
+use wasmer::{CompilerConfig, Imports, Instance, Module, Store};
+use wasmer_compiler_cranelift::{Cranelift, CraneliftOptLevel};
+use wasmer_compiler_singlepass::Singlepass;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Compiler {
+    Singlepass,
+    Cranelift,
+}
+
+impl Compiler {
+    fn store(self) -> Store {
+        match self {
+            Self::Singlepass => {
+                let mut compiler = Singlepass::new();
+                compiler.canonicalize_nans(true);
+                compiler.enable_verifier();
+                Store::new(compiler)
+            }
+            Self::Cranelift => {
+                let mut compiler = Cranelift::new();
+                compiler.canonicalize_nans(true);
+                compiler.enable_verifier();
+                compiler.opt_level(CraneliftOptLevel::Speed);
+                Store::new(compiler)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CalibrationParams {
+    /// The number of loop iterations per benchmark
+    pub iters: u32,
+    /// The number of times each iteration repeats the snippet
+    pub copies: usize,
+    /// The number of times to run each benchmark, keeping the fastest
+    pub trials: usize,
+}
+
+impl Default for CalibrationParams {
+    fn default() -> Self {
+        Self {
+            iters: 100_000,
+            copies: 64,
+            trials: 5,
+        }
+    }
+}
+
+/// An operator's price alongside how long it takes to execute.
+#[derive(Clone, Debug)]
+pub struct Calibration {
+    pub op: OperatorCode,
+    /// The ink charged for the operator as benchmarked
+    pub ink: u64,
+    /// The nanoseconds per op under singlepass, if the op has a benchmark
+    pub singlepass: Option<f64>,
+    /// The nanoseconds per op under Cranelift, if the op has a benchmark
+    pub cranelift: Option<f64>,
+}
+
+impl Calibration {
+    /// The ink charged per nanosecond of singlepass execution.
+    pub fn ink_per_ns(&self) -> Option<f64> {
+        self.singlepass
+            .filter(|ns| *ns > 0.)
+            .map(|ns| self.ink as f64 / ns)
+    }
+}
+
+/// Benchmarks each of the given operators under both compilers.
+pub fn calibrate(
+    costs: &OpCosts,
+    ops: &[OperatorCode],
+    params: CalibrationParams,
+) -> Result<Vec<Calibration>> {
+    let time_ns = |compiler: Compiler, body: &str, base: Duration| -> Result<f64> {
+        let time = time(compiler, &bench_wat(body, params.copies), params)?;
+        let runs = params.iters as f64 * params.copies as f64;
+        Ok(time.saturating_sub(base).as_nanos() as f64 / runs)
+    };
+
+    let single_base = time(Compiler::Singlepass, &bench_wat("", 0), params)?;
+    let crane_base = time(Compiler::Cranelift, &bench_wat("", 0), params)?;
+
+    let mut calibrations = vec![];
+    for &op in ops {
+        let ink = match costs.ops.get(&op) {
+            Some(ink) if op.to_string() == "BrTable" => ink.saturating_add(costs.br_table_target),
+            Some(ink) => *ink,
+            None => continue,
+        };
+        let snippet = snippet(op);
+        let measure = |compiler, base| {
+            let time = snippet.as_ref().map(|body| time_ns(compiler, body, base));
+            let time = time.transpose();
+            time.wrap_err_with(|| format!("failed to benchmark {op} under {compiler:?}"))
+        };
+        calibrations.push(Calibration {
+            op,
+            ink,
+            singlepass: measure(Compiler::Singlepass, single_base)?,
+            cranelift: measure(Compiler::Cranelift, crane_base)?,
+        });
+    }
+    Ok(calibrations)
+}
+
+/// Compiles and runs a benchmark, returning its fastest time.
+fn time(compiler: Compiler, wat: &str, params: CalibrationParams) -> Result<Duration> {
+    let mut store = compiler.store();
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &Imports::new())?;
+    let exports = &instance.exports;
+    let bench = exports.get_typed_function::<(i32, i32), ()>(&store, "bench")?;
+
+    if params.iters == 0 || params.trials == 0 {
+        bail!("nothing to time");
+    }
+    let mut fastest = Duration::MAX;
+    for _ in 0..params.trials {
+        let start = Instant::now();
+        bench.call(&mut store, params.iters as i32, 7)?;
+        fastest = fastest.min(start.elapsed());
+    }
+    Ok(fastest)
+}
+
+/// A module whose `bench` func loops `$n` times, repeating the snippet in each iteration.
+pub fn bench_wat(snippet: &str, copies: usize) -> String {
+    let body = vec![snippet; copies].join("\n            ");
+    format!(
+        r#"
+(module
+    (type $void (func))
+    (memory 1)
+    (table 1 funcref)
+    (elem (i32.const 0) $nop)
+    (data $seg "calibrate")
+    (global $g (mut i32) (i32.const 0))
+    (global $sink32 (mut i32) (i32.const 0))
+    (global $sink64 (mut i64) (i64.const 0))
+    (func $nop)
+    (func $return return)
+    (func (export "bench") (param $n i32) (param $seed i32)
+        (local $n64 i64) (local $addr i32) (local $acc32 i32) (local $acc64 i64)
+        (local $x i32) (local $zero i32)
+        (local.set $acc32 (local.get $seed))
+        (local.set $acc64 (i64.extend_i32_u (local.get $seed)))
+        (loop $loop
+            (local.set $n64 (i64.extend_i32_u (local.get $n)))
+            (local.set $addr (i32.and (local.get $n) (i32.const 4095)))
+            {body}
+            (br_if $loop (local.tee $n (i32.sub (local.get $n) (i32.const 1)))))
+        (global.set $sink32 (local.get $acc32))
+        (global.set $sink64 (local.get $acc64))))
+"#
+    )
+}
+
+/// The wat exercising an operator once, or `None` if it can't be benchmarked.
+pub fn snippet(op: OperatorCode) -> Option<String> {
+    let name = op.to_string();
+    let text = match name.as_str() {
+        "Unreachable" => return None,
+        "Nop" => "nop",
+        "Drop" => "local.get $acc32 drop",
+        "Select" => "local.get $acc32 local.get $n local.get $n select local.set $acc32",
+        "Block" | "End" => "block end",
+        "Loop" => "loop end",
+        "If" => "local.get $n if end",
+        "Else" => "local.get $zero if else end",
+        "Br" => "block br 0 end",
+        "BrIf" => "block local.get $n br_if 0 end",
+        "BrTable" => "block local.get $n br_table 0 0 end",
+        "Return" => "call $return",
+        "Call" => "call $nop",
+        "CallIndirect" => "i32.const 0 call_indirect (type $void)",
+        "LocalGet" => "local.get $n drop",
+        "LocalSet" => "local.get $n local.set $x",
+        "LocalTee" => "local.get $acc32 local.tee $x local.set $acc32",
+        "GlobalGet" => "global.get $g local.set $acc32",
+        "GlobalSet" => "local.get $acc32 global.set $g",
+        "MemorySize" => "memory.size local.set $acc32",
+        "MemoryGrow" => "i32.const 0 memory.grow local.set $acc32",
+        "MemoryCopy" => "local.get $addr local.get $addr i32.const 0 memory.copy",
+        "MemoryFill" => "local.get $addr local.get $acc32 i32.const 0 memory.fill",
+        "MemoryInit" => "local.get $addr i32.const 0 i32.const 0 memory.init $seg",
+        "DataDrop" => "data.drop $seg",
+        _ => return numeric(&name),
+    };
+    Some(text.to_owned())
+}
+
+/// The wat exercising an `i32` or `i64` operator once, or `None` for any other operator.
+/// Float, SIMD, atomic, and conversion ops aren't priced, so they're skipped rather than benchmarked.
+fn numeric(name: &str) -> Option<String> {
+    let ty = match name.get(..3)? {
+        "I32" => "i32",
+        "I64" => "i64",
+        _ => return None,
+    };
+    let kind = &name[3..];
+
+    // convert the name from camel case, as in DivS => div_s
+    let mut op = format!("{ty}.");
+    for (i, c) in kind.chars().enumerate() {
+        if i > 0 && c.is_ascii_uppercase() {
+            op.push('_');
+        }
+        op.push(c.to_ascii_lowercase());
+    }
+
+    let acc = |ty: &str| format!("$acc{}", &ty[1..]);
+    let var = |ty: &str| match ty {
+        "i32" => "$n",
+        _ => "$n64",
+    };
+    let (acc, var) = (acc(ty), var(ty));
+
+    let loads = [
+        "Load", "Load8S", "Load8U", "Load16S", "Load16U", "Load32S", "Load32U",
+    ];
+    let stores = ["Store", "Store8", "Store16", "Store32"];
+    let comparisons = [
+        "Eq", "Ne", "LtS", "LtU", "GtS", "GtU", "LeS", "LeU", "GeS", "GeU",
+    ];
+    let unary = ["Clz", "Ctz", "Popcnt", "Extend8S", "Extend16S", "Extend32S"];
+    let binary = [
+        "Add", "Sub", "Mul", "DivS", "DivU", "RemS", "RemU", "And", "Or", "Xor", "Shl", "ShrS",
+        "ShrU", "Rotl", "Rotr",
+    ];
+
+    let text = match kind {
+        "Const" => format!("{op} 7 local.set {acc}"),
+        "Eqz" => format!("local.get {acc} {op} local.set $acc32"),
+        "WrapI64" => format!("local.get $acc64 {op} local.set $acc32"),
+        "ExtendI32S" | "ExtendI32U" => format!("local.get $acc32 {op} local.set $acc64"),
+        x if loads.contains(&x) => format!("local.get $addr {op} local.set {acc}"),
+        x if stores.contains(&x) => format!("local.get $addr local.get {acc} {op}"),
+        x if comparisons.contains(&x) => {
+            format!("local.get {acc} local.get {var} {op} local.set $acc32")
+        }
+        x if unary.contains(&x) => format!("local.get {acc} {op} local.set {acc}"),
+        x if binary.contains(&x) => format!("local.get {acc} local.get {var} {op} local.set {acc}"),
+        _ => return None,
+    };
+    Some(text)
+}
@@ -50,6 +50,10 @@ where
         &mut self.evm_data.return_data_len
     }
 
+    fn stylus_version(&self) -> u16 {
+        self.config().version
+    }
+
     fn read_fixed<const N: usize>(
         &self,
         ptr: GuestPtr,
@@ -102,6 +106,15 @@ pub(crate) fn read_args<D: DataReader, E: EvmApi<D>>(
     hostio!(env, read_args(ptr))
 }
 
+pub(crate) fn read_args_range<D: DataReader, E: EvmApi<D>>(
+    mut env: WasmEnvMut<D, E>,
+    dest: GuestPtr,
+    offset: u32,
+    size: u32,
+) -> Result<u32, Escape> {
+    hostio!(env, read_args_range(dest, offset, size))
+}
+
 pub(crate) fn write_result<D: DataReader, E: EvmApi<D>>(
     mut env: WasmEnvMut<D, E>,
     ptr: GuestPtr,
@@ -141,6 +154,22 @@ pub(crate) fn storage_flush_cache<D: DataReader, E: EvmApi<D>>(
     hostio!(env, storage_flush_cache(clear != 0))
 }
 
+pub(crate) fn transient_load_bytes32<D: DataReader, E: EvmApi<D>>(
+    mut env: WasmEnvMut<D, E>,
+    key: GuestPtr,
+    dest: GuestPtr,
+) -> MaybeEscape {
+    hostio!(env, transient_load_bytes32(key, dest))
+}
+
+pub(crate) fn transient_store_bytes32<D: DataReader, E: EvmApi<D>>(
+    mut env: WasmEnvMut<D, E>,
+    key: GuestPtr,
+    value: GuestPtr,
+) -> MaybeEscape {
+    hostio!(env, transient_store_bytes32(key, value))
+}
+
 pub(crate) fn call_contract<D: DataReader, E: EvmApi<D>>(
     mut env: WasmEnvMut<D, E>,
     contract: GuestPtr,
@@ -270,6 +299,14 @@ pub(crate) fn account_codehash<D: DataReader, E: EvmApi<D>>(
     hostio!(env, account_codehash(address, ptr))
 }
 
+pub(crate) fn blob_hash<D: DataReader, E: EvmApi<D>>(
+    mut env: WasmEnvMut<D, E>,
+    index: u32,
+    dest: GuestPtr,
+) -> MaybeEscape {
+    hostio!(env, blob_hash(index, dest))
+}
+
 pub(crate) fn block_basefee<D: DataReader, E: EvmApi<D>>(
     mut env: WasmEnvMut<D, E>,
     ptr: GuestPtr,
@@ -277,6 +314,13 @@ pub(crate) fn block_basefee<D: DataReader, E: EvmApi<D>>(
     hostio!(env, block_basefee(ptr))
 }
 
+pub(crate) fn block_blob_basefee<D: DataReader, E: EvmApi<D>>(
+    mut env: WasmEnvMut<D, E>,
+    ptr: GuestPtr,
+) -> MaybeEscape {
+    hostio!(env, block_blob_basefee(ptr))
+}
+
 pub(crate) fn block_coinbase<D: DataReader, E: EvmApi<D>>(
     mut env: WasmEnvMut<D, E>,
     ptr: GuestPtr,
@@ -290,12 +334,27 @@ pub(crate) fn block_gas_limit<D: DataReader, E: EvmApi<D>>(
     hostio!(env, block_gas_limit())
 }
 
+pub(crate) fn block_hash<D: DataReader, E: EvmApi<D>>(
+    mut env: WasmEnvMut<D, E>,
+    number: u64,
+    dest: GuestPtr,
+) -> MaybeEscape {
+    hostio!(env, block_hash(number, dest))
+}
+
 pub(crate) fn block_number<D: DataReader, E: EvmApi<D>>(
     mut env: WasmEnvMut<D, E>,
 ) -> Result<u64, Escape> {
     hostio!(env, block_number())
 }
 
+pub(crate) fn block_prevrandao<D: DataReader, E: EvmApi<D>>(
+    mut env: WasmEnvMut<D, E>,
+    ptr: GuestPtr,
+) -> MaybeEscape {
+    hostio!(env, block_prevrandao(ptr))
+}
+
 pub(crate) fn block_timestamp<D: DataReader, E: EvmApi<D>>(
     mut env: WasmEnvMut<D, E>,
 ) -> Result<u64, Escape> {
@@ -21,9 +21,13 @@ use std::{marker::PhantomData, mem, ptr};
 pub use brotli;
 pub use prover;
 
+pub mod cache;
+#[cfg(feature = "calibrate")]
+pub mod calibrate;
 pub mod env;
 mod evm_api;
 pub mod host;
+pub mod mock;
 pub mod native;
 pub mod run;
 
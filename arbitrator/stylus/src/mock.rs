@@ -0,0 +1,662 @@
+// Copyright 2022-2024, Offchain Labs, Inc.
+// For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+//! An in-memory EVM for running Stylus programs natively, entirely from Rust.
+//!
+//! The world state is a map from addresses to [`MockAccount`]s, each of which has a balance,
+//! a nonce, persistent storage, and optionally a deployed Stylus program. Programs may call
+//! one another, and may deploy new programs via `create1` and `create2`, whose addresses are
+//! derived from the deployer's nonce and the salt exactly as they are onchain. Value sent with
+//! a call or endowed to a deployment is transferred from the sender, failing if it can't pay.
+//!
+//! Every change to the world is recorded in a journal, so that, as in geth, the effects of a call
+//! that reverts or otherwise fails are rolled back without affecting its caller. Storage writes are
+//! cached per call frame and only reach the world when flushed via `storage_flush_cache`. Programs
+//! entered via `static_call` are read-only, so any attempt of theirs to modify the world fails.
+//!
+//! Note that the mock deviates from onchain behavior in a number of ways. Most notably, gas
+//! costs are approximations, and the code passed to `create1` and `create2` is taken to be the
+//! wasm of the program to deploy, with no constructor.
+
+use crate::{native::NativeInstance, run::RunProgram};
+use arbutil::{
+    crypto,
+    evm::{
+        self,
+        api::{EvmApi, VecReader},
+        user::{UserOutcome, UserOutcomeKind},
+        EvmData,
+    },
+    Bytes20, Bytes32,
+};
+use eyre::{bail, eyre, Result};
+use parking_lot::{Mutex, MutexGuard};
+use prover::{
+    binary,
+    programs::{memory::MemoryModel, prelude::*},
+    simd,
+};
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+/// A native instance whose EVM is the in-memory [`MockEvmApi`].
+pub type MockInstance = NativeInstance<VecReader, MockEvmApi>;
+
+/// A log emitted by a program, decoded from the raw `emit_log` request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockLog {
+    /// The program that emitted the log.
+    pub address: Bytes20,
+    /// Up to 4 indexed topics.
+    pub topics: Vec<Bytes32>,
+    /// The log's unindexed data.
+    pub data: Vec<u8>,
+}
+
+/// A Stylus program deployed to an account.
+#[derive(Clone, Debug)]
+pub struct MockProgram {
+    /// The program's wasm, which is what other programs see as the account's code.
+    pub wasm: Arc<[u8]>,
+    /// The natively compiled module.
+    pub module: Arc<[u8]>,
+    /// The runtime config to execute the program with.
+    pub config: StylusConfig,
+}
+
+/// The state of a single account.
+#[derive(Clone, Debug, Default)]
+pub struct MockAccount {
+    /// The account's balance in wei.
+    pub balance: Bytes32,
+    /// The number of contracts this account has deployed, plus 1 if it's itself a contract.
+    pub nonce: u64,
+    /// The account's program, if any.
+    pub program: Option<MockProgram>,
+    /// The account's persistent storage.
+    pub storage: HashMap<Bytes32, Bytes32>,
+}
+
+/// A hostio captured while tracing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockHostio {
+    /// The program that called the hostio.
+    pub address: Bytes20,
+    pub name: String,
+    pub args: Vec<u8>,
+    pub outs: Vec<u8>,
+    pub start_ink: u64,
+    pub end_ink: u64,
+}
+
+/// The world state shared by every call frame.
+///
+/// Changes made via methods are journaled and may be undone with [`MockWorld::revert`].
+#[derive(Clone, Debug, Default)]
+pub struct MockWorld {
+    /// Every account that has been touched.
+    pub accounts: HashMap<Bytes20, MockAccount>,
+    /// Transient storage, which lives for the duration of the transaction.
+    pub transient: HashMap<Bytes20, HashMap<Bytes32, Bytes32>>,
+    /// The logs emitted so far, in the order they were emitted.
+    pub logs: Vec<MockLog>,
+    /// The hostios captured while tracing, which, like a tracer's, survive reverts.
+    pub hostios: Vec<MockHostio>,
+    /// The changes made so far, each with the prior state needed to undo it.
+    journal: Vec<JournalEntry>,
+}
+
+/// A reversible change to the world state.
+#[derive(Clone, Debug)]
+enum JournalEntry {
+    Storage(Bytes20, Bytes32, Option<Bytes32>),
+    Transient(Bytes20, Bytes32, Option<Bytes32>),
+    Balance(Bytes20, Bytes32),
+    Nonce(Bytes20, u64),
+    Program(Bytes20, Option<MockProgram>),
+    Log,
+}
+
+impl MockWorld {
+    /// Marks the current state so that it may later be returned to.
+    pub fn snapshot(&self) -> usize {
+        self.journal.len()
+    }
+
+    /// Undoes every change made since the given snapshot was taken.
+    pub fn revert(&mut self, snapshot: usize) {
+        use JournalEntry::*;
+
+        while self.journal.len() > snapshot {
+            match self.journal.pop().unwrap() {
+                Storage(address, key, prev) => {
+                    let storage = &mut self.account(address).storage;
+                    match prev {
+                        Some(value) => drop(storage.insert(key, value)),
+                        None => drop(storage.remove(&key)),
+                    }
+                }
+                Transient(address, key, prev) => {
+                    let transient = self.transient.entry(address).or_default();
+                    match prev {
+                        Some(value) => drop(transient.insert(key, value)),
+                        None => drop(transient.remove(&key)),
+                    }
+                }
+                Balance(address, prev) => self.account(address).balance = prev,
+                Nonce(address, prev) => self.account(address).nonce = prev,
+                Program(address, prev) => self.account(address).program = prev,
+                Log => drop(self.logs.pop()),
+            }
+        }
+    }
+
+    pub fn set_storage(&mut self, address: Bytes20, key: Bytes32, value: Bytes32) {
+        let prev = self.account(address).storage.insert(key, value);
+        self.journal.push(JournalEntry::Storage(address, key, prev));
+    }
+
+    pub fn set_transient(&mut self, address: Bytes20, key: Bytes32, value: Bytes32) {
+        let transient = self.transient.entry(address).or_default();
+        let prev = transient.insert(key, value);
+        let entry = JournalEntry::Transient(address, key, prev);
+        self.journal.push(entry);
+    }
+
+    pub fn set_balance(&mut self, address: Bytes20, balance: Bytes32) {
+        let prev = std::mem::replace(&mut self.account(address).balance, balance);
+        self.journal.push(JournalEntry::Balance(address, prev));
+    }
+
+    pub fn set_nonce(&mut self, address: Bytes20, nonce: u64) {
+        let prev = std::mem::replace(&mut self.account(address).nonce, nonce);
+        self.journal.push(JournalEntry::Nonce(address, prev));
+    }
+
+    pub fn set_program(&mut self, address: Bytes20, program: Option<MockProgram>) {
+        let prev = std::mem::replace(&mut self.account(address).program, program);
+        self.journal.push(JournalEntry::Program(address, prev));
+    }
+
+    pub fn balance(&self, address: Bytes20) -> Bytes32 {
+        let account = self.accounts.get(&address);
+        account.map(|x| x.balance).unwrap_or_default()
+    }
+
+    /// Moves `value` wei between accounts, returning `false` if the sender can't afford it.
+    pub fn transfer(&mut self, from: Bytes20, to: Bytes20, value: Bytes32) -> bool {
+        if value == Bytes32::default() {
+            return true;
+        }
+        let Some(remaining) = sub_words(self.balance(from), value) else {
+            return false;
+        };
+        self.set_balance(from, remaining);
+        self.set_balance(to, add_words(self.balance(to), value));
+        true
+    }
+
+    pub fn push_log(&mut self, log: MockLog) {
+        self.logs.push(log);
+        self.journal.push(JournalEntry::Log);
+    }
+
+    fn account(&mut self, address: Bytes20) -> &mut MockAccount {
+        self.accounts.entry(address).or_default()
+    }
+}
+
+/// An [`EvmApi`] backed by an in-memory [`MockWorld`].
+///
+/// Clones share the same world state, which allows inspecting it after a program has run.
+#[derive(Clone, Debug)]
+pub struct MockEvmApi {
+    world: Arc<Mutex<MockWorld>>,
+    program: Bytes20,
+    cache: HashMap<Bytes32, Bytes32>,
+    return_data: Vec<u8>,
+    compile: CompileConfig,
+    evm_data: EvmData,
+    pages: Arc<Mutex<(u16, u16)>>,
+    read_only: bool,
+}
+
+impl MockEvmApi {
+    pub fn new(compile: CompileConfig) -> (MockEvmApi, EvmData) {
+        let program = Bytes20::default();
+        let evm_data = EvmData::default();
+
+        let api = MockEvmApi {
+            world: Arc::new(Mutex::new(MockWorld::default())),
+            program,
+            cache: HashMap::new(),
+            return_data: vec![],
+            compile,
+            evm_data,
+            pages: Arc::new(Mutex::new((0, 0))),
+            read_only: false,
+        };
+        (api, evm_data)
+    }
+
+    /// Provides access to the world state.
+    pub fn world(&self) -> MutexGuard<'_, MockWorld> {
+        self.world.lock()
+    }
+
+    /// Sets the context programs see when instantiated via [`MockEvmApi::instance`].
+    pub fn set_evm_data(&mut self, evm_data: EvmData) {
+        self.evm_data = evm_data;
+    }
+
+    /// Compiles and deploys a program to the given address, replacing any existing code.
+    pub fn deploy_wasm(
+        &mut self,
+        address: Bytes20,
+        wasm: &[u8],
+        config: StylusConfig,
+    ) -> Result<()> {
+        let lowered = simd::lower_user(wasm, self.compile.version)?;
+        let bin = binary::parse(&lowered, Path::new("user"))?;
+        let module = crate::native::module(&lowered, &bin, self.compile.clone())?;
+        let program = MockProgram {
+            wasm: wasm.into(),
+            module: module.into(),
+            config,
+        };
+        self.world().set_program(address, Some(program));
+        Ok(())
+    }
+
+    /// Instantiates the program at the given address, as though called directly by the tx origin.
+    pub fn instance(&self, address: Bytes20) -> Result<MockInstance> {
+        let Some(program) = self.program(address) else {
+            bail!("no program at {address}")
+        };
+        let mut evm_data = self.evm_data;
+        evm_data.contract_address = address;
+
+        let mut api = self.clone();
+        api.program = address;
+        api.evm_data = evm_data;
+        api.cache.clear();
+        api.return_data.clear();
+
+        let compile = self.compile.clone();
+        let mut native =
+            unsafe { MockInstance::deserialize(&program.module, compile, api, evm_data)? };
+        native.env_mut().config = Some(program.config);
+        Ok(native)
+    }
+
+    pub fn balance(&self, address: Bytes20) -> Bytes32 {
+        self.world().balance(address)
+    }
+
+    pub fn set_balance(&mut self, address: Bytes20, balance: Bytes32) {
+        self.world().set_balance(address, balance);
+    }
+
+    pub fn nonce(&self, address: Bytes20) -> u64 {
+        let world = self.world();
+        let account = world.accounts.get(&address);
+        account.map(|x| x.nonce).unwrap_or_default()
+    }
+
+    /// Reads the storage of any account.
+    pub fn storage(&self, address: Bytes20, key: Bytes32) -> Bytes32 {
+        let world = self.world();
+        let account = world.accounts.get(&address);
+        let value = account.and_then(|x| x.storage.get(&key));
+        value.cloned().unwrap_or_default()
+    }
+
+    /// Returns the logs emitted so far, across all programs, in the order they were emitted.
+    pub fn logs(&self) -> Vec<MockLog> {
+        self.world().logs.clone()
+    }
+
+    /// Returns the hostios captured so far, across all programs, when [`EvmData::tracing`] is set.
+    pub fn hostios(&self) -> Vec<MockHostio> {
+        self.world().hostios.clone()
+    }
+
+    pub fn set_pages(&mut self, open: u16) {
+        let mut pages = self.pages.lock();
+        pages.0 = open;
+        pages.1 = open.max(pages.1);
+    }
+
+    fn program(&self, address: Bytes20) -> Option<MockProgram> {
+        let world = self.world();
+        world.accounts.get(&address)?.program.clone()
+    }
+
+    /// Runs the code at `code_address` in the context of `contract`, rolling back on failure.
+    /// Any `value` is first transferred from the current program to `contract`.
+    /// Read-only programs can neither send value nor make calls that modify the world.
+    fn call(
+        &mut self,
+        contract: Bytes20,
+        code_address: Bytes20,
+        evm_data: EvmData,
+        calldata: &[u8],
+        gas: u64,
+        value: Bytes32,
+    ) -> (u32, u64, UserOutcomeKind) {
+        self.return_data.clear();
+
+        if self.read_only && value != Bytes32::default() {
+            return (0, 0, UserOutcomeKind::Failure); // write protection
+        }
+
+        let snapshot = self.world().snapshot();
+        if !self.world().transfer(self.program, contract, value) {
+            return (0, 0, UserOutcomeKind::Failure); // insufficient funds
+        }
+
+        // calls to accounts without code always succeed
+        let Some(program) = self.program(code_address) else {
+            return (0, 0, UserOutcomeKind::Success);
+        };
+
+        let mut api = self.clone();
+        api.program = contract;
+        api.evm_data = evm_data;
+        api.cache.clear();
+        api.return_data.clear();
+
+        let compile = self.compile.clone();
+        let config = program.config;
+        let native = unsafe { MockInstance::deserialize(&program.module, compile, api, evm_data) };
+        let Ok(mut native) = native else {
+            self.world().revert(snapshot);
+            return (0, gas, UserOutcomeKind::Failure);
+        };
+
+        let ink = config.pricing.gas_to_ink(gas);
+        let outcome = match native.run_main(calldata, config, ink) {
+            Ok(outcome) => outcome,
+            Err(error) => UserOutcome::Failure(error),
+        };
+        let (status, outs) = outcome.into_data();
+        let outs_len = outs.len() as u32;
+
+        if status != UserOutcomeKind::Success {
+            self.world().revert(snapshot);
+        }
+
+        let ink_left: u64 = native.ink_left().into();
+        let gas_left = config.pricing.ink_to_gas(ink_left);
+        self.return_data = outs;
+        (outs_len, gas.saturating_sub(gas_left), status)
+    }
+
+    fn create(
+        &mut self,
+        code: Vec<u8>,
+        endowment: Bytes32,
+        salt: Option<Bytes32>,
+        gas: u64,
+    ) -> (Result<Bytes20>, u32, u64) {
+        self.return_data.clear();
+
+        if self.read_only {
+            return (Err(eyre!("write protection")), 0, gas);
+        }
+        if self.balance(self.program) < endowment {
+            return (Err(eyre!("insufficient balance for transfer")), 0, 0);
+        }
+        let nonce = self.nonce(self.program);
+        self.world().set_nonce(self.program, nonce + 1);
+
+        let address = match salt {
+            Some(salt) => create2_address(self.program, salt, &code),
+            None => create1_address(self.program, nonce),
+        };
+        if self.program(address).is_some() {
+            return (Err(eyre!("contract address collision")), 0, gas);
+        }
+
+        // deployed programs inherit the deployer's config
+        let config = self.program(self.program).map(|x| x.config);
+        let config = config.unwrap_or_default();
+        if let Err(error) = self.deploy_wasm(address, &code, config) {
+            return (Err(error), 0, gas);
+        }
+        self.world().set_nonce(address, 1); // EIP-161
+        self.world().transfer(self.program, address, endowment);
+        (Ok(address), 0, 0) // no constructor to run
+    }
+}
+
+/// Adds two big-endian words, wrapping on overflow.
+fn add_words(a: Bytes32, b: Bytes32) -> Bytes32 {
+    let mut sum = [0; 32];
+    let mut carry = 0;
+    for i in (0..32).rev() {
+        let digit = a[i] as u16 + b[i] as u16 + carry;
+        sum[i] = digit as u8;
+        carry = digit >> 8;
+    }
+    sum.into()
+}
+
+/// Subtracts two big-endian words, returning `None` on underflow.
+fn sub_words(a: Bytes32, b: Bytes32) -> Option<Bytes32> {
+    if a < b {
+        return None;
+    }
+    let mut diff = [0; 32];
+    let mut borrow = 0;
+    for i in (0..32).rev() {
+        let digit = 0x100 + a[i] as u16 - b[i] as u16 - borrow;
+        diff[i] = digit as u8;
+        borrow = 1 - (digit >> 8);
+    }
+    Some(diff.into())
+}
+
+/// Computes the address of a contract deployed via `CREATE`.
+pub fn create1_address(deployer: Bytes20, nonce: u64) -> Bytes20 {
+    // rlp([deployer, nonce])
+    let nonce_bytes = nonce.to_be_bytes();
+    let nonce_bytes = &nonce_bytes[nonce.leading_zeros() as usize / 8..];
+
+    let mut rlp = vec![0x80 + 20];
+    rlp.extend(deployer);
+    match nonce {
+        1..=0x7f => rlp.push(nonce as u8),
+        _ => {
+            rlp.push(0x80 + nonce_bytes.len() as u8);
+            rlp.extend(nonce_bytes);
+        }
+    }
+    rlp.insert(0, 0xc0 + rlp.len() as u8);
+
+    let hash = crypto::keccak(rlp);
+    hash[12..].try_into().unwrap()
+}
+
+/// Computes the address of a contract deployed via `CREATE2`.
+pub fn create2_address(deployer: Bytes20, salt: Bytes32, code: &[u8]) -> Bytes20 {
+    let mut preimage = vec![0xff];
+    preimage.extend(deployer);
+    preimage.extend(salt);
+    preimage.extend(crypto::keccak(code));
+
+    let hash = crypto::keccak(preimage);
+    hash[12..].try_into().unwrap()
+}
+
+impl EvmApi<VecReader> for MockEvmApi {
+    fn get_bytes32(&mut self, key: Bytes32) -> (Bytes32, u64) {
+        let value = match self.cache.get(&key) {
+            Some(value) => *value,
+            None => self.storage(self.program, key),
+        };
+        (value, 2100) // pretend worst case
+    }
+
+    fn cache_bytes32(&mut self, key: Bytes32, value: Bytes32) -> u64 {
+        self.cache.insert(key, value);
+        0
+    }
+
+    fn flush_storage_cache(&mut self, _clear: bool, _gas_left: u64) -> Result<u64> {
+        if self.read_only && !self.cache.is_empty() {
+            bail!("write protection");
+        }
+        let slots = self.cache.len() as u64;
+        let mut world = self.world.lock();
+        for (key, value) in self.cache.drain() {
+            world.set_storage(self.program, key, value);
+        }
+        Ok(22100 * slots) // pretend worst case
+    }
+
+    fn get_transient_bytes32(&mut self, key: Bytes32) -> (Bytes32, u64) {
+        let world = self.world();
+        let transient = world.transient.get(&self.program);
+        let value = transient.and_then(|x| x.get(&key)).cloned();
+        (value.unwrap_or_default(), evm::TLOAD_GAS)
+    }
+
+    fn set_transient_bytes32(&mut self, key: Bytes32, value: Bytes32) -> Result<u64> {
+        if self.read_only {
+            bail!("write protection");
+        }
+        self.world().set_transient(self.program, key, value);
+        Ok(evm::TSTORE_GAS)
+    }
+
+    fn contract_call(
+        &mut self,
+        contract: Bytes20,
+        calldata: &[u8],
+        gas: u64,
+        value: Bytes32,
+    ) -> (u32, u64, UserOutcomeKind) {
+        let mut evm_data = self.evm_data;
+        evm_data.contract_address = contract;
+        evm_data.msg_sender = self.program;
+        evm_data.msg_value = value;
+        self.call(contract, contract, evm_data, calldata, gas, value)
+    }
+
+    fn delegate_call(
+        &mut self,
+        contract: Bytes20,
+        calldata: &[u8],
+        gas: u64,
+    ) -> (u32, u64, UserOutcomeKind) {
+        let (evm_data, value) = (self.evm_data, Bytes32::default());
+        self.call(self.program, contract, evm_data, calldata, gas, value)
+    }
+
+    fn static_call(
+        &mut self,
+        contract: Bytes20,
+        calldata: &[u8],
+        gas: u64,
+    ) -> (u32, u64, UserOutcomeKind) {
+        // the callee, and any program it calls, inherits the flag via clones of this api
+        let read_only = std::mem::replace(&mut self.read_only, true);
+        let result = self.contract_call(contract, calldata, gas, Bytes32::default());
+        self.read_only = read_only;
+        result
+    }
+
+    fn create1(
+        &mut self,
+        code: Vec<u8>,
+        endowment: Bytes32,
+        gas: u64,
+    ) -> (Result<Bytes20>, u32, u64) {
+        self.create(code, endowment, None, gas)
+    }
+
+    fn create2(
+        &mut self,
+        code: Vec<u8>,
+        endowment: Bytes32,
+        salt: Bytes32,
+        gas: u64,
+    ) -> (Result<Bytes20>, u32, u64) {
+        self.create(code, endowment, Some(salt), gas)
+    }
+
+    fn get_return_data(&self) -> VecReader {
+        VecReader::new(self.return_data.clone())
+    }
+
+    fn emit_log(&mut self, data: Vec<u8>, topics: u32) -> Result<()> {
+        if self.read_only {
+            bail!("write protection");
+        }
+        let (topics, data) = data.split_at(32 * topics as usize);
+        let topics = topics.chunks(32).map(|x| x.try_into().unwrap()).collect();
+        let log = MockLog {
+            address: self.program,
+            topics,
+            data: data.to_vec(),
+        };
+        self.world().push_log(log);
+        Ok(())
+    }
+
+    fn account_balance(&mut self, address: Bytes20) -> (Bytes32, u64) {
+        (self.balance(address), evm::COLD_ACCOUNT_GAS)
+    }
+
+    fn account_code(&mut self, address: Bytes20, _gas_left: u64) -> (VecReader, u64) {
+        let code = self.program(address).map(|x| x.wasm.to_vec());
+        let code = VecReader::new(code.unwrap_or_default());
+        (code, evm::COLD_ACCOUNT_GAS)
+    }
+
+    fn account_codehash(&mut self, address: Bytes20) -> (Bytes32, u64) {
+        let hash = match self.program(address) {
+            Some(program) => crypto::keccak(&program.wasm).into(),
+            None => Bytes32::default(),
+        };
+        (hash, evm::COLD_ACCOUNT_GAS)
+    }
+
+    fn block_hash(&mut self, number: u64) -> (Bytes32, u64) {
+        let hash = evm::mock::block_hash(number, self.evm_data.block_number);
+        (hash, evm::BLOCKHASH_GAS)
+    }
+
+    fn blob_hash(&mut self, index: u32) -> (Bytes32, u64) {
+        (evm::mock::blob_hash(index), evm::BLOBHASH_GAS)
+    }
+
+    fn add_pages(&mut self, new: u16) -> u64 {
+        let model = MemoryModel::new(2, 1000);
+        let (open, ever) = *self.pages.lock();
+
+        let mut pages = self.pages.lock();
+        pages.0 = pages.0.saturating_add(new);
+        pages.1 = pages.1.max(pages.0);
+        model.gas_cost(new, open, ever)
+    }
+
+    fn capture_hostio(
+        &mut self,
+        name: &str,
+        args: &[u8],
+        outs: &[u8],
+        start_ink: u64,
+        end_ink: u64,
+    ) {
+        let hostio = MockHostio {
+            address: self.program,
+            name: name.to_owned(),
+            args: args.to_vec(),
+            outs: outs.to_vec(),
+            start_ink,
+            end_ink,
+        };
+        self.world().hostios.push(hostio);
+    }
+}
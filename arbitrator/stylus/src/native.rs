@@ -2,6 +2,7 @@
 // For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
 
 use crate::{
+    cache::ArtifactCache,
     env::{MeterData, WasmEnv},
     host,
 };
@@ -15,6 +16,7 @@ use arbutil::{
 };
 use eyre::{bail, eyre, ErrReport, Result};
 use prover::{
+    binary::{self, WasmBinary},
     machine::Module as ProverModule,
     programs::{
         config::PricingParams,
@@ -22,19 +24,24 @@ use prover::{
         depth::STYLUS_STACK_LEFT,
         meter::{STYLUS_INK_LEFT, STYLUS_INK_STATUS},
         prelude::*,
+        profiler::{FuncProfile, InkProfile, Profiler, ProfilingMachine},
         start::STYLUS_START,
         StylusData,
     },
+    simd,
 };
 use std::{
+    borrow::Cow,
     collections::BTreeMap,
     fmt::Debug,
     ops::{Deref, DerefMut},
+    path::Path,
 };
 use wasmer::{
     imports, AsStoreMut, Function, FunctionEnv, Instance, Memory, Module, Pages, Store,
     TypedFunction, Value, WasmTypeList,
 };
+use wasmer_types::{entity::EntityRef, ImportIndex};
 use wasmer_vm::VMExtern;
 
 #[derive(Debug)]
@@ -112,7 +119,9 @@ impl<D: DataReader, E: EvmApi<D>> NativeInstance<D, E> {
         let env = WasmEnv::new(compile.clone(), Some(config), evm_api, evm_data);
         let store = env.compile.store();
         let wat_or_wasm = std::fs::read(path)?;
-        let module = Module::new(&store, wat_or_wasm)?;
+        let wasm = wasmer::wat2wasm(&wat_or_wasm)?;
+        let wasm = simd::lower_user(&wasm, compile.version)?;
+        let module = Module::new(&store, binary::fold_const_exprs(&wasm)?)?;
         Self::from_module(module, store, env)
     }
 
@@ -127,11 +136,14 @@ impl<D: DataReader, E: EvmApi<D>> NativeInstance<D, E> {
         let mut imports = imports! {
             "vm_hooks" => {
                 "read_args" => func!(host::read_args),
+                "read_args_range" => func!(host::read_args_range),
                 "write_result" => func!(host::write_result),
                 "exit_early" => func!(host::exit_early),
                 "storage_load_bytes32" => func!(host::storage_load_bytes32),
                 "storage_cache_bytes32" => func!(host::storage_cache_bytes32),
                 "storage_flush_cache" => func!(host::storage_flush_cache),
+                "transient_load_bytes32" => func!(host::transient_load_bytes32),
+                "transient_store_bytes32" => func!(host::transient_store_bytes32),
                 "call_contract" => func!(host::call_contract),
                 "delegate_call_contract" => func!(host::delegate_call_contract),
                 "static_call_contract" => func!(host::static_call_contract),
@@ -146,11 +158,15 @@ impl<D: DataReader, E: EvmApi<D>> NativeInstance<D, E> {
                 "account_code_size" => func!(host::account_code_size),
                 "evm_gas_left" => func!(host::evm_gas_left),
                 "evm_ink_left" => func!(host::evm_ink_left),
+                "blob_hash" => func!(host::blob_hash),
                 "block_basefee" => func!(host::block_basefee),
+                "block_blob_basefee" => func!(host::block_blob_basefee),
                 "chainid" => func!(host::chainid),
                 "block_coinbase" => func!(host::block_coinbase),
                 "block_gas_limit" => func!(host::block_gas_limit),
+                "block_hash" => func!(host::block_hash),
                 "block_number" => func!(host::block_number),
+                "block_prevrandao" => func!(host::block_prevrandao),
                 "block_timestamp" => func!(host::block_timestamp),
                 "contract_address" => func!(host::contract_address),
                 "msg_reentrant" => func!(host::msg_reentrant),
@@ -291,6 +307,40 @@ impl<D: DataReader, E: EvmApi<D>> CountingMachine for NativeInstance<D, E> {
     }
 }
 
+impl<D: DataReader, E: EvmApi<D>> ProfilingMachine for NativeInstance<D, E> {
+    fn ink_profile(&mut self) -> Result<InkProfile> {
+        let info = self.module().info().clone();
+
+        let mut funcs = BTreeMap::new();
+        for (index, _) in info.functions.iter() {
+            let func = index.as_u32();
+            let mut profile = FuncProfile {
+                name: info.function_names.get(&index).cloned().unwrap_or_default(),
+                calls: self.get_global(&Profiler::calls_name(func))?,
+                ink: self.get_global(&Profiler::ink_name(func))?,
+                ..Default::default()
+            };
+            if index.index() < info.num_imported_functions {
+                for (key, import) in &info.imports {
+                    if matches!(import, ImportIndex::Function(f) if *f == index) {
+                        profile.name = key.field.clone();
+                        profile.import = Some(key.module.clone());
+                    }
+                }
+            } else {
+                let slot = |block| Profiler::block_name(func, block);
+                while let Ok(ink) = self.get_global(&slot(profile.blocks.len() as u32)) {
+                    profile.blocks.push(ink);
+                }
+            }
+            if profile.calls != 0 || profile.ink != 0 {
+                funcs.insert(func, profile);
+            }
+        }
+        Ok(InkProfile { funcs })
+    }
+}
+
 impl<D: DataReader, E: EvmApi<D>> DepthCheckedMachine for NativeInstance<D, E> {
     fn stack_left(&mut self) -> u32 {
         self.get_global(STYLUS_STACK_LEFT).unwrap()
@@ -311,7 +361,13 @@ impl<D: DataReader, E: EvmApi<D>> StartlessMachine for NativeInstance<D, E> {
     }
 }
 
-pub fn module(wasm: &[u8], compile: CompileConfig) -> Result<Vec<u8>> {
+/// Compiles a program's native code, given its parsed binary.
+/// Any SIMD must already have been lowered via [`simd::lower_user`].
+pub fn module(wasm: &[u8], bin: &WasmBinary, compile: CompileConfig) -> Result<Vec<u8>> {
+    let wasm = match bin.extended_consts {
+        true => binary::fold_const_exprs(wasm)?,
+        false => Cow::Borrowed(wasm),
+    };
     let mut store = compile.store();
     let module = Module::new(&store, wasm)?;
     macro_rules! stub {
@@ -337,11 +393,14 @@ pub fn module(wasm: &[u8], compile: CompileConfig) -> Result<Vec<u8>> {
     let mut imports = imports! {
         "vm_hooks" => {
             "read_args" => stub!(|_: u32|),
+            "read_args_range" => stub!(u32 <- |_: u32, _: u32, _: u32|),
             "write_result" => stub!(|_: u32, _: u32|),
             "exit_early" => stub!(|_: u32|),
             "storage_load_bytes32" => stub!(|_: u32, _: u32|),
             "storage_cache_bytes32" => stub!(|_: u32, _: u32|),
             "storage_flush_cache" => stub!(|_: u32|),
+            "transient_load_bytes32" => stub!(|_: u32, _: u32|),
+            "transient_store_bytes32" => stub!(|_: u32, _: u32|),
             "call_contract" => stub!(u8 <- |_: u32, _: u32, _: u32, _: u32, _: u64, _: u32|),
             "delegate_call_contract" => stub!(u8 <- |_: u32, _: u32, _: u32, _: u64, _: u32|),
             "static_call_contract" => stub!(u8 <- |_: u32, _: u32, _: u32, _: u64, _: u32|),
@@ -356,11 +415,15 @@ pub fn module(wasm: &[u8], compile: CompileConfig) -> Result<Vec<u8>> {
             "account_code_size" => stub!(u32 <- |_: u32|),
             "evm_gas_left" => stub!(u64 <- ||),
             "evm_ink_left" => stub!(u64 <- ||),
+            "blob_hash" => stub!(|_: u32, _: u32|),
             "block_basefee" => stub!(|_: u32|),
+            "block_blob_basefee" => stub!(|_: u32|),
             "chainid" => stub!(u64 <- ||),
             "block_coinbase" => stub!(|_: u32|),
             "block_gas_limit" => stub!(u64 <- ||),
+            "block_hash" => stub!(|_: u64, _: u32|),
             "block_number" => stub!(u64 <- ||),
+            "block_prevrandao" => stub!(|_: u32|),
             "block_timestamp" => stub!(u64 <- ||),
             "contract_address" => stub!(|_: u32|),
             "msg_reentrant" => stub!(u32 <- ||),
@@ -399,8 +462,33 @@ pub fn activate(
     gas: &mut u64,
 ) -> Result<(Vec<u8>, ProverModule, StylusData)> {
     let compile = CompileConfig::version(version, debug);
-    let (module, stylus_data) = ProverModule::activate(wasm, version, page_limit, debug, gas)?;
+    let wasm = simd::lower_user(wasm, version)?;
+    let (bin, module, stylus_data) =
+        ProverModule::activate_binary(&wasm, version, page_limit, debug, gas)?;
 
-    let asm = self::module(wasm, compile).expect("failed to generate stylus module");
+    let asm = self::module(&wasm, &bin, compile).expect("failed to generate stylus module");
     Ok((asm, module, stylus_data))
 }
+
+/// Compiles an activated program's native code, reusing the cached artifact if one exists.
+pub fn compile(
+    wasm: &[u8],
+    compile: CompileConfig,
+    cache: Option<&ArtifactCache>,
+) -> Result<Vec<u8>> {
+    let module = |compile: CompileConfig| {
+        let wasm = simd::lower_user(wasm, compile.version)?;
+        let bin = binary::parse(&wasm, Path::new("user"))?;
+        self::module(&wasm, &bin, compile)
+    };
+    let Some(cache) = cache else {
+        return module(compile);
+    };
+    let key = ArtifactCache::key(wasm, &compile);
+    if let Some(asm) = cache.get(key) {
+        return Ok(asm);
+    }
+    let asm = module(compile)?;
+    let _ = cache.insert(key, &asm); // the cache is only an optimization
+    Ok(asm)
+}
@@ -80,3 +80,45 @@ fn test_console() -> Result<()> {
     machine.call_user_func(STYLUS_START, vec![], ink)?;
     check_instrumentation(native, machine)
 }
+
+#[test]
+#[cfg(feature = "calibrate")]
+fn test_calibration() -> Result<()> {
+    use crate::calibrate::{self, CalibrationParams};
+    use arbutil::operator::OperatorCode;
+
+    let params = CalibrationParams {
+        iters: 1,
+        copies: 1,
+        trials: 1,
+    };
+
+    // every priced operator but `unreachable` can be benchmarked
+    for version in [1, 2] {
+        let costs = CompileConfig::version(version, false).pricing.costs;
+        let ops: Vec<_> = costs.ops.keys().copied().collect();
+        for calibration in calibrate::calibrate(&costs, &ops, params)? {
+            let measured = calibration.singlepass.is_some() && calibration.cranelift.is_some();
+            assert_eq!(measured, calibration.op.to_string() != "Unreachable");
+        }
+    }
+
+    // operators without a snippet are skipped rather than failing to compile
+    let mut costs = CompileConfig::version(2, false).pricing.costs;
+    let unsupported = [
+        "I32x4Add",
+        "I64x2Mul",
+        "I32TruncF32S",
+        "F64Add",
+        "I64AtomicLoad",
+    ];
+    let ops: Vec<OperatorCode> = unsupported.iter().map(|x| x.parse().unwrap()).collect();
+    costs.ops.extend(ops.iter().map(|op| (*op, 1)));
+
+    let calibrations = calibrate::calibrate(&costs, &ops, params)?;
+    assert_eq!(calibrations.len(), ops.len());
+    for calibration in calibrations {
+        assert!(calibration.singlepass.is_none() && calibration.cranelift.is_none());
+    }
+    Ok(())
+}
@@ -0,0 +1,261 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+use crate::{
+    mock::{create1_address, create2_address, MockEvmApi, MockLog, MockWorld},
+    test::{random_bytes20, random_bytes32, test_configs},
+};
+use arbutil::{
+    evm::{
+        api::{DataReader, EvmApi},
+        user::UserOutcomeKind,
+        EvmData,
+    },
+    Bytes20, Bytes32,
+};
+use eyre::Result;
+
+#[test]
+fn test_create_addresses() -> Result<()> {
+    macro_rules! addr {
+        ($hex:expr) => {
+            Bytes20::try_from(hex::decode($hex)?)?
+        };
+    }
+
+    let deployer = addr!("6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0");
+    let first = addr!("cd234a471b72ba2f1ccf0a70fcaba648a5eecd8d");
+    let second = addr!("343c43a37d37dff08ae8c4a11544c718abb4fcf8");
+    assert_eq!(create1_address(deployer, 0), first);
+    assert_eq!(create1_address(deployer, 1), second);
+
+    // example 0 from EIP-1014
+    let address = addr!("4d1a2e2bb4f88f0250f26ffff098b0b30b26bf38");
+    let (deployer, salt) = (Bytes20::default(), Bytes32::default());
+    assert_eq!(create2_address(deployer, salt, &[0x00]), address);
+    Ok(())
+}
+
+#[test]
+fn test_revert_rollback() -> Result<()> {
+    // in store-and-exit.wat
+    //     the input is a key, a value, and a status code
+    //     the value is written to storage, then the program exits with the status
+
+    let wasm = wasmer::wat2wasm(&std::fs::read("tests/store-and-exit.wat")?)?;
+    let (compile, config, _) = test_configs();
+    let (mut evm, _) = MockEvmApi::new(compile);
+
+    let program = random_bytes20();
+    evm.deploy_wasm(program, &wasm, config)?;
+
+    let key = random_bytes32();
+    let value = random_bytes32();
+
+    let mut args = key.to_vec();
+    args.extend(value);
+
+    // reverting undoes the write
+    args.push(1);
+    let (_, _, status) = evm.contract_call(program, &args, 1_000_000, Bytes32::default());
+    assert_eq!(status, UserOutcomeKind::Revert);
+    assert_eq!(evm.storage(program, key), Bytes32::default());
+
+    // succeeding persists it
+    args[64] = 0;
+    let (_, _, status) = evm.contract_call(program, &args, 1_000_000, Bytes32::default());
+    assert_eq!(status, UserOutcomeKind::Success);
+    assert_eq!(evm.storage(program, key), value);
+    Ok(())
+}
+
+#[test]
+fn test_storage_cache() -> Result<()> {
+    let (compile, _, _) = test_configs();
+    let (mut evm, _) = MockEvmApi::new(compile);
+    let program = Bytes20::default();
+    let key = random_bytes32();
+    let value = random_bytes32();
+
+    // cached writes are visible to the program but not the world
+    evm.cache_bytes32(key, value);
+    assert_eq!(evm.get_bytes32(key).0, value);
+    assert_eq!(evm.storage(program, key), Bytes32::default());
+
+    evm.flush_storage_cache(true, u64::MAX)?;
+    assert_eq!(evm.storage(program, key), value);
+    Ok(())
+}
+
+#[test]
+fn test_journal() {
+    let mut world = MockWorld::default();
+    let address = random_bytes20();
+    let key = random_bytes32();
+    let (first, second) = (random_bytes32(), random_bytes32());
+
+    let outer = world.snapshot();
+    world.set_storage(address, key, first);
+    world.set_balance(address, first);
+
+    let inner = world.snapshot();
+    world.set_storage(address, key, second);
+    world.set_transient(address, key, second);
+    world.set_nonce(address, 1);
+    world.push_log(MockLog {
+        address,
+        topics: vec![],
+        data: vec![],
+    });
+
+    world.revert(inner);
+    let account = &world.accounts[&address];
+    assert_eq!(account.storage[&key], first);
+    assert_eq!(account.balance, first);
+    assert_eq!(account.nonce, 0);
+    assert!(world.transient[&address].is_empty());
+    assert!(world.logs.is_empty());
+
+    world.revert(outer);
+    let account = &world.accounts[&address];
+    assert!(account.storage.is_empty());
+    assert_eq!(account.balance, Bytes32::default());
+}
+
+#[test]
+fn test_value_transfer() -> Result<()> {
+    // in msg-value.wat
+    //     the output is the call's msg_value
+    //
+    // in store-and-exit.wat
+    //     the last byte of the input is the status to exit with
+
+    let wat = |name: &str| -> Result<Vec<u8>> {
+        let wat = std::fs::read(format!("tests/{name}.wat"))?;
+        Ok(wasmer::wat2wasm(&wat)?.to_vec())
+    };
+    let (compile, config, _) = test_configs();
+    let (mut evm, _) = MockEvmApi::new(compile);
+
+    let sender = Bytes20::default();
+    let payable = random_bytes20();
+    let reverts = random_bytes20();
+    evm.deploy_wasm(payable, &wat("msg-value")?, config)?;
+    evm.deploy_wasm(reverts, &wat("store-and-exit")?, config)?;
+    evm.set_balance(sender, 100_u64.into());
+
+    // the value is both transferred and visible to the callee
+    let value = Bytes32::from(30_u64);
+    let (_, _, status) = evm.contract_call(payable, &[], 1_000_000, value);
+    assert_eq!(status, UserOutcomeKind::Success);
+    assert_eq!(evm.get_return_data().slice(), &value[..]);
+    assert_eq!(evm.balance(sender), 70_u64.into());
+    assert_eq!(evm.balance(payable), 30_u64.into());
+
+    // the sender can't afford it
+    let (_, _, status) = evm.contract_call(payable, &[], 1_000_000, 71_u64.into());
+    assert_eq!(status, UserOutcomeKind::Failure);
+    assert_eq!(evm.balance(sender), 70_u64.into());
+
+    // reverting returns the value
+    let mut args = vec![0; 64];
+    args.push(1);
+    let (_, _, status) = evm.contract_call(reverts, &args, 1_000_000, 70_u64.into());
+    assert_eq!(status, UserOutcomeKind::Revert);
+    assert_eq!(evm.balance(sender), 70_u64.into());
+    assert_eq!(evm.balance(reverts), Bytes32::default());
+
+    // plain transfers to accounts without code succeed
+    let account = random_bytes20();
+    let (_, _, status) = evm.contract_call(account, &[], 1_000_000, 70_u64.into());
+    assert_eq!(status, UserOutcomeKind::Success);
+    assert_eq!(evm.balance(account), 70_u64.into());
+    assert_eq!(evm.balance(sender), Bytes32::default());
+    Ok(())
+}
+
+#[test]
+fn test_static_call() -> Result<()> {
+    // in store-and-exit.wat
+    //     the input is a key, a value, and a status code
+    //     the value is written to storage, then the program exits with the status
+    //
+    // in msg-value.wat
+    //     the output is the call's msg_value
+
+    let wat = |name: &str| -> Result<Vec<u8>> {
+        let wat = std::fs::read(format!("tests/{name}.wat"))?;
+        Ok(wasmer::wat2wasm(&wat)?.to_vec())
+    };
+    let (compile, config, _) = test_configs();
+    let (mut evm, _) = MockEvmApi::new(compile);
+
+    let store = random_bytes20();
+    let reader = random_bytes20();
+    evm.deploy_wasm(store, &wat("store-and-exit")?, config)?;
+    evm.deploy_wasm(reader, &wat("msg-value")?, config)?;
+
+    // programs that only read succeed
+    let (_, _, status) = evm.static_call(reader, &[], 1_000_000);
+    assert_eq!(status, UserOutcomeKind::Success);
+    assert_eq!(evm.get_return_data().slice(), &[0; 32]);
+
+    // but writing to storage fails, leaving the world unchanged
+    let key = random_bytes32();
+    let mut args = key.to_vec();
+    args.extend(random_bytes32());
+    args.push(0);
+    let (_, _, status) = evm.static_call(store, &args, 1_000_000);
+    assert_eq!(status, UserOutcomeKind::Failure);
+    assert_eq!(evm.storage(store, key), Bytes32::default());
+    Ok(())
+}
+
+#[test]
+fn test_create_nonces() -> Result<()> {
+    let wasm = wasmer::wat2wasm(&std::fs::read("tests/msg-value.wat")?)?.to_vec();
+    let (compile, _, _) = test_configs();
+    let (mut evm, _) = MockEvmApi::new(compile);
+    let deployer = Bytes20::default();
+
+    let (first, _, _) = evm.create1(wasm.clone(), Bytes32::default(), 1_000_000);
+    let (second, _, _) = evm.create1(wasm, Bytes32::default(), 1_000_000);
+    assert_eq!(first?, create1_address(deployer, 0));
+    assert_eq!(second?, create1_address(deployer, 1));
+
+    // the deployer counts its deployments, while new contracts start at 1 per EIP-161
+    assert_eq!(evm.nonce(deployer), 2);
+    assert_eq!(evm.nonce(create1_address(deployer, 0)), 1);
+    Ok(())
+}
+
+#[test]
+fn test_capture_hostio() -> Result<()> {
+    // in msg-value.wat
+    //     the output is the call's msg_value
+
+    let wasm = wasmer::wat2wasm(&std::fs::read("tests/msg-value.wat")?)?;
+    let (compile, config, _) = test_configs();
+    let (mut evm, _) = MockEvmApi::new(compile);
+    let program = random_bytes20();
+    evm.deploy_wasm(program, &wasm, config)?;
+
+    // nothing is captured unless tracing
+    evm.contract_call(program, &[], 1_000_000, Bytes32::default());
+    assert!(evm.hostios().is_empty());
+
+    evm.set_evm_data(EvmData {
+        tracing: true,
+        ..EvmData::default()
+    });
+
+    let (_, _, status) = evm.contract_call(program, &[], 1_000_000, Bytes32::default());
+    assert_eq!(status, UserOutcomeKind::Success);
+
+    let hostios = evm.hostios();
+    assert!(!hostios.is_empty());
+    assert!(hostios.iter().all(|x| x.address == program));
+    assert!(hostios.iter().all(|x| x.start_ink >= x.end_ink));
+    assert!(hostios.iter().any(|x| x.name == "msg_value"));
+    Ok(())
+}
@@ -1,15 +1,20 @@
 // Copyright 2022-2023, Offchain Labs, Inc.
 // For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
 
-use crate::{env::WasmEnv, native::NativeInstance, run::RunProgram, test::api::TestEvmApi};
+use crate::{
+    env::WasmEnv,
+    mock::{MockEvmApi, MockInstance},
+    run::RunProgram,
+};
 use arbutil::{
-    evm::{api::VecReader, user::UserOutcome},
+    evm::{user::UserOutcome, EvmData},
     Bytes20, Bytes32, Color,
 };
 use eyre::{bail, Result};
 use prover::{
     machine::GlobalState,
-    programs::{config::SigMap, prelude::*},
+    programs::{config::OpCosts, prelude::*},
+    value::Value,
     Machine,
 };
 use rand::prelude::*;
@@ -20,8 +25,8 @@ use wasmer::{
 };
 use wasmer_compiler_singlepass::Singlepass;
 
-mod api;
 mod misc;
+mod mock;
 mod native;
 mod sdk;
 mod wavm;
@@ -29,7 +34,7 @@ mod wavm;
 #[cfg(feature = "timings")]
 mod timings;
 
-type TestInstance = NativeInstance<VecReader, TestEvmApi>;
+type TestInstance = MockInstance;
 
 impl TestInstance {
     fn new_test(path: &str, compile: CompileConfig) -> Result<Self> {
@@ -67,7 +72,7 @@ impl TestInstance {
 
     fn new_sans_env(instance: Instance, mut store: Store) -> Self {
         let compile = CompileConfig::default();
-        let (evm, evm_data) = TestEvmApi::new(compile.clone());
+        let (evm, evm_data) = MockEvmApi::new(compile.clone());
         let env = FunctionEnv::new(&mut store, WasmEnv::new(compile, None, evm, evm_data));
         Self::new(instance, store, env)
     }
@@ -84,8 +89,8 @@ impl TestInstance {
         path: &str,
         compile: &CompileConfig,
         config: StylusConfig,
-    ) -> Result<(Self, TestEvmApi)> {
-        let (mut evm, evm_data) = TestEvmApi::new(compile.clone());
+    ) -> Result<(Self, MockEvmApi)> {
+        let (mut evm, evm_data) = MockEvmApi::new(compile.clone());
         let native = Self::from_path(path, evm.clone(), evm_data, compile, config)?;
         let footprint = native.memory().ty(&native.store).minimum.0 as u16;
         evm.set_pages(footprint);
@@ -93,13 +98,20 @@ impl TestInstance {
     }
 }
 
-fn expensive_add(op: &Operator, _tys: &SigMap) -> u64 {
-    match op {
-        Operator::I32Add => 100,
-        _ => 0,
+impl MockEvmApi {
+    fn deploy(&mut self, address: Bytes20, config: StylusConfig, name: &str) -> Result<()> {
+        let file = format!("tests/{name}/target/wasm32-unknown-unknown/release/{name}.wasm");
+        let wasm = std::fs::read(file)?;
+        self.deploy_wasm(address, &wasm, config)
     }
 }
 
+fn expensive_add() -> OpCosts {
+    let mut costs = OpCosts::uniform(0);
+    costs.ops.insert(Operator::I32Add.into(), 100);
+    costs
+}
+
 pub fn random_ink(min: u64) -> u64 {
     rand::thread_rng().gen_range(min..=u64::MAX)
 }
@@ -136,9 +148,21 @@ fn test_configs() -> (CompileConfig, StylusConfig, u64) {
     )
 }
 
+/// Like [`test_configs`], but for Stylus version 2, whose programs may use more proposals.
+fn test_configs_v2() -> (CompileConfig, StylusConfig, u64) {
+    let mut compile_config = CompileConfig::version(2, true);
+    compile_config.debug.count_ops = true;
+    (
+        compile_config,
+        uniform_cost_config(),
+        random_ink(100_000_000),
+    )
+}
+
 fn new_test_machine(path: &str, compile: &CompileConfig) -> Result<Machine> {
     let wat = std::fs::read(path)?;
     let wasm = wasmer::wat2wasm(&wat)?;
+    let wasm = prover::simd::lower_user(&wasm, compile.version)?;
     let mut bin = prover::binary::parse(&wasm, Path::new("user"))?;
     let stylus_data = bin.instrument(compile)?;
 
@@ -184,6 +208,19 @@ fn run_machine(
     }
 }
 
+/// Sets the block context seen by programs run on a machine made via `Machine::from_user_path`.
+fn set_machine_block(machine: &mut Machine, evm_data: &EvmData) -> Result<()> {
+    let limbs = |word: Bytes32| -> Vec<Value> {
+        let limb = |x: &[u8]| Value::I64(u64::from_be_bytes(x.try_into().unwrap()));
+        word.chunks(8).map(limb).collect()
+    };
+    let mut args = vec![Value::I64(evm_data.block_number)];
+    args.extend(limbs(evm_data.block_prevrandao));
+    args.extend(limbs(evm_data.block_blob_basefee));
+    machine.call_function("user_test", "set_block", args)?;
+    Ok(())
+}
+
 fn check_instrumentation(mut native: TestInstance, mut machine: Machine) -> Result<()> {
     assert_eq!(native.ink_left(), machine.ink_left());
     assert_eq!(native.stack_left(), machine.stack_left());
@@ -7,10 +7,13 @@
 )]
 
 use crate::{
+    cache::ArtifactCache,
+    mock::{MockEvmApi, MockLog},
+    native,
     run::RunProgram,
     test::{
         check_instrumentation, random_bytes20, random_bytes32, random_ink, run_machine, run_native,
-        test_compile_config, test_configs, TestInstance,
+        set_machine_block, test_compile_config, test_configs, test_configs_v2, TestInstance,
     },
 };
 use arbutil::{
@@ -18,21 +21,28 @@ use arbutil::{
     evm::{
         api::EvmApi,
         user::{UserOutcome, UserOutcomeKind},
+        EvmData,
     },
     format, Bytes20, Bytes32, Color,
 };
 use eyre::{bail, ensure, Result};
 use prover::{
-    binary,
+    binary::{self, ActivationError, ActivationErrors, LimitKind, Location},
     programs::{
+        config::{NativeCompiler, OpCosts, OptLevel},
         counter::{Counter, CountingMachine},
+        estimate::InkEstimate,
         prelude::*,
+        profiler::ProfilingMachine,
         start::StartMover,
         MiddlewareWrapper, ModuleMod,
     },
+    value::{ArbValueType, FunctionType},
     Machine,
 };
-use std::{collections::HashMap, path::Path, sync::Arc, time::Instant};
+use std::{
+    collections::HashMap, fs, os::unix::fs::PermissionsExt, path::Path, sync::Arc, time::Instant,
+};
 use wasmer::wasmparser::Operator;
 use wasmer::{CompilerConfig, ExportIndex, Imports, Pages, Store};
 use wasmer_compiler_singlepass::Singlepass;
@@ -40,7 +50,7 @@ use wasmer_compiler_singlepass::Singlepass;
 #[test]
 fn test_ink() -> Result<()> {
     let mut compile = test_compile_config();
-    compile.pricing.costs = super::expensive_add;
+    compile.pricing.costs = super::expensive_add();
 
     let mut native = TestInstance::new_test("tests/add.wat", compile)?;
     let exports = &native.exports;
@@ -71,6 +81,103 @@ fn test_ink() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_ink_estimate() -> Result<()> {
+    // in estimate.wat
+    //     the func `pick` calls `double` only when its argument is nonzero
+    //     the func `spin` loops until its argument reaches zero
+
+    let file = "tests/estimate.wat";
+    let compile = test_compile_config();
+    let wasm = wasmer::wat2wasm(&std::fs::read(file)?)?;
+    let binary = binary::parse(&wasm, Path::new(file))?;
+    let estimate = InkEstimate::new(&binary, &compile.pricing)?;
+
+    let mut native = TestInstance::new_test(file, compile)?;
+    let exports = &native.exports;
+    let pick = exports.get_typed_function::<i32, i32>(&native.store, "pick")?;
+    let spin = exports.get_typed_function::<i32, ()>(&native.store, "spin")?;
+
+    macro_rules! ink_used {
+        ($func:expr, $arg:expr) => {{
+            native.set_ink(u64::MAX);
+            $func.call(&mut native.store, $arg)?;
+            u64::MAX - native.ink_left().ink()
+        }};
+    }
+
+    // the bounds of a loop-free function are exact
+    let pick_ink = estimate.export("pick").unwrap();
+    assert_eq!(pick_ink.min_ink, ink_used!(pick, 0));
+    assert_eq!(pick_ink.max_ink, Some(ink_used!(pick, 1)));
+    assert!(pick_ink.loops.is_empty());
+
+    // each additional iteration costs exactly the estimate
+    let spin_ink = estimate.export("spin").unwrap();
+    let [(_, Some(iteration))] = spin_ink.loops[..] else {
+        bail!("expected a single bounded loop")
+    };
+    assert_eq!(spin_ink.max_ink, None);
+    assert_eq!(spin_ink.min_ink, ink_used!(spin, 1));
+    assert_eq!(ink_used!(spin, 5) - ink_used!(spin, 1), 4 * iteration);
+    Ok(())
+}
+
+#[test]
+fn test_ink_profile() -> Result<()> {
+    // in profile.wat
+    //     the func `run` calls the hostio `noop`, loops `x` times, then calls `double`
+    //     the func `double` fills memory twice, which the dynamic meter charges for
+    //     the funcs are numbered noop = 0, double = 1, and run = 2
+
+    let file = "tests/profile.wat";
+    let mut compile = CompileConfig::version(1, true);
+    compile.debug.profile_ink = true;
+    compile.debug.profile_blocks = 2;
+
+    let mut native = TestInstance::new_test(file, compile)?;
+    let run = native
+        .exports
+        .get_typed_function::<i32, i32>(&native.store, "run")?;
+
+    let mut profile = |iters: i32| -> Result<_> {
+        native.set_ink(u64::MAX);
+        run.call(&mut native.store, iters)?;
+        let ink_used = u64::MAX - native.ink_left().ink();
+        let profile = native.ink_profile()?;
+
+        // all of the ink is attributed, and blocks beyond the last slot are included in it
+        assert_eq!(profile.total(), ink_used);
+        let run = &profile.funcs[&2];
+        assert_eq!(run.blocks.len(), 2);
+        assert_eq!(run.blocks.iter().sum::<u64>(), run.ink);
+        Ok(profile)
+    };
+
+    // the globals accumulate across calls
+    let once = profile(1)?;
+    let twice = profile(3)?;
+    let (noop, double, run) = (&twice.funcs[&0], &twice.funcs[&1], &twice.funcs[&2]);
+    assert_eq!(noop.import.as_deref(), Some("test"));
+    assert_eq!((noop.calls, noop.ink), (2, 0));
+    assert_eq!((double.calls, double.ink), (2, 2 * once.funcs[&1].ink));
+    assert_eq!(run.calls, 2);
+
+    // only the loop costs more, which comes after the first block
+    let loops = run.ink - 2 * once.funcs[&2].ink;
+    assert!(loops > 0);
+    assert_eq!(run.blocks[0], 2 * once.funcs[&2].blocks[0]);
+
+    // both fills are dynamic, even though they share a basic block
+    let fills = double.ink - double.blocks.iter().sum::<u64>();
+    assert!(fills > 0);
+    assert_eq!(double.blocks[1], 0);
+
+    let folded = twice.folded("profile");
+    assert!(folded.contains(&format!("[module] profile;double;dynamic {fills}\n")));
+    Ok(())
+}
+
 #[test]
 fn test_depth() -> Result<()> {
     // in depth.wat
@@ -234,6 +341,89 @@ fn test_module_mod() -> Result<()> {
     check("more")
 }
 
+#[test]
+fn test_limit_usage() -> Result<()> {
+    // in grow-120.wat
+    //     there's a single memory of 120 pages
+
+    let wasm = std::fs::read("tests/grow-120.wat")?;
+    let wasm = wasmer::wat2wasm(&wasm)?;
+    let compile = test_compile_config();
+
+    let check = |page_limit: u16| -> Result<Option<u64>> {
+        let mut bin = binary::parse(&wasm, Path::new("user"))?;
+        bin.instrument(&compile)?;
+        let usage = bin.limit_usage(page_limit);
+        let pages = usage.iter().find(|x| x.name == "memory pages").unwrap();
+        assert_eq!(pages.actual, 120);
+
+        let exceeded: Vec<_> = usage.iter().filter(|x| x.exceeded()).collect();
+        let parsed = binary::WasmBinary::parse_user(&wasm, page_limit, &compile);
+        assert_eq!(exceeded.is_empty(), parsed.is_ok());
+        Ok((!pages.exceeded()).then(|| pages.headroom()))
+    };
+
+    assert_eq!(check(128)?, Some(8));
+    assert_eq!(check(120)?, Some(0));
+    assert_eq!(check(119)?, None);
+    Ok(())
+}
+
+#[test]
+fn test_activation_errors() -> Result<()> {
+    // a program with several problems, all of which should be reported at once
+    let locals = "i32 ".repeat(400);
+    let wat = format!(
+        r#"(module
+            (memory (export "memory") 2 2)
+            (func $start)
+            (func (local {locals}))
+            (func (local {locals}))
+            (func (export "user_entrypoint") (result i32) i32.const 0)
+            (start $start))"#
+    );
+    let wasm = wasmer::wat2wasm(wat.as_bytes())?;
+    let compile = test_compile_config();
+
+    let error = binary::WasmBinary::parse_user(&wasm, 1, &compile).unwrap_err();
+    let ActivationErrors(errors) = error.downcast()?;
+
+    let limit = |kind, name, limit, actual, location| ActivationError::Limit {
+        kind,
+        name,
+        limit,
+        actual,
+        location,
+    };
+    let func = |index| Some(Location::Func(index));
+    let expected = vec![
+        ActivationError::WrongType {
+            name: "user_entrypoint".into(),
+            ty: FunctionType::new([], [ArbValueType::I32]),
+        },
+        limit(LimitKind::Memory, "memory pages", 1, 2, None),
+        limit(LimitKind::Count, "locals", 348, 400, func(1)),
+        limit(LimitKind::Count, "locals", 348, 400, func(2)),
+        limit(LimitKind::Forbidden, "start functions", 0, 1, None),
+    ];
+    assert_eq!(errors, expected);
+    Ok(())
+}
+
+#[test]
+fn test_missing_memory() -> Result<()> {
+    // instrumentation needs a memory, so its absence is reported rather than failing instrumentation
+    let wat = r#"(module
+        (func (export "user_entrypoint") (param i32) (result i32) i32.const 0))"#;
+    let wasm = wasmer::wat2wasm(wat.as_bytes())?;
+    let compile = test_compile_config();
+
+    let error = binary::WasmBinary::parse_user(&wasm, 128, &compile).unwrap_err();
+    let ActivationErrors(errors) = error.downcast()?;
+    assert_eq!(errors, vec![ActivationError::MissingMemory]);
+    Ok(())
+}
+
 #[test]
 fn test_heap() -> Result<()> {
     // in memory.wat
@@ -242,7 +432,7 @@ fn test_heap() -> Result<()> {
 
     let (mut compile, config, _) = test_configs();
     compile.bounds.heap_bound = Pages(128);
-    compile.pricing.costs = |_, _| 0;
+    compile.pricing.costs = OpCosts::uniform(0);
 
     let extra: u8 = rand::random::<u8>() % 128;
 
@@ -378,6 +568,331 @@ fn test_storage() -> Result<()> {
     check_instrumentation(native, machine)
 }
 
+#[test]
+fn test_transient_storage() -> Result<()> {
+    // in transient.wat
+    //     the input is a key followed by a value
+    //     the value is written to transient storage, then read back as the output
+
+    let filename = "tests/transient.wat";
+    let (compile, config, ink) = test_configs();
+
+    let key = random_bytes32();
+    let value = random_bytes32();
+
+    let mut args = key.to_vec();
+    args.extend(value);
+
+    let (mut native, mut evm) = TestInstance::new_with_evm(filename, &compile, config)?;
+    let output = run_native(&mut native, &args, ink)?;
+    assert_eq!(hex::encode(output), hex::encode(value));
+    assert_eq!(evm.get_transient_bytes32(key).0, value);
+    assert_eq!(evm.get_bytes32(key).0, Bytes32::default());
+
+    let mut machine = Machine::from_user_path(Path::new(filename), &compile)?;
+    let output = run_machine(&mut machine, &args, config, ink)?;
+    assert_eq!(hex::encode(output), hex::encode(value));
+
+    check_instrumentation(native, machine)
+}
+
+#[test]
+fn test_logs() -> Result<()> {
+    // in log.rs
+    //     the first byte is the number of topics
+    //     the next 32 * topics bytes are the topics, with the rest being the log's data
+
+    let filename = "tests/log/target/wasm32-unknown-unknown/release/log.wasm";
+    let (compile, config, ink) = test_configs();
+    let (mut native, evm) = TestInstance::new_with_evm(filename, &compile, config)?;
+
+    let mut expected = vec![];
+    for topics in 0..=4 {
+        let topics: Vec<_> = (0..topics).map(|_| random_bytes32()).collect();
+        let data = random_bytes32().to_vec();
+
+        let mut args = vec![topics.len() as u8];
+        topics.iter().for_each(|x| args.extend(*x));
+        args.extend(&data);
+        run_native(&mut native, &args, ink)?;
+
+        expected.push(MockLog {
+            address: Bytes20::default(),
+            topics,
+            data,
+        });
+    }
+    assert_eq!(evm.logs(), expected);
+    Ok(())
+}
+
+#[test]
+fn test_read_args_range() -> Result<()> {
+    // in read-args-range.wat
+    //     the input starts with a little-endian offset and size
+    //     the output is the overlapping window of the input, which may be shorter than the size
+
+    let filename = "tests/read-args-range.wat";
+    let (compile, config, ink) = test_configs();
+
+    let mut args = vec![0; 8];
+    args.extend(random_bytes32());
+    args.extend(random_bytes32());
+
+    let windows = [(8, 32), (0, 72), (40, 64), (71, 1), (72, 8), (96, 8)];
+    for (offset, size) in windows {
+        args[..4].copy_from_slice(&u32::to_le_bytes(offset));
+        args[4..8].copy_from_slice(&u32::to_le_bytes(size));
+        let expected = arbutil::slice_with_runoff(&args, offset, offset.saturating_add(size));
+
+        let (mut native, _) = TestInstance::new_with_evm(filename, &compile, config)?;
+        let output = run_native(&mut native, &args, ink)?;
+        assert_eq!(hex::encode(output), hex::encode(expected));
+
+        let mut machine = Machine::from_user_path(Path::new(filename), &compile)?;
+        let output = run_machine(&mut machine, &args, config, ink)?;
+        assert_eq!(hex::encode(output), hex::encode(expected));
+
+        check_instrumentation(native, machine)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_account_code() -> Result<()> {
+    // in account-code.wat
+    //     the input is an address followed by a little-endian offset and size
+    //     the output is the overlapping window of the account's code
+
+    let filename = "tests/account-code.wat";
+    let (compile, mut config, ink) = test_configs();
+    let code = wasmer::wat2wasm(&fs::read("tests/read-args-range.wat")?)?.to_vec();
+    let code_len = code.len() as u64;
+    assert!(code_len > 64);
+
+    let address = random_bytes20();
+    let (offset, size) = (8, 16);
+    let mut args = address.to_vec();
+    args.extend(u32::to_le_bytes(offset));
+    args.extend(u32::to_le_bytes(size));
+    let expected = &code[offset as usize..][..size as usize];
+
+    // version 1 pays to write the whole code, whereas version 2 pays only for the window
+    let mut used = vec![];
+    for version in [1, 2] {
+        config.version = version;
+        let (mut native, mut evm) = TestInstance::new_with_evm(filename, &compile, config)?;
+        evm.deploy_wasm(address, &code, config)?;
+
+        let output = run_native(&mut native, &args, ink)?;
+        assert_eq!(hex::encode(output), hex::encode(expected));
+        used.push(ink - native.ink_ready()?);
+    }
+    assert_eq!(used[0] - used[1], 25 * (code_len - 32));
+    Ok(())
+}
+
+#[test]
+fn test_simd() -> Result<()> {
+    // in simd.wat
+    //     the input is three vectors and a little-endian scalar
+    //     the output is the result of each SIMD operation, which version 2 lowers to i64 pairs
+
+    let filename = "tests/simd.wat";
+    let (compile, config, ink) = test_configs_v2();
+
+    let edges = [0x00, 0x01, 0x7f, 0x80, 0xff];
+    let mut inputs = vec![];
+    for (i, edge) in edges.into_iter().enumerate() {
+        let mut args = vec![edge; 48];
+        args[16..32].copy_from_slice(&random_bytes32()[..16]);
+        args.extend([0_u32, 7, 9, 33, 65][i].to_le_bytes());
+        inputs.push(args);
+    }
+    for _ in 0..8 {
+        let mut args = random_bytes32().to_vec();
+        args.extend(&random_bytes32()[..20]);
+        inputs.push(args);
+    }
+
+    for args in inputs {
+        let (mut native, _) = TestInstance::new_with_evm(filename, &compile, config)?;
+        let expected = run_native(&mut native, &args, ink)?;
+
+        let mut machine = Machine::from_user_path(Path::new(filename), &compile)?;
+        let output = run_machine(&mut machine, &args, config, ink)?;
+        assert_eq!(hex::encode(output), hex::encode(expected));
+
+        check_instrumentation(native, machine)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_extended_const() -> Result<()> {
+    // in extended-const.wat
+    //     globals, a data segment, and an element segment are initialized by arithmetic
+    //     the output is the data pointer, its segment, a computed i64, a table call, and the segment again
+
+    let filename = "tests/extended-const.wat";
+    let (compile, config, ink) = test_configs_v2();
+
+    // extended constants are only allowed from version 2 on
+    let v1 = CompileConfig::version(1, true);
+    _ = Machine::from_user_path(Path::new(filename), &v1).unwrap_err();
+
+    let mut expected = 0x120_u32.to_le_bytes().to_vec();
+    expected.extend([0xaa, 0xbb, 0xcc, 0xdd]);
+    expected.extend(20_u64.to_le_bytes());
+    expected.extend(42_u32.to_le_bytes());
+    expected.extend([0xaa, 0xbb, 0xcc, 0xdd]);
+
+    let (mut native, _) = TestInstance::new_with_evm(filename, &compile, config)?;
+    let output = run_native(&mut native, &[], ink)?;
+    assert_eq!(hex::encode(output), hex::encode(&expected));
+
+    let mut machine = Machine::from_user_path(Path::new(filename), &compile)?;
+    let output = run_machine(&mut machine, &[], config, ink)?;
+    assert_eq!(hex::encode(output), hex::encode(&expected));
+
+    check_instrumentation(native, machine)
+}
+
+#[test]
+fn test_bulk_segments() -> Result<()> {
+    // in bulk-segments.wat
+    //     a passive data segment is copied into memory, then dropped
+    //     the output is the copied bytes
+
+    let filename = "tests/bulk-segments.wat";
+    let (compile, config, ink) = test_configs_v2();
+    let segment = [0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88];
+
+    // data segments can only be read from version 2 onward
+    let v1 = CompileConfig::version(1, true);
+    _ = Machine::from_user_path(Path::new(filename), &v1).unwrap_err();
+
+    for n in [0_u32, 3, 8] {
+        let mut expected = [0; 8];
+        expected[..n as usize].copy_from_slice(&segment[..n as usize]);
+
+        let args = n.to_le_bytes();
+        let (mut native, _) = TestInstance::new_with_evm(filename, &compile, config)?;
+        let output = run_native(&mut native, &args, ink)?;
+        assert_eq!(hex::encode(output), hex::encode(&expected));
+
+        let mut machine = Machine::from_user_path(Path::new(filename), &compile)?;
+        let output = run_machine(&mut machine, &args, config, ink)?;
+        assert_eq!(hex::encode(output), hex::encode(&expected));
+
+        check_instrumentation(native, machine)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_artifact_cache() -> Result<()> {
+    // in bulk-segments.wat
+    //     the output is the first 3 bytes of a segment
+
+    let wasm = wasmer::wat2wasm(&fs::read("tests/bulk-segments.wat")?)?.to_vec();
+    let (mut compile, config, ink) = test_configs_v2();
+    compile.compiler = NativeCompiler::Cranelift(OptLevel::Speed);
+
+    let dir = std::env::temp_dir().join(format!("stylus-artifacts-{}", random_bytes32()));
+    let cache = ArtifactCache::new(&dir)?;
+
+    // the first compilation fills the cache, and the second reads it
+    let key = ArtifactCache::key(&wasm, &compile);
+    assert!(cache.get(key).is_none());
+    let asm = native::compile(&wasm, compile.clone(), Some(&cache))?;
+    assert_eq!(cache.get(key).as_ref(), Some(&asm));
+    assert_eq!(native::compile(&wasm, compile.clone(), Some(&cache))?, asm);
+
+    // other programs and compilers have their own artifacts
+    let mut singlepass = compile.clone();
+    singlepass.compiler = NativeCompiler::Singlepass;
+    assert_ne!(ArtifactCache::key(&wasm, &singlepass), key);
+
+    let other = wasmer::wat2wasm(&fs::read("tests/add.wat")?)?;
+    assert_ne!(ArtifactCache::key(&other, &compile), key);
+
+    // the optimized code behaves like singlepass's
+    let (evm, evm_data) = MockEvmApi::new(compile.clone());
+    let mut native = unsafe { TestInstance::deserialize(&asm, compile, evm, evm_data)? };
+    native.env_mut().config = Some(config);
+
+    let expected = [0x11, 0x22, 0x33, 0, 0, 0, 0, 0];
+    let output = run_native(&mut native, &3_u32.to_le_bytes(), ink)?;
+    assert_eq!(hex::encode(output), hex::encode(expected));
+
+    // only the current user may access the cache
+    assert_eq!(fs::metadata(&dir)?.permissions().mode() & 0o777, 0o700);
+
+    // artifacts copied to another key are discarded
+    let path = dir.join(format!("{key}.bin"));
+    let other_key = ArtifactCache::key(&other, &compile);
+    let other_path = dir.join(format!("{other_key}.bin"));
+    fs::copy(&path, &other_path)?;
+    assert!(cache.get(other_key).is_none());
+    assert!(!other_path.exists());
+
+    // corrupt artifacts are discarded
+    let mut data = fs::read(&path)?;
+    data[40] ^= 1;
+    fs::write(&path, data)?;
+    assert!(cache.get(key).is_none());
+    assert!(!path.exists());
+
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_block_context() -> Result<()> {
+    // in block-context.wat
+    //     the output is the prevrandao, the blob basefee, the current and previous blocks' hashes,
+    //     the first blob's hash, and that of a blob the tx doesn't carry
+
+    let filename = "tests/block-context.wat";
+    let (compile, config, ink) = test_configs();
+
+    let mut evm_data = EvmData::default();
+    evm_data.block_number = 1024;
+    evm_data.block_prevrandao = random_bytes32();
+    evm_data.block_blob_basefee = random_bytes32();
+
+    let (mut native, mut evm) = TestInstance::new_with_evm(filename, &compile, config)?;
+    native.env_mut().evm_data = evm_data;
+    native.env_mut().evm_api.set_evm_data(evm_data);
+    evm.set_evm_data(evm_data);
+
+    let prior_hash = evm.block_hash(1023).0;
+    let blob_hash = arbutil::evm::mock::blob_hash(0);
+    assert_ne!(prior_hash, Bytes32::default());
+    assert_ne!(blob_hash, Bytes32::default());
+
+    let expected = [
+        *evm_data.block_prevrandao,
+        *evm_data.block_blob_basefee,
+        [0; 32],
+        *prior_hash,
+        *blob_hash,
+        [0; 32],
+    ]
+    .concat();
+
+    let output = run_native(&mut native, &[], ink)?;
+    assert_eq!(hex::encode(&output), hex::encode(&expected));
+
+    let mut machine = Machine::from_user_path(Path::new(filename), &compile)?;
+    set_machine_block(&mut machine, &evm_data)?;
+    let output = run_machine(&mut machine, &[], config, ink)?;
+    assert_eq!(hex::encode(output), hex::encode(expected));
+
+    check_instrumentation(native, machine)
+}
+
 #[test]
 fn test_calls() -> Result<()> {
     // in call.rs
@@ -452,7 +967,7 @@ fn test_calls() -> Result<()> {
     run_native(&mut native, &args, ink)?;
 
     for (key, value) in slots {
-        assert_eq!(evm.get_bytes32(key).0, value);
+        assert_eq!(evm.storage(store_addr, key), value);
     }
     Ok(())
 }
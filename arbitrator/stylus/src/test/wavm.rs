@@ -8,7 +8,7 @@ use prover::{programs::prelude::*, Machine};
 #[test]
 fn test_ink() -> Result<()> {
     let mut compile = test_compile_config();
-    compile.pricing.costs = super::expensive_add;
+    compile.pricing.costs = super::expensive_add();
 
     let machine = &mut new_test_machine("tests/add.wat", &compile)?;
     let call = |mech: &mut Machine, v: u32| mech.call_function("user", "add_one", vec![v.into()]);
@@ -0,0 +1,24 @@
+;; Copyright 2024, Offchain Labs, Inc.
+;; For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+(module
+    (import "vm_hooks" "read_args"    (func $read_args    (param i32)))
+    (import "vm_hooks" "account_code" (func $account_code (param i32 i32 i32 i32) (result i32)))
+    (import "vm_hooks" "write_result" (func $write_result (param i32 i32)))
+    (memory (export "memory") 1 1)
+    (func (export "user_entrypoint") (param $args_len i32) (result i32)
+        (local $written i32)
+
+        ;; read the address, then the little-endian offset and size, to offsets 0, 20, and 24
+        (call $read_args (i32.const 0))
+
+        ;; copy the requested window of the account's code to offset 28
+        (local.set $written
+            (call $account_code
+                (i32.const 0) (i32.load (i32.const 20)) (i32.load (i32.const 24)) (i32.const 28)))
+
+        ;; return only the bytes written
+        (call $write_result (i32.const 28) (local.get $written))
+        i32.const 0
+    )
+)
@@ -0,0 +1,31 @@
+;; Copyright 2024, Offchain Labs, Inc.
+;; For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+(module
+    (import "vm_hooks" "write_result"       (func $write_result       (param i32 i32)))
+    (import "vm_hooks" "block_prevrandao"   (func $block_prevrandao   (param i32)))
+    (import "vm_hooks" "block_blob_basefee" (func $block_blob_basefee (param i32)))
+    (import "vm_hooks" "block_hash"         (func $block_hash         (param i64 i32)))
+    (import "vm_hooks" "blob_hash"          (func $blob_hash          (param i32 i32)))
+    (import "vm_hooks" "block_number"       (func $block_number       (result i64)))
+    (memory (export "memory") 1 1)
+    (func (export "user_entrypoint") (param $args_len i32) (result i32)
+        ;; write the prevrandao and blob basefee to offsets 0 and 32
+        (call $block_prevrandao (i32.const 0))
+        (call $block_blob_basefee (i32.const 32))
+
+        ;; write the hash of the current block to offset 64, which is always zero
+        (call $block_hash (call $block_number) (i32.const 64))
+
+        ;; write the hash of the previous block to offset 96
+        (call $block_hash (i64.sub (call $block_number) (i64.const 1)) (i32.const 96))
+
+        ;; write the first blob's hash to offset 128, and a missing blob's to offset 160
+        (call $blob_hash (i32.const 0) (i32.const 128))
+        (call $blob_hash (i32.const 255) (i32.const 160))
+
+        ;; return all six words
+        (call $write_result (i32.const 0) (i32.const 192))
+        i32.const 0
+    )
+)
@@ -0,0 +1,28 @@
+;; Copyright 2024, Offchain Labs, Inc.
+;; For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+(module
+    (import "vm_hooks" "read_args"    (func $read_args    (param i32)))
+    (import "vm_hooks" "write_result" (func $write_result (param i32 i32)))
+    (memory (export "memory") 1 1)
+
+    ;; a passive segment, as emitted by newer toolchains
+    (data $bytes "\11\22\33\44\55\66\77\88")
+
+    (func (export "user_entrypoint") (param $args_len i32) (result i32)
+        (local $n i32)
+
+        ;; read the little-endian byte count to offset 0
+        (call $read_args (i32.const 0))
+        (local.set $n (i32.load (i32.const 0)))
+
+        ;; copy that many bytes of the data segment, then drop it
+        (memory.init $bytes (i32.const 0x100) (i32.const 0) (local.get $n))
+        (data.drop $bytes)
+        (memory.init $bytes (i32.const 0x100) (i32.const 0) (i32.const 0))
+
+        ;; write the copied bytes
+        (call $write_result (i32.const 0x100) (i32.const 8))
+        i32.const 0
+    )
+)
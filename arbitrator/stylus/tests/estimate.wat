@@ -0,0 +1,20 @@
+;; Copyright 2024, Offchain Labs, Inc.
+;; For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+(module
+    (memory (export "memory") 0 0)
+    (func $double (param $x i32) (result i32)
+        (i32.add (local.get $x) (local.get $x)))
+    (func (export "pick") (param $x i32) (result i32)
+        local.get $x
+        if (result i32)
+            local.get $x
+            call $double
+        else
+            i32.const 0
+        end)
+    (func (export "spin") (param $x i32)
+        (loop $loop
+            (br_if $loop (local.tee $x (i32.sub (local.get $x) (i32.const 1))))))
+    (func (export "user_entrypoint") (param $args_len i32) (result i32)
+        (i32.const 0)))
@@ -0,0 +1,31 @@
+;; Copyright 2024, Offchain Labs, Inc.
+;; For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+(module
+    (import "vm_hooks" "write_result" (func $write_result (param i32 i32)))
+    (memory (export "memory") 1 1)
+    (type $get (func (result i32)))
+    (table 4 funcref)
+
+    ;; initializers computed by arithmetic on constants, per the extended-const proposal
+    (global $data i32 (i32.add (i32.const 0x100) (i32.const 0x20)))
+    (global $wide i64 (i64.sub (i64.mul (i64.const 3) (i64.const 7)) (i64.const 1)))
+
+    (data (i32.add (i32.const 0x100) (i32.const 0x24)) "\aa\bb\cc\dd")
+    (elem (i32.sub (i32.const 4) (i32.const 1)) $answer)
+
+    (func $answer (result i32)
+        i32.const 42
+    )
+
+    (func (export "user_entrypoint") (param $args_len i32) (result i32)
+        ;; write the data pointer, the wide global, the answer, and the data
+        (i32.store (global.get $data) (global.get $data))
+        (i64.store (i32.add (global.get $data) (i32.const 8)) (global.get $wide))
+        (i32.store (i32.add (global.get $data) (i32.const 16)) (call_indirect (type $get) (i32.const 3)))
+        (i32.store (i32.add (global.get $data) (i32.const 20)) (i32.load (i32.const 0x124)))
+
+        (call $write_result (global.get $data) (i32.const 24))
+        i32.const 0
+    )
+)
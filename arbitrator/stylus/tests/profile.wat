@@ -0,0 +1,18 @@
+;; Copyright 2024, Offchain Labs, Inc.
+;; For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+(module
+    (import "test" "noop" (func $noop))
+    (memory (export "memory") 1 1)
+    (func $double (param $x i32) (result i32)
+        ;; two dynamically priced fills within a single basic block
+        (memory.fill (i32.const 0) (i32.const 0) (local.get $x))
+        (memory.fill (i32.const 0) (i32.const 0) (local.get $x))
+        (i32.add (local.get $x) (local.get $x)))
+    (func (export "run") (param $x i32) (result i32)
+        call $noop
+        (loop $loop
+            (br_if $loop (local.tee $x (i32.sub (local.get $x) (i32.const 1)))))
+        (call $double (i32.const 2)))
+    (func (export "user_entrypoint") (param $args_len i32) (result i32)
+        (i32.const 0)))
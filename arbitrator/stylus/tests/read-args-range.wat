@@ -0,0 +1,22 @@
+;; Copyright 2024, Offchain Labs, Inc.
+;; For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+(module
+    (import "vm_hooks" "read_args_range" (func $read_args_range (param i32 i32 i32) (result i32)))
+    (import "vm_hooks" "write_result"    (func $write_result    (param i32 i32)))
+    (memory (export "memory") 1 1)
+    (func (export "user_entrypoint") (param $args_len i32) (result i32)
+        (local $written i32)
+
+        ;; read the little-endian offset and size to offsets 0 and 4
+        (drop (call $read_args_range (i32.const 0) (i32.const 0) (i32.const 8)))
+
+        ;; copy the requested window to offset 8
+        (local.set $written
+            (call $read_args_range (i32.const 8) (i32.load (i32.const 0)) (i32.load (i32.const 4))))
+
+        ;; return only the bytes written
+        (call $write_result (i32.const 8) (local.get $written))
+        i32.const 0
+    )
+)
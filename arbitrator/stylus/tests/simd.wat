@@ -0,0 +1,205 @@
+;; Copyright 2024, Offchain Labs, Inc.
+;; For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+(module
+    (import "vm_hooks" "read_args"    (func $read_args    (param i32)))
+    (import "vm_hooks" "write_result" (func $write_result (param i32 i32)))
+    (memory (export "memory") 1 1)
+    (global $out (mut i32) (i32.const 0x100))
+
+    ;; appends a vector to the output
+    (func $v (param $x v128)
+        (v128.store (global.get $out) (local.get $x))
+        (global.set $out (i32.add (global.get $out) (i32.const 16)))
+    )
+
+    ;; appends a scalar to the output
+    (func $i (param $x i32)
+        (i32.store (global.get $out) (local.get $x))
+        (global.set $out (i32.add (global.get $out) (i32.const 4)))
+    )
+
+    ;; yields a vector from a block, whose lowering yields a pair of i64s
+    (func $pick (param $x v128) (param $y v128) (param $n i32) (result v128)
+        (local $t v128)
+        (block (result v128)
+            (drop (if (result v128) (i32.and (local.get $n) (i32.const 2))
+                (then (local.tee $t (local.get $x)))
+                (else (local.tee $t (local.get $y)))
+            ))
+            (select (local.get $t) (local.get $y) (i32.and (local.get $n) (i32.const 4)))
+        )
+    )
+
+    (func (export "user_entrypoint") (param $args_len i32) (result i32)
+        (local $a v128)
+        (local $b v128)
+        (local $c v128)
+        (local $n i32)
+
+        ;; read three vectors and a scalar
+        (call $read_args (i32.const 0))
+        (local.set $a (v128.load (i32.const 0)))
+        (local.set $b (v128.load (i32.const 16)))
+        (local.set $c (v128.load (i32.const 32)))
+        (local.set $n (i32.load (i32.const 48)))
+
+        ;; shuffle and swizzle, with some indices out of range
+        (call $v (i8x16.shuffle 0 17 2 19 4 21 6 23 31 30 29 28 3 2 1 0 (local.get $a) (local.get $b)))
+        (call $v (i8x16.swizzle (local.get $a) (v128.and (local.get $b) (v128.const i8x16 0x1f 0x1f 0x1f 0x1f 0x1f 0x1f 0x1f 0x1f 0x1f 0x1f 0x1f 0x1f 0x1f 0x1f 0x1f 0x1f))))
+
+        ;; narrowing
+        (call $v (i8x16.narrow_i16x8_s (local.get $a) (local.get $b)))
+        (call $v (i8x16.narrow_i16x8_u (local.get $a) (local.get $b)))
+        (call $v (i16x8.narrow_i32x4_s (local.get $a) (local.get $b)))
+        (call $v (i16x8.narrow_i32x4_u (local.get $a) (local.get $b)))
+
+        ;; saturating arithmetic
+        (call $v (i8x16.add_sat_s (local.get $a) (local.get $b)))
+        (call $v (i8x16.add_sat_u (local.get $a) (local.get $b)))
+        (call $v (i8x16.sub_sat_s (local.get $a) (local.get $b)))
+        (call $v (i8x16.sub_sat_u (local.get $a) (local.get $b)))
+        (call $v (i16x8.add_sat_u (local.get $a) (local.get $b)))
+        (call $v (i16x8.sub_sat_s (local.get $a) (local.get $b)))
+
+        ;; q15mulr, dot, and extended multiplication
+        (call $v (i16x8.q15mulr_sat_s (local.get $a) (local.get $b)))
+        (call $v (i16x8.q15mulr_sat_s (local.get $c) (local.get $c)))
+        (call $v (i32x4.dot_i16x8_s (local.get $a) (local.get $b)))
+        (call $v (i16x8.extmul_low_i8x16_s (local.get $a) (local.get $b)))
+        (call $v (i16x8.extmul_high_i8x16_u (local.get $a) (local.get $b)))
+        (call $v (i32x4.extmul_low_i16x8_u (local.get $a) (local.get $b)))
+        (call $v (i32x4.extmul_high_i16x8_s (local.get $a) (local.get $b)))
+        (call $v (i64x2.extmul_low_i32x4_s (local.get $a) (local.get $b)))
+        (call $v (i64x2.extmul_high_i32x4_u (local.get $a) (local.get $b)))
+
+        ;; bitmasks and lane tests
+        (call $i (i8x16.bitmask (local.get $a)))
+        (call $i (i16x8.bitmask (local.get $a)))
+        (call $i (i32x4.bitmask (local.get $a)))
+        (call $i (i64x2.bitmask (local.get $a)))
+        (call $i (i8x16.all_true (local.get $a)))
+        (call $i (i16x8.all_true (local.get $c)))
+        (call $i (i32x4.all_true (v128.or (local.get $a) (v128.const i32x4 1 1 1 1))))
+        (call $i (i64x2.all_true (local.get $b)))
+        (call $i (v128.any_true (local.get $a)))
+        (call $i (v128.any_true (v128.xor (local.get $a) (local.get $a))))
+
+        ;; lane loads and replacements, including unaligned ones
+        (call $v (v128.load8_lane 3 (i32.const 37) (local.get $a)))
+        (call $v (v128.load16_lane 5 (i32.const 33) (local.get $a)))
+        (call $v (v128.load32_lane 1 (i32.const 41) (local.get $a)))
+        (call $v (v128.load64_lane 1 (i32.const 35) (local.get $a)))
+        (call $v (i8x16.replace_lane 9 (local.get $a) (local.get $n)))
+        (call $v (i16x8.replace_lane 2 (local.get $a) (local.get $n)))
+        (call $v (i32x4.replace_lane 3 (local.get $a) (local.get $n)))
+        (call $v (i64x2.replace_lane 0 (local.get $a) (i64.load (i32.const 40))))
+
+        ;; lane extraction and splats
+        (call $i (i8x16.extract_lane_s 7 (local.get $a)))
+        (call $i (i8x16.extract_lane_u 15 (local.get $a)))
+        (call $i (i16x8.extract_lane_s 3 (local.get $b)))
+        (call $i (i16x8.extract_lane_u 6 (local.get $b)))
+        (call $i (i32x4.extract_lane 2 (local.get $c)))
+        (call $i (i32.wrap_i64 (i64.shr_u (i64x2.extract_lane 1 (local.get $c)) (i64.const 32))))
+        (call $v (i8x16.splat (local.get $n)))
+        (call $v (i16x8.splat (local.get $n)))
+        (call $v (i32x4.splat (local.get $n)))
+        (call $v (i64x2.splat (i64.load (i32.const 40))))
+
+        ;; bitwise operations and float signs
+        (call $v (v128.not (local.get $a)))
+        (call $v (v128.and (local.get $a) (local.get $b)))
+        (call $v (v128.andnot (local.get $a) (local.get $b)))
+        (call $v (v128.or (local.get $a) (local.get $b)))
+        (call $v (v128.xor (local.get $a) (local.get $b)))
+        (call $v (v128.bitselect (local.get $a) (local.get $b) (local.get $c)))
+        (call $v (f32x4.abs (local.get $a)))
+        (call $v (f32x4.neg (local.get $a)))
+        (call $v (f64x2.abs (local.get $b)))
+        (call $v (f64x2.neg (local.get $b)))
+
+        ;; lanewise unary operations
+        (call $v (i8x16.abs (local.get $a)))
+        (call $v (i16x8.neg (local.get $a)))
+        (call $v (i32x4.abs (local.get $b)))
+        (call $v (i64x2.neg (local.get $b)))
+        (call $v (i8x16.popcnt (local.get $c)))
+        (call $v (i16x8.extend_low_i8x16_s (local.get $a)))
+        (call $v (i32x4.extend_high_i16x8_u (local.get $a)))
+        (call $v (i64x2.extend_low_i32x4_u (local.get $b)))
+        (call $v (i64x2.extend_high_i32x4_s (local.get $b)))
+        (call $v (i16x8.extadd_pairwise_i8x16_s (local.get $a)))
+        (call $v (i32x4.extadd_pairwise_i16x8_u (local.get $a)))
+
+        ;; lanewise binary operations
+        (call $v (i8x16.add (local.get $a) (local.get $b)))
+        (call $v (i16x8.sub (local.get $a) (local.get $b)))
+        (call $v (i16x8.mul (local.get $a) (local.get $b)))
+        (call $v (i32x4.mul (local.get $a) (local.get $b)))
+        (call $v (i64x2.add (local.get $a) (local.get $b)))
+        (call $v (i64x2.mul (local.get $a) (local.get $b)))
+        (call $v (i8x16.min_s (local.get $a) (local.get $b)))
+        (call $v (i8x16.max_u (local.get $a) (local.get $b)))
+        (call $v (i16x8.min_u (local.get $a) (local.get $b)))
+        (call $v (i32x4.max_s (local.get $a) (local.get $b)))
+        (call $v (i8x16.avgr_u (local.get $a) (local.get $b)))
+        (call $v (i16x8.avgr_u (local.get $a) (local.get $b)))
+
+        ;; comparisons, some against partially equal vectors
+        (call $v (i8x16.eq (local.get $a) (v128.bitselect (local.get $a) (local.get $b) (local.get $c))))
+        (call $v (i16x8.ne (local.get $a) (v128.bitselect (local.get $a) (local.get $b) (local.get $c))))
+        (call $v (i32x4.lt_s (local.get $a) (local.get $b)))
+        (call $v (i8x16.gt_u (local.get $a) (local.get $b)))
+        (call $v (i16x8.le_s (local.get $a) (local.get $b)))
+        (call $v (i32x4.ge_u (local.get $a) (local.get $b)))
+        (call $v (i64x2.lt_s (local.get $a) (local.get $b)))
+        (call $v (i64x2.eq (local.get $a) (local.get $a)))
+
+        ;; shifts by counts beyond the lane width
+        (call $v (i8x16.shl (local.get $a) (local.get $n)))
+        (call $v (i16x8.shr_s (local.get $a) (local.get $n)))
+        (call $v (i32x4.shr_u (local.get $a) (local.get $n)))
+        (call $v (i64x2.shl (local.get $a) (local.get $n)))
+        (call $v (i64x2.shr_s (local.get $a) (local.get $n)))
+
+        ;; selection between vectors
+        (call $v (select (local.get $a) (local.get $b) (i32.and (local.get $n) (i32.const 1))))
+
+        ;; operators lowered inline, with loads and stores at various offsets
+        (call $v (v128.load8x8_s offset=3 (i32.const 0)))
+        (call $v (v128.load8x8_u offset=5 (i32.const 0)))
+        (call $v (v128.load16x4_s offset=7 (i32.const 0)))
+        (call $v (v128.load16x4_u offset=9 (i32.const 0)))
+        (call $v (v128.load32x2_s offset=11 (i32.const 0)))
+        (call $v (v128.load32x2_u offset=13 (i32.const 0)))
+        (call $v (v128.load8_splat offset=17 (i32.const 0)))
+        (call $v (v128.load16_splat offset=19 (i32.const 0)))
+        (call $v (v128.load32_splat offset=21 (i32.const 0)))
+        (call $v (v128.load64_splat offset=23 (i32.const 0)))
+        (call $v (v128.load32_zero offset=25 (i32.const 0)))
+        (call $v (v128.load64_zero offset=27 (i32.const 0)))
+        (v128.store offset=3 (i32.const 0xf0) (local.get $c))
+        (v128.store8_lane offset=1 11 (i32.const 0xf0) (local.get $a))
+        (v128.store16_lane offset=4 3 (i32.const 0xf0) (local.get $a))
+        (v128.store32_lane offset=6 2 (i32.const 0xf0) (local.get $b))
+        (v128.store64_lane offset=9 1 (i32.const 0xf0) (local.get $b))
+        (call $v (v128.load (i32.const 0xf0)))
+        (call $v (v128.load offset=3 (i32.const 0xf0)))
+
+        ;; float lanes, which share the bits of the integer lanes
+        (call $i (i32.reinterpret_f32 (f32x4.extract_lane 1 (local.get $a))))
+        (call $i (i32.wrap_i64 (i64.reinterpret_f64 (f64x2.extract_lane 1 (local.get $b)))))
+        (call $v (f32x4.replace_lane 2 (local.get $a) (f32.load (i32.const 44))))
+        (call $v (f64x2.replace_lane 0 (local.get $a) (f64.load (i32.const 40))))
+        (call $v (f32x4.splat (f32.load (i32.const 44))))
+        (call $v (f64x2.splat (f64.load (i32.const 40))))
+
+        ;; blocks, tees, and drops of vectors
+        (call $v (call $pick (local.get $a) (local.get $b) (local.get $n)))
+        (call $v (call $pick (local.get $c) (local.get $a) (i32.xor (local.get $n) (i32.const 6))))
+
+        (call $write_result (i32.const 0x100) (i32.sub (global.get $out) (i32.const 0x100)))
+        i32.const 0
+    )
+)
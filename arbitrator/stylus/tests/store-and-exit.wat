@@ -0,0 +1,24 @@
+;; Copyright 2024, Offchain Labs, Inc.
+;; For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+(module
+    (import "vm_hooks" "read_args"             (func $read_args   (param i32)))
+    (import "vm_hooks" "storage_cache_bytes32" (func $cache       (param i32 i32)))
+    (import "vm_hooks" "storage_flush_cache"   (func $flush       (param i32)))
+    (import "vm_hooks" "exit_early"            (func $exit        (param i32)))
+    (memory (export "memory") 1 1)
+    (func (export "user_entrypoint") (param $args_len i32) (result i32)
+        ;; write the key, value, and status to offsets 0, 32, and 64
+        (call $read_args (i32.const 0))
+
+        ;; store the value at the key and persist it
+        (call $cache (i32.const 0) (i32.const 32))
+        (call $flush (i32.const 0))
+
+        ;; exit with the status code
+        (call $exit (i32.load8_u (i32.const 64)))
+
+        ;; unreachable
+        (i32.const 0xff)
+    )
+)
@@ -0,0 +1,24 @@
+;; Copyright 2024, Offchain Labs, Inc.
+;; For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
+
+(module
+    (import "vm_hooks" "read_args"               (func $read_args    (param i32)))
+    (import "vm_hooks" "write_result"            (func $write_result (param i32 i32)))
+    (import "vm_hooks" "transient_load_bytes32"  (func $tload        (param i32 i32)))
+    (import "vm_hooks" "transient_store_bytes32" (func $tstore       (param i32 i32)))
+    (memory (export "memory") 1 1)
+    (func (export "user_entrypoint") (param $args_len i32) (result i32)
+        ;; write the key and value to offsets 0 and 32
+        (call $read_args (i32.const 0))
+
+        ;; store the value at the key
+        (call $tstore (i32.const 0) (i32.const 32))
+
+        ;; load the value back into offset 64
+        (call $tload (i32.const 0) (i32.const 64))
+
+        ;; return what was loaded
+        (call $write_result (i32.const 64) (i32.const 32))
+        i32.const 0
+    )
+)
@@ -6,7 +6,7 @@ use std::{fs::File, io::Write, path::PathBuf};
 use structopt::StructOpt;
 
 /// order matters!
-const HOSTIOS: [[&str; 3]; 35] = [
+const HOSTIOS: [[&str; 3]; 42] = [
     ["read_args", "i32", ""],
     ["write_result", "i32 i32", ""],
     ["exit_early", "i32", ""],
@@ -42,6 +42,13 @@ const HOSTIOS: [[&str; 3]; 35] = [
     ["tx_ink_price", "", "i32"],
     ["tx_origin", "i32", ""],
     ["pay_for_memory_grow", "i32", ""],
+    ["transient_load_bytes32", "i32 i32", ""],
+    ["transient_store_bytes32", "i32 i32", ""],
+    ["block_hash", "i64 i32", ""],
+    ["blob_hash", "i32 i32", ""],
+    ["block_prevrandao", "i32", ""],
+    ["block_blob_basefee", "i32", ""],
+    ["read_args_range", "i32 i32 i32", "i32"],
 ];
 
 #[derive(StructOpt)]
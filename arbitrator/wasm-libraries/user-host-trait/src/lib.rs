@@ -68,6 +68,9 @@ pub trait UserHost<DR: DataReader>: GasMeteredMachine {
     fn evm_data(&self) -> &EvmData;
     fn evm_return_data_len(&mut self) -> &mut u32;
 
+    /// The Stylus version the program was activated against.
+    fn stylus_version(&self) -> u16;
+
     fn read_slice(&self, ptr: GuestPtr, len: u32) -> Result<Vec<u8>, Self::MemoryErr>;
     fn read_fixed<const N: usize>(&self, ptr: GuestPtr) -> Result<[u8; N], Self::MemoryErr>;
 
@@ -104,6 +107,43 @@ pub trait UserHost<DR: DataReader>: GasMeteredMachine {
         trace!("read_args", self, &[], self.args())
     }
 
+    /// Copies a window of the program calldata. Does not revert if out of bounds, but rather
+    /// copies the overlapping portion. The semantics are otherwise equivalent to that of the
+    /// EVM's [`CALLDATA_COPY`] opcode, aside from the write to `dest` stopping after the last
+    /// byte is copied rather than right padding with zeros.
+    ///
+    /// Returns the number of bytes written.
+    ///
+    /// [`CALLDATA_COPY`]: https://www.evm.codes/#37
+    fn read_args_range(
+        &mut self,
+        dest: GuestPtr,
+        offset: u32,
+        size: u32,
+    ) -> Result<u32, Self::Err> {
+        self.buy_ink(HOSTIO_INK)?;
+
+        // pay for only as many bytes as could possibly be written
+        let max = (self.args().len() as u32).saturating_sub(offset);
+        self.pay_for_write(size.min(max))?;
+
+        let args = self.args();
+        let end = offset.saturating_add(size);
+        let out_slice = arbutil::slice_with_runoff(&args, offset, end);
+
+        let out_len = out_slice.len() as u32;
+        if out_len > 0 {
+            self.write_slice(dest, out_slice)?;
+        }
+        trace!(
+            "read_args_range",
+            self,
+            [be!(offset), be!(size)],
+            arbutil::slice_with_runoff(&self.args(), offset, end),
+            out_len
+        )
+    }
+
     /// Writes the final return data. If not called before the program exists, the return data will
     /// be 0 bytes long. Note that this hostio does not cause the program to exit, which happens
     /// naturally when `user_entrypoint` returns.
@@ -185,6 +225,44 @@ pub trait UserHost<DR: DataReader>: GasMeteredMachine {
         trace!("storage_flush_cache", self, [be!(clear as u8)], &[])
     }
 
+    /// Reads a 32-byte value from transient storage. Stylus's storage format is identical to
+    /// that of the EVM. This means that, under the hood, this hostio is accessing the 32-byte
+    /// value stored in transient storage at offset `key`, which will be `0` when not previously
+    /// set during the current transaction. The semantics, then, are equivalent to that of the
+    /// EVM's [`TLOAD`] opcode.
+    ///
+    /// [`TLOAD`]: https://www.evm.codes/#5c
+    fn transient_load_bytes32(&mut self, key: GuestPtr, dest: GuestPtr) -> Result<(), Self::Err> {
+        self.buy_ink(HOSTIO_INK + 2 * PTR_INK + EVM_API_INK)?;
+        self.require_gas(evm::TLOAD_GAS)?;
+
+        let key = self.read_bytes32(key)?;
+
+        let (value, gas_cost) = self.evm_api().get_transient_bytes32(key);
+        self.buy_gas(gas_cost)?;
+        self.write_bytes32(dest, value)?;
+        trace!("transient_load_bytes32", self, key, value)
+    }
+
+    /// Writes a 32-byte value to transient storage. Stylus's storage format is identical to that
+    /// of the EVM. This means that, under the hood, this hostio represents storing a 32-byte value
+    /// into transient storage at offset `key`, which is discarded at the end of the transaction.
+    /// Unlike `storage_cache_bytes32`, the value is written immediately and need not be flushed.
+    /// The semantics are equivalent to that of the EVM's [`TSTORE`] opcode.
+    ///
+    /// [`TSTORE`]: https://www.evm.codes/#5d
+    fn transient_store_bytes32(&mut self, key: GuestPtr, value: GuestPtr) -> Result<(), Self::Err> {
+        self.buy_ink(HOSTIO_INK + 2 * PTR_INK + EVM_API_INK)?;
+        self.require_gas(evm::TSTORE_GAS)?;
+
+        let key = self.read_bytes32(key)?;
+        let value = self.read_bytes32(value)?;
+
+        let gas_cost = self.evm_api().set_transient_bytes32(key, value)?;
+        self.buy_gas(gas_cost)?;
+        trace!("transient_store_bytes32", self, [key, value], &[])
+    }
+
     /// Calls the contract at the given address with options for passing value and to limit the
     /// amount of gas supplied. The return status indicates whether the call succeeded, and is
     /// nonzero on failure.
@@ -554,11 +632,14 @@ pub trait UserHost<DR: DataReader>: GasMeteredMachine {
         let (code, gas_cost) = self.evm_api().account_code(address, gas);
         self.buy_gas(gas_cost)?;
 
+        // since v2, pay for only the bytes actually copied
         let code = code.slice();
-        self.pay_for_write(code.len() as u32)?;
-
         let out_slice = arbutil::slice_with_runoff(&code, offset, offset.saturating_add(size));
         let out_len = out_slice.len() as u32;
+        match self.stylus_version() {
+            0 | 1 => self.pay_for_write(code.len() as u32)?,
+            _ => self.pay_for_write(out_len)?,
+        }
         self.write_slice(dest, out_slice)?;
 
         trace!(
@@ -605,6 +686,20 @@ pub trait UserHost<DR: DataReader>: GasMeteredMachine {
         trace!("account_codehash", self, address, hash)
     }
 
+    /// Gets the versioned hash of the current transaction's blob at the given index, or `0` if
+    /// there's no such blob. The semantics are equivalent to that of the EVM's [`BLOBHASH`] opcode.
+    ///
+    /// [`BLOBHASH`]: https://www.evm.codes/#49
+    fn blob_hash(&mut self, index: u32, dest: GuestPtr) -> Result<(), Self::Err> {
+        self.buy_ink(HOSTIO_INK + PTR_INK + EVM_API_INK)?;
+        self.require_gas(evm::BLOBHASH_GAS)?;
+
+        let (hash, gas_cost) = self.evm_api().blob_hash(index);
+        self.buy_gas(gas_cost)?;
+        self.write_bytes32(dest, hash)?;
+        trace!("blob_hash", self, be!(index), hash)
+    }
+
     /// Gets the basefee of the current block. The semantics are equivalent to that of the EVM's
     /// [`BASEFEE`] opcode.
     ///
@@ -615,6 +710,21 @@ pub trait UserHost<DR: DataReader>: GasMeteredMachine {
         trace!("block_basefee", self, &[], self.evm_data().block_basefee)
     }
 
+    /// Gets the blob basefee of the current block. The semantics are equivalent to that of the
+    /// EVM's [`BLOBBASEFEE`] opcode.
+    ///
+    /// [`BLOBBASEFEE`]: https://www.evm.codes/#4a
+    fn block_blob_basefee(&mut self, ptr: GuestPtr) -> Result<(), Self::Err> {
+        self.buy_ink(HOSTIO_INK + PTR_INK)?;
+        self.write_bytes32(ptr, self.evm_data().block_blob_basefee)?;
+        trace!(
+            "block_blob_basefee",
+            self,
+            &[],
+            self.evm_data().block_blob_basefee
+        )
+    }
+
     /// Gets the coinbase of the current block, which on Arbitrum chains is the L1 batch poster's
     /// address. This differs from Ethereum where the validator including the transaction
     /// determines the coinbase.
@@ -637,6 +747,22 @@ pub trait UserHost<DR: DataReader>: GasMeteredMachine {
         trace!("block_gas_limit", self, &[], be!(limit), limit)
     }
 
+    /// Gets the hash of the given block, which must be one of the 256 most recent complete blocks.
+    /// Otherwise the hash will be `0`. The semantics are equivalent to that of the EVM's
+    /// [`BLOCKHASH`] opcode. Note that block numbers are those of the L1, as with `block_number`,
+    /// and that the hashes returned are not a secure source of randomness.
+    ///
+    /// [`BLOCKHASH`]: https://www.evm.codes/#40
+    fn block_hash(&mut self, number: u64, dest: GuestPtr) -> Result<(), Self::Err> {
+        self.buy_ink(HOSTIO_INK + PTR_INK + EVM_API_INK)?;
+        self.require_gas(evm::BLOCKHASH_GAS)?;
+
+        let (hash, gas_cost) = self.evm_api().block_hash(number);
+        self.buy_gas(gas_cost)?;
+        self.write_bytes32(dest, hash)?;
+        trace!("block_hash", self, be!(number), hash)
+    }
+
     /// Gets a bounded estimate of the L1 block number at which the Sequencer sequenced the
     /// transaction. See [`Block Numbers and Time`] for more information on how this value is
     /// determined.
@@ -648,6 +774,22 @@ pub trait UserHost<DR: DataReader>: GasMeteredMachine {
         trace!("block_number", self, &[], be!(number), number)
     }
 
+    /// Gets the randomness beacon of the current block. The semantics are equivalent to that of
+    /// the EVM's [`PREVRANDAO`] opcode. Note that on Arbitrum chains this value is not random,
+    /// and so must not be used as a source of entropy.
+    ///
+    /// [`PREVRANDAO`]: https://www.evm.codes/#44
+    fn block_prevrandao(&mut self, ptr: GuestPtr) -> Result<(), Self::Err> {
+        self.buy_ink(HOSTIO_INK + PTR_INK)?;
+        self.write_bytes32(ptr, self.evm_data().block_prevrandao)?;
+        trace!(
+            "block_prevrandao",
+            self,
+            &[],
+            self.evm_data().block_prevrandao
+        )
+    }
+
     /// Gets a bounded estimate of the Unix timestamp at which the Sequencer sequenced the
     /// transaction. See [`Block Numbers and Time`] for more information on how this value is
     /// determined.
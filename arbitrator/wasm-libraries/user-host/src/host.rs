@@ -28,6 +28,11 @@ pub unsafe extern "C" fn user_host__read_args(ptr: GuestPtr) {
     hostio!(read_args(ptr))
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn user_host__read_args_range(dest: GuestPtr, offset: u32, size: u32) -> u32 {
+    hostio!(read_args_range(dest, offset, size))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn user_host__exit_early(status: u32) {
     hostio!(exit_early(status));
@@ -58,6 +63,16 @@ pub unsafe extern "C" fn user_host__storage_flush_cache(clear: u32) {
     hostio!(storage_flush_cache(clear != 0))
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn user_host__transient_load_bytes32(key: GuestPtr, dest: GuestPtr) {
+    hostio!(transient_load_bytes32(key, dest))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn user_host__transient_store_bytes32(key: GuestPtr, value: GuestPtr) {
+    hostio!(transient_store_bytes32(key, value))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn user_host__call_contract(
     contract: GuestPtr,
@@ -160,11 +175,21 @@ pub unsafe extern "C" fn user_host__account_codehash(address: GuestPtr, ptr: Gue
     hostio!(account_codehash(address, ptr))
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn user_host__blob_hash(index: u32, dest: GuestPtr) {
+    hostio!(blob_hash(index, dest))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn user_host__block_basefee(ptr: GuestPtr) {
     hostio!(block_basefee(ptr))
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn user_host__block_blob_basefee(ptr: GuestPtr) {
+    hostio!(block_blob_basefee(ptr))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn user_host__block_coinbase(ptr: GuestPtr) {
     hostio!(block_coinbase(ptr))
@@ -175,11 +200,21 @@ pub unsafe extern "C" fn user_host__block_gas_limit() -> u64 {
     hostio!(block_gas_limit())
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn user_host__block_hash(number: u64, dest: GuestPtr) {
+    hostio!(block_hash(number, dest))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn user_host__block_number() -> u64 {
     hostio!(block_number())
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn user_host__block_prevrandao(ptr: GuestPtr) {
+    hostio!(block_prevrandao(ptr))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn user_host__block_timestamp() -> u64 {
     hostio!(block_timestamp())
@@ -239,10 +239,12 @@ pub unsafe extern "C" fn programs__create_stylus_config(
 #[no_mangle]
 pub unsafe extern "C" fn programs__create_evm_data(
     block_basefee_ptr: GuestPtr,
+    block_blob_basefee_ptr: GuestPtr,
     chainid: u64,
     block_coinbase_ptr: GuestPtr,
     block_gas_limit: u64,
     block_number: u64,
+    block_prevrandao_ptr: GuestPtr,
     block_timestamp: u64,
     contract_address_ptr: GuestPtr,
     msg_sender_ptr: GuestPtr,
@@ -253,10 +255,12 @@ pub unsafe extern "C" fn programs__create_evm_data(
 ) -> u64 {
     let evm_data = EvmData {
         block_basefee: read_bytes32(block_basefee_ptr),
+        block_blob_basefee: read_bytes32(block_blob_basefee_ptr),
         chainid,
         block_coinbase: read_bytes20(block_coinbase_ptr),
         block_gas_limit,
         block_number,
+        block_prevrandao: read_bytes32(block_prevrandao_ptr),
         block_timestamp,
         contract_address: read_bytes20(contract_address_ptr),
         msg_sender: read_bytes20(msg_sender_ptr),
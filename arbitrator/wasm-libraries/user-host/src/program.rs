@@ -236,6 +236,10 @@ impl UserHost<VecReader> for Program {
         &mut self.evm_data.return_data_len
     }
 
+    fn stylus_version(&self) -> u16 {
+        self.config.version
+    }
+
     fn read_slice(&self, ptr: GuestPtr, len: u32) -> Result<Vec<u8>, MemoryBoundsError> {
         self.check_memory_access(ptr, len)?;
         unsafe { Ok(STATIC_MEM.read_slice(ptr, len as usize)) }
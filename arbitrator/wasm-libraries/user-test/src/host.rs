@@ -19,6 +19,11 @@ pub unsafe extern "C" fn vm_hooks__read_args(ptr: GuestPtr) {
     hostio!(read_args(ptr))
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn vm_hooks__read_args_range(dest: GuestPtr, offset: u32, size: u32) -> u32 {
+    hostio!(read_args_range(dest, offset, size))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn vm_hooks__exit_early(status: u32) {
     hostio!(exit_early(status));
@@ -44,6 +49,16 @@ pub unsafe extern "C" fn vm_hooks__storage_flush_cache(clear: u32) {
     hostio!(storage_flush_cache(clear != 0))
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn vm_hooks__transient_load_bytes32(key: GuestPtr, dest: GuestPtr) {
+    hostio!(transient_load_bytes32(key, dest))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn vm_hooks__transient_store_bytes32(key: GuestPtr, value: GuestPtr) {
+    hostio!(transient_store_bytes32(key, value))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn vm_hooks__call_contract(
     contract: GuestPtr,
@@ -142,11 +157,21 @@ pub unsafe extern "C" fn vm_hooks__account_codehash(address: GuestPtr, ptr: Gues
     hostio!(account_codehash(address, ptr))
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn vm_hooks__blob_hash(index: u32, dest: GuestPtr) {
+    hostio!(blob_hash(index, dest))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn vm_hooks__block_basefee(ptr: GuestPtr) {
     hostio!(block_basefee(ptr))
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn vm_hooks__block_blob_basefee(ptr: GuestPtr) {
+    hostio!(block_blob_basefee(ptr))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn vm_hooks__block_coinbase(ptr: GuestPtr) {
     hostio!(block_coinbase(ptr))
@@ -157,11 +182,21 @@ pub unsafe extern "C" fn vm_hooks__block_gas_limit() -> u64 {
     hostio!(block_gas_limit())
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn vm_hooks__block_hash(number: u64, dest: GuestPtr) {
+    hostio!(block_hash(number, dest))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn vm_hooks__block_number() -> u64 {
     hostio!(block_number())
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn vm_hooks__block_prevrandao(ptr: GuestPtr) {
+    hostio!(block_prevrandao(ptr))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn vm_hooks__block_timestamp() -> u64 {
     hostio!(block_timestamp())
@@ -19,10 +19,11 @@ pub(crate) static mut LOGS: Vec<Vec<u8>> = vec![];
 pub(crate) static mut CONFIG: Option<StylusConfig> = None;
 pub(crate) static mut OPEN_PAGES: u16 = 0;
 pub(crate) static mut EVER_PAGES: u16 = 0;
+pub(crate) static mut EVM_DATA: Option<EvmData> = None;
 
 lazy_static! {
     static ref KEYS: Mutex<HashMap<Bytes32, Bytes32>> = Mutex::new(HashMap::default());
-    static ref EVM_DATA: EvmData = EvmData::default();
+    static ref TRANSIENT: Mutex<HashMap<Bytes32, Bytes32>> = Mutex::new(HashMap::default());
 }
 
 #[no_mangle]
@@ -38,6 +39,39 @@ pub unsafe extern "C" fn user_test__prepare(
     ARGS.as_ptr()
 }
 
+/// Sets the block context programs see, which is zeroed until then.
+/// Each word is passed as four big-endian limbs, so callers needn't know `EvmData`'s layout.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn user_test__set_block(
+    number: u64,
+    prevrandao_0: u64,
+    prevrandao_1: u64,
+    prevrandao_2: u64,
+    prevrandao_3: u64,
+    blob_basefee_0: u64,
+    blob_basefee_1: u64,
+    blob_basefee_2: u64,
+    blob_basefee_3: u64,
+) {
+    let word = |limbs: [u64; 4]| {
+        let mut word = [0; 32];
+        for (chunk, limb) in word.chunks_mut(8).zip(limbs) {
+            chunk.copy_from_slice(&limb.to_be_bytes());
+        }
+        Bytes32::from(word)
+    };
+    let evm_data = EVM_DATA.get_or_insert_with(EvmData::default);
+    evm_data.block_number = number;
+    evm_data.block_prevrandao = word([prevrandao_0, prevrandao_1, prevrandao_2, prevrandao_3]);
+    evm_data.block_blob_basefee = word([
+        blob_basefee_0,
+        blob_basefee_1,
+        blob_basefee_2,
+        blob_basefee_3,
+    ]);
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn user_test__set_pages(pages: u16) {
     OPEN_PAGES = OPEN_PAGES.saturating_add(pages);
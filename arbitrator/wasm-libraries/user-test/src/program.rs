@@ -1,9 +1,10 @@
 // Copyright 2022-2024, Offchain Labs, Inc.
 // For license information, see https://github.com/OffchainLabs/nitro/blob/master/LICENSE
 
-use crate::{ARGS, EVER_PAGES, EVM_DATA, KEYS, LOGS, OPEN_PAGES, OUTS};
+use crate::{ARGS, CONFIG, EVER_PAGES, EVM_DATA, KEYS, LOGS, OPEN_PAGES, OUTS, TRANSIENT};
 use arbutil::{
     evm::{
+        self,
         api::{EvmApi, VecReader},
         user::UserOutcomeKind,
         EvmData,
@@ -49,13 +50,17 @@ impl UserHost<VecReader> for Program {
     }
 
     fn evm_data(&self) -> &EvmData {
-        &EVM_DATA
+        unsafe { EVM_DATA.get_or_insert_with(EvmData::default) }
     }
 
     fn evm_return_data_len(&mut self) -> &mut u32 {
         unimplemented!()
     }
 
+    fn stylus_version(&self) -> u16 {
+        unsafe { CONFIG.unwrap().version }
+    }
+
     fn read_slice(&self, ptr: GuestPtr, len: u32) -> Result<Vec<u8>, MemoryBoundsError> {
         self.check_memory_access(ptr, len)?;
         unsafe { Ok(STATIC_MEM.read_slice(ptr, len as usize)) }
@@ -116,6 +121,16 @@ impl EvmApi<VecReader> for MockEvmApi {
         Ok(22100 * KEYS.lock().len() as u64) // pretend worst case
     }
 
+    fn get_transient_bytes32(&mut self, key: Bytes32) -> (Bytes32, u64) {
+        let value = TRANSIENT.lock().get(&key).cloned().unwrap_or_default();
+        (value, evm::TLOAD_GAS)
+    }
+
+    fn set_transient_bytes32(&mut self, key: Bytes32, value: Bytes32) -> Result<u64> {
+        TRANSIENT.lock().insert(key, value);
+        Ok(evm::TSTORE_GAS)
+    }
+
     /// Simulates a contract call.
     /// Note: this call function is for testing purposes only and deviates from onchain behavior.
     fn contract_call(
@@ -186,6 +201,15 @@ impl EvmApi<VecReader> for MockEvmApi {
         unimplemented!()
     }
 
+    fn block_hash(&mut self, number: u64) -> (Bytes32, u64) {
+        let current = unsafe { EVM_DATA.map(|x| x.block_number).unwrap_or_default() };
+        (evm::mock::block_hash(number, current), evm::BLOCKHASH_GAS)
+    }
+
+    fn blob_hash(&mut self, index: u32) -> (Bytes32, u64) {
+        (evm::mock::blob_hash(index), evm::BLOBHASH_GAS)
+    }
+
     fn add_pages(&mut self, pages: u16) -> u64 {
         let model = MemoryModel::new(2, 1000);
         unsafe {
@@ -36,6 +36,10 @@ const (
 	AccountCodeHash
 	AddPages
 	CaptureHostIO
+	GetTransientBytes32
+	SetTransientBytes32
+	BlockHash
+	BlobHash
 )
 
 type apiStatus uint8
@@ -96,6 +100,16 @@ func newApiClosures(
 		}
 		return Success
 	}
+	getTransientBytes32 := func(key common.Hash) (common.Hash, uint64) {
+		return db.GetTransientState(actingAddress, key), params.WarmStorageReadCostEIP2929
+	}
+	setTransientBytes32 := func(key, value common.Hash) apiStatus {
+		if readOnly {
+			return WriteProtection
+		}
+		db.SetTransientState(actingAddress, key, value)
+		return Success
+	}
 	doCall := func(
 		contract common.Address, opcode vm.OpCode, input []byte, gas uint64, value *big.Int,
 	) ([]byte, uint64, error) {
@@ -251,6 +265,30 @@ func newApiClosures(
 		cost := vm.WasmAccountTouchCost(evm.StateDB, address, false)
 		return evm.StateDB.GetCodeHash(address), cost
 	}
+	blockHash := func(number uint64) (common.Hash, uint64) {
+		// Block numbers are those of the L1, matching the EVM's opBlockhash on Arbitrum chains.
+		cost := vm.GasExtStep
+		upper, err := evm.ProcessingHook.L1BlockNumber(evm.Context)
+		if err != nil {
+			return common.Hash{}, cost
+		}
+		lower := am.SaturatingUSub(upper, 256)
+		if number < lower || number >= upper {
+			return common.Hash{}, cost
+		}
+		hash, err := evm.ProcessingHook.L1BlockHash(evm.Context, number)
+		if err != nil {
+			return common.Hash{}, cost
+		}
+		return hash, cost
+	}
+	blobHash := func(index uint32) (common.Hash, uint64) {
+		cost := vm.GasFastestStep
+		if uint64(index) >= uint64(len(evm.TxContext.BlobHashes)) {
+			return common.Hash{}, cost
+		}
+		return evm.TxContext.BlobHashes[index], cost
+	}
 	addPages := func(pages uint16) uint64 {
 		open, ever := db.AddStylusPages(pages)
 		return memoryModel.GasCost(pages, open, ever)
@@ -316,6 +354,15 @@ func newApiClosures(
 			gas := gasLeft
 			status := setTrieSlots(takeRest(), &gas)
 			return status.to_slice(), nil, gasLeft - gas
+		case GetTransientBytes32:
+			key := takeHash()
+			out, cost := getTransientBytes32(key)
+			return out[:], nil, cost
+		case SetTransientBytes32:
+			key := takeHash()
+			value := takeHash()
+			status := setTransientBytes32(key, value)
+			return status.to_slice(), nil, params.WarmStorageReadCostEIP2929
 		case ContractCall, DelegateCall, StaticCall:
 			var opcode vm.OpCode
 			switch req {
@@ -380,6 +427,14 @@ func newApiClosures(
 			address := takeAddress()
 			codeHash, cost := accountCodehash(address)
 			return codeHash[:], nil, cost
+		case BlockHash:
+			number := takeU64()
+			hash, cost := blockHash(number)
+			return hash[:], nil, cost
+		case BlobHash:
+			index := takeU32()
+			hash, cost := blobHash(index)
+			return hash[:], nil, cost
 		case AddPages:
 			pages := takeU16()
 			cost := addPages(pages)
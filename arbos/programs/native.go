@@ -208,19 +208,21 @@ func (params *goParams) encode() C.StylusConfig {
 
 func (data *evmData) encode() C.EvmData {
 	return C.EvmData{
-		block_basefee:    hashToBytes32(data.blockBasefee),
-		chainid:          u64(data.chainId),
-		block_coinbase:   addressToBytes20(data.blockCoinbase),
-		block_gas_limit:  u64(data.blockGasLimit),
-		block_number:     u64(data.blockNumber),
-		block_timestamp:  u64(data.blockTimestamp),
-		contract_address: addressToBytes20(data.contractAddress),
-		msg_sender:       addressToBytes20(data.msgSender),
-		msg_value:        hashToBytes32(data.msgValue),
-		tx_gas_price:     hashToBytes32(data.txGasPrice),
-		tx_origin:        addressToBytes20(data.txOrigin),
-		reentrant:        u32(data.reentrant),
-		return_data_len:  0,
-		tracing:          cbool(data.tracing),
+		block_basefee:      hashToBytes32(data.blockBasefee),
+		block_blob_basefee: hashToBytes32(data.blockBlobBasefee),
+		chainid:            u64(data.chainId),
+		block_coinbase:     addressToBytes20(data.blockCoinbase),
+		block_gas_limit:    u64(data.blockGasLimit),
+		block_number:       u64(data.blockNumber),
+		block_prevrandao:   hashToBytes32(data.blockPrevRandao),
+		block_timestamp:    u64(data.blockTimestamp),
+		contract_address:   addressToBytes20(data.contractAddress),
+		msg_sender:         addressToBytes20(data.msgSender),
+		msg_value:          hashToBytes32(data.msgValue),
+		tx_gas_price:       hashToBytes32(data.txGasPrice),
+		tx_origin:          addressToBytes20(data.txOrigin),
+		reentrant:          u32(data.reentrant),
+		return_data_len:    0,
+		tracing:            cbool(data.tracing),
 	}
 }
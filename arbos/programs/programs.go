@@ -173,20 +173,31 @@ func (p Programs) CallProgram(
 	statedb.AddStylusPages(program.footprint)
 	defer statedb.SetStylusPagesOpen(open)
 
+	blobBasefee := common.Hash{}
+	if evm.Context.BlobBaseFee != nil {
+		blobBasefee = common.BigToHash(evm.Context.BlobBaseFee)
+	}
+	prevRandao := common.BigToHash(evm.Context.Difficulty)
+	if evm.Context.Random != nil {
+		prevRandao = *evm.Context.Random
+	}
+
 	evmData := &evmData{
-		blockBasefee:    common.BigToHash(evm.Context.BaseFee),
-		chainId:         evm.ChainConfig().ChainID.Uint64(),
-		blockCoinbase:   evm.Context.Coinbase,
-		blockGasLimit:   evm.Context.GasLimit,
-		blockNumber:     l1BlockNumber,
-		blockTimestamp:  evm.Context.Time,
-		contractAddress: scope.Contract.Address(),
-		msgSender:       scope.Contract.Caller(),
-		msgValue:        common.BigToHash(scope.Contract.Value()),
-		txGasPrice:      common.BigToHash(evm.TxContext.GasPrice),
-		txOrigin:        evm.TxContext.Origin,
-		reentrant:       arbmath.BoolToUint32(reentrant),
-		tracing:         tracingInfo != nil,
+		blockBasefee:     common.BigToHash(evm.Context.BaseFee),
+		blockBlobBasefee: blobBasefee,
+		chainId:          evm.ChainConfig().ChainID.Uint64(),
+		blockCoinbase:    evm.Context.Coinbase,
+		blockGasLimit:    evm.Context.GasLimit,
+		blockNumber:      l1BlockNumber,
+		blockPrevRandao:  prevRandao,
+		blockTimestamp:   evm.Context.Time,
+		contractAddress:  scope.Contract.Address(),
+		msgSender:        scope.Contract.Caller(),
+		msgValue:         common.BigToHash(scope.Contract.Value()),
+		txGasPrice:       common.BigToHash(evm.TxContext.GasPrice),
+		txOrigin:         evm.TxContext.Origin,
+		reentrant:        arbmath.BoolToUint32(reentrant),
+		tracing:          tracingInfo != nil,
 	}
 
 	address := contract.Address()
@@ -347,19 +358,21 @@ func (p Programs) goParams(version uint16, debug bool, params *StylusParams) *go
 }
 
 type evmData struct {
-	blockBasefee    common.Hash
-	chainId         uint64
-	blockCoinbase   common.Address
-	blockGasLimit   uint64
-	blockNumber     uint64
-	blockTimestamp  uint64
-	contractAddress common.Address
-	msgSender       common.Address
-	msgValue        common.Hash
-	txGasPrice      common.Hash
-	txOrigin        common.Address
-	reentrant       uint32
-	tracing         bool
+	blockBasefee     common.Hash
+	blockBlobBasefee common.Hash
+	chainId          uint64
+	blockCoinbase    common.Address
+	blockGasLimit    uint64
+	blockNumber      uint64
+	blockPrevRandao  common.Hash
+	blockTimestamp   uint64
+	contractAddress  common.Address
+	msgSender        common.Address
+	msgValue         common.Hash
+	txGasPrice       common.Hash
+	txOrigin         common.Address
+	reentrant        uint32
+	tracing          bool
 }
 
 type activationInfo struct {
@@ -61,7 +61,19 @@ func testConstants() error {
 	if err := errIfNotEq(14, CaptureHostIO, C.EvmApiMethod_CaptureHostIO); err != nil {
 		return err
 	}
-	if err := errIfNotEq(15, EvmApiMethodReqOffset, C.EVM_API_METHOD_REQ_OFFSET); err != nil {
+	if err := errIfNotEq(15, GetTransientBytes32, C.EvmApiMethod_GetTransientBytes32); err != nil {
+		return err
+	}
+	if err := errIfNotEq(16, SetTransientBytes32, C.EvmApiMethod_SetTransientBytes32); err != nil {
+		return err
+	}
+	if err := errIfNotEq(17, BlockHash, C.EvmApiMethod_BlockHash); err != nil {
+		return err
+	}
+	if err := errIfNotEq(18, BlobHash, C.EvmApiMethod_BlobHash); err != nil {
+		return err
+	}
+	if err := errIfNotEq(19, EvmApiMethodReqOffset, C.EVM_API_METHOD_REQ_OFFSET); err != nil {
 		return err
 	}
 
@@ -22,10 +22,12 @@ type evmDataHandler uint64
 //go:wasmimport programs create_evm_data
 func createEvmData(
 	blockBaseFee unsafe.Pointer,
+	blockBlobBaseFee unsafe.Pointer,
 	chainid uint64,
 	blockCoinbase unsafe.Pointer,
 	gasLimit uint64,
 	blockNumber uint64,
+	blockPrevRandao unsafe.Pointer,
 	blockTimestamp uint64,
 	contractAddress unsafe.Pointer,
 	msgSender unsafe.Pointer,
@@ -42,10 +44,12 @@ func (params *goParams) createHandler() stylusConfigHandler {
 func (data *evmData) createHandler() evmDataHandler {
 	return createEvmData(
 		arbutil.SliceToUnsafePointer(data.blockBasefee[:]),
+		arbutil.SliceToUnsafePointer(data.blockBlobBasefee[:]),
 		data.chainId,
 		arbutil.SliceToUnsafePointer(data.blockCoinbase[:]),
 		data.blockGasLimit,
 		data.blockNumber,
+		arbutil.SliceToUnsafePointer(data.blockPrevRandao[:]),
 		data.blockTimestamp,
 		arbutil.SliceToUnsafePointer(data.contractAddress[:]),
 		arbutil.SliceToUnsafePointer(data.msgSender[:]),